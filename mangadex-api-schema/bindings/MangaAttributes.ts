@@ -9,4 +9,4 @@ import type { MangaState } from "./MangaState";
 import type { MangaStatus } from "./MangaStatus";
 import type { TagAttributes } from "./TagAttributes";
 
-export interface MangaAttributes { title: Map<string, string>, altTitles: Array<Map<string, string>>, description: Map<string, string>, isLocked: boolean, links: MangaLinks | null, originalLanguage: Language, lastVolume: string | null, lastChapter: string | null, publicationDemographic: Demographic | null, status: MangaStatus, year: number | null, contentRating: ContentRating | null, chapterNumbersResetOnNewVolume: boolean, latestUploadedChapter: string, availableTranslatedLanguages: Array<Language>, tags: Array<ApiObject<TagAttributes>>, state: MangaState, createdAt: MangaDexDateTime, updatedAt: MangaDexDateTime | null, version: number, }
\ No newline at end of file
+export interface MangaAttributes { title: Map<string, string>, altTitles: Array<Map<string, string>>, description: Map<string, string>, isLocked: boolean, links: MangaLinks | null, originalLanguage: string, lastVolume: string | null, lastChapter: string | null, publicationDemographic: Demographic | null, status: MangaStatus, year: number | null, contentRating: ContentRating | null, chapterNumbersResetOnNewVolume: boolean, latestUploadedChapter: string, availableTranslatedLanguages: Array<Language>, tags: Array<ApiObject<TagAttributes>>, state: MangaState, createdAt: MangaDexDateTime, updatedAt: MangaDexDateTime | null, version: number, }
\ No newline at end of file
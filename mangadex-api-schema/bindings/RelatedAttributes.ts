@@ -6,6 +6,7 @@ import type { CustomListAttributes } from "./CustomListAttributes";
 import type { MangaAttributes } from "./MangaAttributes";
 import type { ScanlationGroupAttributes } from "./ScanlationGroupAttributes";
 import type { TagAttributes } from "./TagAttributes";
+import type { UploadSessionFileAttributes } from "./UploadSessionFileAttributes";
 import type { UserAttributes } from "./UserAttributes";
 
-export type RelatedAttributes = MangaAttributes | ChapterAttributes | CoverAttributes | AuthorAttributes | ScanlationGroupAttributes | TagAttributes | UserAttributes | CustomListAttributes;
\ No newline at end of file
+export type RelatedAttributes = MangaAttributes | ChapterAttributes | CoverAttributes | AuthorAttributes | ScanlationGroupAttributes | TagAttributes | UserAttributes | CustomListAttributes | UploadSessionFileAttributes | any;
\ No newline at end of file
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { UploadSource } from "./UploadSource";
+
+export interface UploadSessionFileAttributes { originalFileName: string, fileHash: string, fileSize: bigint, mimeType: string, source: UploadSource, version: number, }
\ No newline at end of file
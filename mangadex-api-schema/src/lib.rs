@@ -61,20 +61,33 @@ pub trait Endpoint {
     }
 }
 
-#[derive(Deserialize)]
-#[serde(tag = "result", remote = "std::result::Result")]
-enum ApiResultDef<T, E> {
-    #[serde(rename = "ok")]
-    Ok(T),
-    #[serde(rename = "error")]
-    Err(E),
-}
+pub struct ApiResult<T, E = MangaDexErrorResponse>(std::result::Result<T, E>);
 
-#[derive(Deserialize)]
-#[serde(bound = "T: DeserializeOwned, E: DeserializeOwned")]
-pub struct ApiResult<T, E = MangaDexErrorResponse>(
-    #[serde(with = "ApiResultDef")] std::result::Result<T, E>,
-);
+impl<'de, T, E> Deserialize<'de> for ApiResult<T, E>
+where
+    T: DeserializeOwned,
+    E: DeserializeOwned,
+{
+    /// Deserialize `T` or `E` according to the `result` field.
+    ///
+    /// The `result` field is left in the object, the response types hold it too.
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        let result = match value.get("result") {
+            Some(result) => ResultType::deserialize(result).map_err(serde::de::Error::custom)?,
+            None => return Err(serde::de::Error::missing_field("result")),
+        };
+        match result {
+            ResultType::Ok => T::deserialize(value).map(Ok),
+            ResultType::Error => E::deserialize(value).map(Err),
+        }
+        .map(Self)
+        .map_err(serde::de::Error::custom)
+    }
+}
 
 impl<T, E> ApiResult<T, E> {
     pub fn into_result(self) -> Result<T, E> {
@@ -182,3 +195,38 @@ where
     let opt = Option::deserialize(deserializer)?;
     Ok(opt.unwrap_or_default())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn api_results_keep_their_result_field() {
+        let res: ApiResult<ApiData<String>> =
+            serde_json::from_str(r#"{"result": "ok", "response": "entity", "data": "chapter"}"#)
+                .unwrap();
+        let data = res.into_result().unwrap();
+        assert_eq!(data.result, ResultType::Ok);
+        assert_eq!(data.data, "chapter");
+
+        let res: ApiResult<ApiData<String>> = serde_json::from_str(
+            r#"{
+                "result": "error",
+                "errors": [{
+                    "id": "f9a9e4b3-2c1b-4c8e-9b0a-6f5e2a1d3c4b",
+                    "status": 404,
+                    "title": "not_found_http_exception",
+                    "detail": "The chapter was not found"
+                }]
+            }"#,
+        )
+        .unwrap();
+        let errors = res.into_result().unwrap_err().errors;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].status, 404);
+
+        assert!(
+            serde_json::from_str::<ApiResult<ApiData<String>>>(r#"{"data": "chapter"}"#).is_err()
+        );
+    }
+}
@@ -10,6 +10,7 @@ pub mod error;
 mod exports_types;
 pub mod is_following_response;
 pub mod legacy_id_mapping;
+pub mod localized_string;
 pub mod login_response;
 pub mod manga;
 pub mod manga_aggregate;
@@ -31,23 +32,26 @@ pub mod user_report;
 pub mod user_settings;
 
 pub use self::exports_types::*;
-use std::collections::HashMap;
+pub use self::localized_string::{normalize_title, LocalizedString, TitleMatcher};
 
 use mangadex_api_types as types;
 use serde::Deserialize;
 use ts_rs::TS;
 use uuid::Uuid;
 
-use types::{Language, MangaRelation, RelationshipType, ResponseType, ResultType};
+use types::{MangaRelation, RelationshipType, ResponseType, ResultType};
 
 pub(crate) use crate::ApiObject;
 
 // TODO: Find a way to reduce the boilerplate for this.
 // `struct-variant` (https://docs.rs/struct-variant) is a potential candidate for this.
-#[derive(Debug, Deserialize, Clone, TS)]
+/// Attributes of an expanded [`Relationship`].
+///
+/// The variant is chosen from the relationship `type` field rather than by trying each variant in turn,
+/// see [`RelatedAttributes::from_value`].
+#[derive(Debug, Clone, TS)]
 #[allow(clippy::large_enum_variant)]
-#[serde(untagged)]
-#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize), serde(untagged))]
 #[cfg_attr(feature = "specta", derive(specta::Type))]
 #[ts(export)]
 pub enum RelatedAttributes {
@@ -63,39 +67,119 @@ pub enum RelatedAttributes {
     CoverArt(CoverAttributes),
     /// Author resource.
     Author(AuthorAttributes),
+    /// Author resource (drawers only).
+    ///
+    /// Skipped in the TypeScript bindings, it has the same shape as [`RelatedAttributes::Author`].
+    #[ts(skip)]
+    Artist(AuthorAttributes),
     /// ScanlationGroup resource.
     ScanlationGroup(ScanlationGroupAttributes),
     /// Tag resource.
     Tag(TagAttributes),
     /// User resource.
+    ///
+    /// This is also used for the `leader`, `member` and `creator` relationship types.
     User(UserAttributes),
     /// CustomList resource.
     CustomList(CustomListAttributes),
+    /// File of an upload session.
+    UploadSessionFile(UploadSessionFileAttributes),
+    /// Attributes of a relationship type that this library doesn't map (yet).
+    ///
+    /// The raw JSON object is kept as-is so that no data is lost.
+    Unknown(#[ts(type = "any")] serde_json::Value),
 }
 
-#[derive(Debug, Deserialize, Clone, TS)]
+impl RelatedAttributes {
+    /// Deserialize the attributes of a relationship from its `type`.
+    ///
+    /// Relationship types without a dedicated variant are kept as [`RelatedAttributes::Unknown`].
+    pub fn from_value(
+        type_: RelationshipType,
+        value: serde_json::Value,
+    ) -> Result<Self, serde_json::Error> {
+        Ok(match type_ {
+            RelationshipType::Manga => Self::Manga(serde_json::from_value(value)?),
+            RelationshipType::Chapter => Self::Chapter(serde_json::from_value(value)?),
+            RelationshipType::CoverArt => Self::CoverArt(serde_json::from_value(value)?),
+            RelationshipType::Author => Self::Author(serde_json::from_value(value)?),
+            RelationshipType::Artist => Self::Artist(serde_json::from_value(value)?),
+            RelationshipType::ScanlationGroup => {
+                Self::ScanlationGroup(serde_json::from_value(value)?)
+            }
+            RelationshipType::Tag => Self::Tag(serde_json::from_value(value)?),
+            RelationshipType::User
+            | RelationshipType::Leader
+            | RelationshipType::Member
+            | RelationshipType::Creator => Self::User(serde_json::from_value(value)?),
+            RelationshipType::CustomList => Self::CustomList(serde_json::from_value(value)?),
+            RelationshipType::UploadSessionFile => {
+                Self::UploadSessionFile(serde_json::from_value(value)?)
+            }
+            _ => Self::Unknown(value),
+        })
+    }
+}
+
+#[derive(Debug, Clone, TS)]
 #[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[cfg_attr(feature = "specta", derive(specta::Type))]
 #[ts(export)]
 pub struct Relationship {
     #[ts(type = "string")]
     pub id: Uuid,
-    #[serde(rename = "type")]
+    #[cfg_attr(feature = "serialize", serde(rename = "type"))]
+    #[ts(rename = "type")]
     pub type_: RelationshipType,
     /// Related Manga type.
     ///
     /// <https://api.mangadex.org/docs/static-data/#manga-related-enum>
     ///
     /// This is only present for a Manga entity and a Manga relationship.
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "serialize", serde(skip_serializing_if = "Option::is_none"))]
+    #[ts(optional)]
     pub related: Option<MangaRelation>,
     /// Contains object attributes for the type.
     ///
     /// Present if [Reference Expansion](https://api.mangadex.org/docs/reference-expansion/) is applied.
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "serialize", serde(skip_serializing_if = "Option::is_none"))]
+    #[ts(optional)]
     pub attributes: Option<RelatedAttributes>,
 }
 
+impl<'de> Deserialize<'de> for Relationship {
+    /// Deserialize the `attributes` field according to the sibling `type` field.
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct RelationshipHelper {
+            id: Uuid,
+            #[serde(rename = "type")]
+            type_: RelationshipType,
+            related: Option<MangaRelation>,
+            attributes: Option<serde_json::Value>,
+        }
+
+        let helper = RelationshipHelper::deserialize(deserializer)?;
+        let attributes = match helper.attributes {
+            Some(value) => Some(
+                RelatedAttributes::from_value(helper.type_, value)
+                    .map_err(serde::de::Error::custom)?,
+            ),
+            None => None,
+        };
+
+        Ok(Self {
+            id: helper.id,
+            type_: helper.type_,
+            related: helper.related,
+            attributes,
+        })
+    }
+}
+
 #[derive(Debug, Deserialize, Clone)]
 #[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[cfg_attr(feature = "specta", derive(specta::Type))]
@@ -108,16 +192,12 @@ pub struct Results<T> {
     pub total: u32,
 }
 
-pub type LocalizedString = HashMap<Language, String>;
-
 /// Originally a Deserializer helper to handle JSON array or object types.
 ///
 /// MangaDex currently returns an empty array when the localized string field isn't present.
 ///
 /// The Serializer was added in 0.2.0 for pratical and necessities reason
 pub(crate) mod localizedstring_array_or_map {
-    use std::collections::HashMap;
-
     use super::LocalizedString;
     use serde::de::{Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
     #[cfg(feature = "serialize")]
@@ -140,7 +220,7 @@ pub(crate) mod localizedstring_array_or_map {
             where
                 A: SeqAccess<'de>,
             {
-                Ok(HashMap::new())
+                Ok(LocalizedString::new())
             }
 
             fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
@@ -171,6 +251,7 @@ pub(crate) mod localizedstring_array_or_map {
 /// The Serializer was added in 0.2.0 for pratical and necessities reason
 pub(crate) mod volume_aggregate_array_or_map {
     use super::manga_aggregate::VolumeAggregate;
+    use mangadex_api_types::VolumeNumber;
     use serde::de::{Deserializer, MapAccess, SeqAccess, Visitor};
     #[cfg(feature = "serialize")]
     use serde::ser::Serializer;
@@ -182,8 +263,6 @@ pub(crate) mod volume_aggregate_array_or_map {
 
     type VolumeAggregateCollection = Vec<VolumeAggregate>;
 
-    const PAD_WIDTH: usize = 5;
-
     pub fn deserialize<'de, D>(deserializer: D) -> Result<VolumeAggregateCollection, D::Error>
     where
         D: Deserializer<'de>,
@@ -214,31 +293,13 @@ pub(crate) mod volume_aggregate_array_or_map {
             where
                 M: MapAccess<'de>,
             {
-                // Temporary collection to sort the results because serde doesn't seem to iterate
-                // through the map in the order they appear.
+                // Temporary collection to sort the results by number because serde doesn't seem
+                // to iterate through the map in the order they appear.
                 let mut sorting_map = BTreeMap::new();
 
                 while let Some((volume_number, volume)) =
-                    map.next_entry::<String, VolumeAggregate>()?
+                    map.next_entry::<VolumeNumber, VolumeAggregate>()?
                 {
-                    let volume_number = if volume_number.contains('.') {
-                        match volume_number.parse::<f64>() {
-                            Ok(_) => {
-                                //
-                                let (i, f) = volume_number.split_once('.').unwrap();
-                                let i = i.parse::<i32>().unwrap();
-                                // Pad the whole number part so that it is sorted correctly with the
-                                // other keys.
-                                format!("{i:0PAD_WIDTH$}.{f}")
-                            }
-                            Err(_) => volume_number,
-                        }
-                    } else {
-                        match volume_number.parse::<i32>() {
-                            Ok(n) => format!("{n:0PAD_WIDTH$}"),
-                            Err(_) => volume_number,
-                        }
-                    };
                     sorting_map.insert(volume_number, volume);
                 }
 
@@ -281,8 +342,7 @@ pub(crate) mod chapter_aggregate_array_or_map {
     use std::collections::BTreeMap;
 
     use super::manga_aggregate::ChapterAggregate;
-
-    const PAD_WIDTH: usize = 5;
+    use mangadex_api_types::ChapterNumber;
 
     type ChapterAggregateCollection = Vec<ChapterAggregate>;
 
@@ -316,31 +376,13 @@ pub(crate) mod chapter_aggregate_array_or_map {
             where
                 M: MapAccess<'de>,
             {
-                // Temporary collection to sort the results because serde doesn't seem to iterate
-                // through the map in the order they appear.
+                // Temporary collection to sort the results by number because serde doesn't seem
+                // to iterate through the map in the order they appear.
                 let mut sorting_map = BTreeMap::new();
 
                 while let Some((chapter_number, chapter)) =
-                    map.next_entry::<String, ChapterAggregate>()?
+                    map.next_entry::<ChapterNumber, ChapterAggregate>()?
                 {
-                    let chapter_number = if chapter_number.contains('.') {
-                        match chapter_number.parse::<f64>() {
-                            Ok(_) => {
-                                //
-                                let (i, f) = chapter_number.split_once('.').unwrap();
-                                let i = i.parse::<i32>().unwrap();
-                                // Pad the whole number part so that it is sorted correctly with the
-                                // other keys.
-                                format!("{i:0PAD_WIDTH$}.{f}")
-                            }
-                            Err(_) => chapter_number,
-                        }
-                    } else {
-                        match chapter_number.parse::<i32>() {
-                            Ok(n) => format!("{n:0PAD_WIDTH$}"),
-                            Err(_) => chapter_number,
-                        }
-                    };
                     sorting_map.insert(chapter_number, chapter);
                 }
 
@@ -524,3 +566,145 @@ pub(crate) mod language_array_or_skip_null {
         to_use.serialize(serializer)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn relationship_attributes_follow_the_type_field() {
+        let relationship: Relationship = serde_json::from_value(json!({
+            "id": "fc343004-569b-4750-aba0-05ab35efc17c",
+            "type": "artist",
+            "attributes": {
+                "name": "Hologfx",
+                "imageUrl": null,
+                "biography": [],
+                "createdAt": "2021-04-19T21:59:45+00:00",
+                "updatedAt": "2021-04-19T21:59:45+00:00",
+                "version": 1
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(relationship.type_, RelationshipType::Artist);
+        match relationship.attributes.unwrap() {
+            RelatedAttributes::Artist(artist) => assert_eq!(artist.name, "Hologfx"),
+            other => panic!("Expected artist RelatedAttributes, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn relationship_custom_list_is_not_decoded_as_user() {
+        let relationship: Relationship = serde_json::from_value(json!({
+            "id": "fc343004-569b-4750-aba0-05ab35efc17c",
+            "type": "custom_list",
+            "attributes": {
+                "name": "Favorites",
+                "visibility": "public",
+                "version": 1
+            }
+        }))
+        .unwrap();
+
+        match relationship.attributes.unwrap() {
+            RelatedAttributes::CustomList(list) => assert_eq!(list.name, "Favorites"),
+            other => panic!("Expected custom list RelatedAttributes, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn relationship_keeps_raw_attributes_for_unknown_types() {
+        let relationship: Relationship = serde_json::from_value(json!({
+            "id": "fc343004-569b-4750-aba0-05ab35efc17c",
+            "type": "something_new",
+            "attributes": {
+                "foo": "bar"
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(relationship.type_, RelationshipType::Unknown);
+        match relationship.attributes.unwrap() {
+            RelatedAttributes::Unknown(value) => assert_eq!(value, json!({ "foo": "bar" })),
+            other => panic!("Expected unknown RelatedAttributes, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn relationship_rejects_attributes_not_matching_the_type() {
+        let res = serde_json::from_value::<Relationship>(json!({
+            "id": "fc343004-569b-4750-aba0-05ab35efc17c",
+            "type": "user",
+            "attributes": {
+                "name": "Favorites",
+                "visibility": "public",
+                "version": 1
+            }
+        }));
+
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn relationship_without_attributes() {
+        let relationship: Relationship = serde_json::from_value(json!({
+            "id": "fc343004-569b-4750-aba0-05ab35efc17c",
+            "type": "manga",
+            "related": "sequel"
+        }))
+        .unwrap();
+
+        assert_eq!(relationship.related, Some(MangaRelation::Sequel));
+        assert!(relationship.attributes.is_none());
+    }
+
+    #[test]
+    fn manga_aggregate_is_ordered_by_number() {
+        let chapter = |number: &str| {
+            json!({
+                "chapter": number,
+                "id": "fc343004-569b-4750-aba0-05ab35efc17c",
+                "others": [],
+                "count": 1
+            })
+        };
+        let aggregate: manga_aggregate::MangaAggregate = serde_json::from_value(json!({
+            "result": "ok",
+            "volumes": {
+                "none": { "volume": "none", "count": 1, "chapters": { "none": chapter("none") } },
+                "10": { "volume": "10", "count": 1, "chapters": [] },
+                "2": {
+                    "volume": "2",
+                    "count": 3,
+                    "chapters": {
+                        "10.5": chapter("10.5"),
+                        "10": chapter("10"),
+                        "9": chapter("9")
+                    }
+                }
+            }
+        }))
+        .unwrap();
+
+        let volumes: Vec<&str> = aggregate
+            .volumes
+            .iter()
+            .map(|v| v.volume.as_str())
+            .collect();
+        assert_eq!(volumes, ["2", "10", "none"]);
+        let chapters: Vec<&str> = aggregate.volumes[0]
+            .chapters
+            .iter()
+            .map(|c| c.chapter.as_str())
+            .collect();
+        assert_eq!(chapters, ["9", "10", "10.5"]);
+        assert_eq!(
+            aggregate.volumes[0].chapters[2].number().as_f64(),
+            Some(10.5)
+        );
+        assert!(aggregate.volumes[2].number().is_unnumbered());
+    }
+}
@@ -1,10 +1,11 @@
 use serde::Deserialize;
+use time::OffsetDateTime;
 use ts_rs::TS;
 use url::Url;
 use uuid::Uuid;
 
-use crate::deserialize_null_default;
-use mangadex_api_types::{Language, MangaDexDateTime};
+use crate::{deserialize_null_default, ApiObject};
+use mangadex_api_types::{ChapterNumber, LanguageTag, MangaDexDateTime, VolumeNumber};
 
 /// General chapter information.
 /// More details at https://api.mangadex.org/docs/swagger.html#model-ChapterAttributes
@@ -24,8 +25,10 @@ pub struct ChapterAttributes {
     pub chapter: Option<String>,
     /// Count of readable images for this chapter.
     pub pages: u32,
-    /// Language the text is in.
-    pub translated_language: Language,
+    /// Language the text is in, as MangaDex gave it.
+    #[ts(type = "string")]
+    #[cfg_attr(feature = "specta", specta(type = String))]
+    pub translated_language: LanguageTag,
     #[serde(skip_serializing_if = "Option::is_none")]
     #[ts(type = "string")]
     pub uploader: Option<Uuid>,
@@ -44,3 +47,75 @@ pub struct ChapterAttributes {
     #[cfg_attr(feature = "specta", specta(type = String))]
     pub readable_at: MangaDexDateTime,
 }
+
+impl ChapterAttributes {
+    /// Check if the chapter links to an external source, like an official publisher.
+    ///
+    /// External chapters have no pages on MangaDex@Home.
+    pub fn is_external(&self) -> bool {
+        self.external_url.is_some()
+    }
+    /// Check if the pages of the chapter can be read at `at`.
+    ///
+    /// The chapter must be hosted on MangaDex, published and readable.
+    pub fn is_readable_at(&self, at: &OffsetDateTime) -> bool {
+        !self.is_external() && self.publish_at.as_ref() <= at && self.readable_at.as_ref() <= at
+    }
+    /// Check if the pages of the chapter can be read now.
+    pub fn is_readable_now(&self) -> bool {
+        self.is_readable_at(&OffsetDateTime::now_utc())
+    }
+    /// The parsed [`chapter`](Self::chapter), unnumbered if `None`.
+    pub fn chapter_number(&self) -> ChapterNumber {
+        ChapterNumber::from_option(self.chapter.as_deref())
+    }
+    /// The parsed [`volume`](Self::volume), unnumbered if `None`.
+    pub fn volume_number(&self) -> VolumeNumber {
+        VolumeNumber::from_option(self.volume.as_deref())
+    }
+}
+
+impl ApiObject<ChapterAttributes> {
+    /// See [`ChapterAttributes::is_external`].
+    pub fn is_external(&self) -> bool {
+        self.attributes.is_external()
+    }
+    /// See [`ChapterAttributes::is_readable_now`].
+    pub fn is_readable_now(&self) -> bool {
+        self.attributes.is_readable_now()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(unix_timestamp: i64) -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(unix_timestamp).unwrap()
+    }
+
+    #[test]
+    fn chapter_readability() {
+        let chapter: ChapterAttributes = serde_json::from_value(serde_json::json!({
+            "title": "",
+            "volume": null,
+            "chapter": "1",
+            "pages": 0,
+            "translatedLanguage": "en",
+            "externalUrl": "https://mangaplus.shueisha.co.jp/viewer/1000000",
+            "version": 1,
+            "createdAt": "2023-01-01T00:00:00+00:00",
+            "updatedAt": null,
+            "publishAt": "2023-01-01T00:00:00+00:00",
+            "readableAt": "2023-01-02T00:00:00+00:00"
+        }))
+        .unwrap();
+        assert!(chapter.is_external());
+        assert!(!chapter.is_readable_at(&at(1_704_067_200)));
+
+        let mut chapter = chapter;
+        chapter.external_url = None;
+        assert!(!chapter.is_readable_at(&at(1_672_574_400)));
+        assert!(chapter.is_readable_at(&at(1_672_617_600)));
+    }
+}
@@ -1,4 +1,4 @@
-use mangadex_api_types::{Language, MangaDexDateTime};
+use mangadex_api_types::{LanguageTag, MangaDexDateTime};
 use serde::Deserialize;
 use ts_rs::TS;
 
@@ -11,7 +11,10 @@ use ts_rs::TS;
 #[ts(export)]
 pub struct CoverAttributes {
     pub description: String,
-    pub locale: Option<Language>,
+    /// Language of the cover, as MangaDex gave it.
+    #[ts(type = "string | null")]
+    #[cfg_attr(feature = "specta", specta(type = Option<String>))]
+    pub locale: Option<LanguageTag>,
     /// Volume number in the manga.
     pub volume: Option<String>,
     /// Cover art filename as it's stored on the MangaDex servers.
@@ -0,0 +1,355 @@
+//! Text available in several languages, such as titles and descriptions.
+//!
+//! The texts are keyed by their [`LanguageTag`], so the languages MangaDex doesn't support
+//! keep their own entry.
+//!
+//! ```
+//! use mangadex_api_schema_rust::v5::LocalizedString;
+//! use mangadex_api_types::Language;
+//!
+//! let title = LocalizedString::from_iter([
+//!     (Language::Japanese, "進撃の巨人".to_string()),
+//!     (Language::JapaneseRomanized, "Shingeki no Kyojin".to_string()),
+//! ]);
+//! assert_eq!(title.best(&[Language::French]), Some("Shingeki no Kyojin"));
+//! assert_eq!(title.best(&[Language::JapaneseRomanized]), Some("Shingeki no Kyojin"));
+//! assert_eq!(title.best(&[Language::Japanese]), Some("進撃の巨人"));
+//! ```
+
+use std::collections::hash_map;
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+
+use mangadex_api_types::{Language, LanguageFallback, LanguageTag};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// Text in several languages, at most one per language tag.
+///
+/// It derefs to the underlying map, and serializes as a `{ language: text }` object.
+#[derive(Debug, Clone, Default, PartialEq, Eq, TS)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[ts(export)]
+pub struct LocalizedString(
+    #[ts(type = "Record<string, string>")]
+    #[cfg_attr(feature = "specta", specta(type = HashMap<String, String>))]
+    HashMap<LanguageTag, String>,
+);
+
+impl<'de> Deserialize<'de> for LocalizedString {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        HashMap::deserialize(deserializer).map(Self)
+    }
+}
+
+impl Serialize for LocalizedString {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.0.serialize(serializer)
+    }
+}
+
+impl LocalizedString {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn into_inner(self) -> HashMap<LanguageTag, String> {
+        self.0
+    }
+    /// The text in `language`, under its MangaDex code or else any tag read as it.
+    pub fn text(&self, language: Language) -> Option<&str> {
+        if language == Language::Unknown {
+            return None;
+        }
+        self.0
+            .get(&LanguageTag::from(language))
+            .or_else(|| {
+                self.0
+                    .iter()
+                    .find(|(tag, _)| tag.language() == language)
+                    .map(|(_, text)| text)
+            })
+            .map(String::as_str)
+    }
+    /// The text in each language, in order of preference.
+    ///
+    /// The order is the [`LanguageFallback`] chain of `languages`, English,
+    /// the romanized languages, then the others by language tag.
+    pub fn iter_preferred(
+        &self,
+        languages: &[Language],
+    ) -> impl Iterator<Item = (&LanguageTag, &str)> {
+        let chain = LanguageFallback::new(languages.iter().copied()).then(Language::English);
+        let mut texts: Vec<(&LanguageTag, &str)> = self
+            .0
+            .iter()
+            .map(|(tag, text)| (tag, text.as_str()))
+            .collect();
+        texts.sort_by_cached_key(|(tag, _)| {
+            let language = tag.language();
+            let position = chain.languages().iter().position(|l| *l == language);
+            (
+                position.unwrap_or(usize::MAX),
+                !language.is_romanized(),
+                tag.as_str().to_ascii_lowercase(),
+            )
+        });
+        texts.into_iter()
+    }
+    /// The text in the first of `languages` it is available in, see [`iter_preferred`](Self::iter_preferred).
+    ///
+    /// `None` only if there is no text at all.
+    pub fn best(&self, languages: &[Language]) -> Option<&str> {
+        self.iter_preferred(languages).next().map(|(_, text)| text)
+    }
+    /// The text in the first language of `chain` it is available in, without falling back to any other.
+    pub fn resolve(&self, chain: &LanguageFallback) -> Option<&str> {
+        chain
+            .languages()
+            .iter()
+            .find_map(|language| self.text(*language))
+    }
+    /// Add the texts of `other` in the language tags missing from `self`.
+    pub fn merge(&mut self, other: &LocalizedString) {
+        for (tag, text) in other.iter() {
+            self.0.entry(tag.clone()).or_insert_with(|| text.clone());
+        }
+    }
+}
+
+impl Deref for LocalizedString {
+    type Target = HashMap<LanguageTag, String>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for LocalizedString {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<L: Into<LanguageTag>> From<HashMap<L, String>> for LocalizedString {
+    fn from(value: HashMap<L, String>) -> Self {
+        Self::from_iter(value)
+    }
+}
+
+impl From<LocalizedString> for HashMap<LanguageTag, String> {
+    fn from(value: LocalizedString) -> Self {
+        value.0
+    }
+}
+
+impl<L: Into<LanguageTag>> FromIterator<(L, String)> for LocalizedString {
+    fn from_iter<T: IntoIterator<Item = (L, String)>>(iter: T) -> Self {
+        Self(
+            iter.into_iter()
+                .map(|(language, text)| (language.into(), text))
+                .collect(),
+        )
+    }
+}
+
+impl<L: Into<LanguageTag>> Extend<(L, String)> for LocalizedString {
+    fn extend<T: IntoIterator<Item = (L, String)>>(&mut self, iter: T) {
+        self.0.extend(
+            iter.into_iter()
+                .map(|(language, text)| (language.into(), text)),
+        )
+    }
+}
+
+impl IntoIterator for LocalizedString {
+    type Item = (LanguageTag, String);
+    type IntoIter = hash_map::IntoIter<LanguageTag, String>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a LocalizedString {
+    type Item = (&'a LanguageTag, &'a String);
+    type IntoIter = hash_map::Iter<'a, LanguageTag, String>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+/// Normalize a title for comparison.
+///
+/// The title is lowercased, fullwidth and halfwidth forms are folded to their usual form,
+/// diacritics are removed (except the kana voicing marks) and every run of punctuation
+/// or whitespace becomes a single space, so `Ｋａｇｕｙａ-sama:  Lové` becomes `kaguya sama love`.
+pub fn normalize_title(title: &str) -> String {
+    let folded: String = title
+        .nfkd()
+        .filter(|c| !is_combining_mark(*c) || matches!(c, '\u{3099}' | '\u{309A}'))
+        .nfc()
+        .flat_map(char::to_lowercase)
+        .collect();
+    folded
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Finds a title among localized titles, ignoring case, width and diacritics.
+///
+/// ```
+/// use mangadex_api_schema_rust::v5::{LocalizedString, TitleMatcher};
+/// use mangadex_api_types::Language;
+///
+/// let title = LocalizedString::from_iter([(Language::English, "Pokémon Adventures".to_string())]);
+/// assert!(TitleMatcher::new("pokemon adventures").matches_any([&title]));
+/// assert!(!TitleMatcher::new("pokemon").matches_any([&title]));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TitleMatcher {
+    normalized: String,
+}
+
+impl TitleMatcher {
+    pub fn new(query: &str) -> Self {
+        Self {
+            normalized: normalize_title(query),
+        }
+    }
+    /// The normalized query, see [`normalize_title`].
+    pub fn as_str(&self) -> &str {
+        &self.normalized
+    }
+    /// Whether `title` is the query once both are normalized.
+    pub fn matches(&self, title: &str) -> bool {
+        !self.normalized.is_empty() && normalize_title(title) == self.normalized
+    }
+    /// The first of `titles`, in any language, matching the query.
+    pub fn find<'a, I>(&self, titles: I) -> Option<(&'a LanguageTag, &'a str)>
+    where
+        I: IntoIterator<Item = &'a LocalizedString>,
+    {
+        titles.into_iter().find_map(|title| {
+            title
+                .iter()
+                .find(|(_, text)| self.matches(text))
+                .map(|(tag, text)| (tag, text.as_str()))
+        })
+    }
+    pub fn matches_any<'a, I>(&self, titles: I) -> bool
+    where
+        I: IntoIterator<Item = &'a LocalizedString>,
+    {
+        self.find(titles).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn texts_are_iterated_in_preference_order() {
+        let title = LocalizedString::from_iter(
+            [
+                (Language::Korean, "나 혼자만 레벨업"),
+                (Language::KoreanRomanized, "Na Honjaman Level Up"),
+                (Language::English, "Solo Leveling"),
+                (Language::French, "Solo Leveling FR"),
+                (Language::Arabic, "سولو ليفلينج"),
+            ]
+            .map(|(language, text)| (language, text.to_string())),
+        );
+        let languages: Vec<Language> = title
+            .iter_preferred(&[Language::KoreanRomanized])
+            .map(|(tag, _)| tag.language())
+            .collect();
+        assert_eq!(
+            languages,
+            [
+                Language::KoreanRomanized,
+                Language::Korean,
+                Language::English,
+                Language::Arabic,
+                Language::French
+            ]
+        );
+        assert_eq!(title.best(&[Language::German]), Some("Solo Leveling"));
+        assert_eq!(LocalizedString::new().best(&[Language::English]), None);
+        assert_eq!(
+            title.resolve(&LanguageFallback::new([Language::German])),
+            None
+        );
+    }
+
+    #[test]
+    fn merge_keeps_the_existing_texts() {
+        let mut title = LocalizedString::from_iter([(Language::English, "Title".to_string())]);
+        title.merge(&LocalizedString::from_iter([
+            (Language::English, "Alt title".to_string()),
+            (Language::German, "Titel".to_string()),
+        ]));
+        assert_eq!(title.text(Language::English), Some("Title"));
+        assert_eq!(title.text(Language::German), Some("Titel"));
+    }
+
+    #[test]
+    fn unknown_language_tags_stay_distinct() {
+        let title: LocalizedString = serde_json::from_str(r#"{"xx": "a", "yy": "b"}"#).unwrap();
+        assert_eq!(title.len(), 2);
+        assert_eq!(
+            title.get(&LanguageTag::new("xx")).map(String::as_str),
+            Some("a")
+        );
+        assert_eq!(
+            title.get(&LanguageTag::new("yy")).map(String::as_str),
+            Some("b")
+        );
+        assert_eq!(title.text(Language::Unknown), None);
+        assert_eq!(
+            serde_json::to_value(&title).unwrap(),
+            serde_json::json!({"xx": "a", "yy": "b"})
+        );
+        let tags: Vec<&str> = title
+            .iter_preferred(&[])
+            .map(|(tag, _)| tag.as_str())
+            .collect();
+        assert_eq!(tags, ["xx", "yy"]);
+    }
+
+    #[test]
+    fn texts_are_found_under_any_tag_of_their_language() {
+        let title = LocalizedString::from_iter([
+            ("pt-BR", "Título".to_string()),
+            ("ja-Latn", "Taitoru".to_string()),
+        ]);
+        assert_eq!(title.text(Language::PortugueseBrazilian), Some("Título"));
+        assert_eq!(title.text(Language::JapaneseRomanized), Some("Taitoru"));
+        assert_eq!(title.text(Language::PortuguesePortugal), None);
+    }
+
+    #[test]
+    fn titles_are_normalized() {
+        assert_eq!(
+            normalize_title("Ｋａｇｕｙａ-sama:  Lové"),
+            "kaguya sama love"
+        );
+        assert_eq!(normalize_title("ﾃﾞｽﾉｰﾄ"), "デスノート");
+        assert_eq!(normalize_title("  ÉPÉE!! "), "epee");
+        let matcher = TitleMatcher::new("DEATH NOTE");
+        assert!(matcher.matches("Death Note"));
+        assert!(!matcher.matches("Death Note 2"));
+        assert!(!TitleMatcher::new("!!").matches("?"));
+    }
+}
@@ -1,5 +1,6 @@
 use mangadex_api_types::{
-    ContentRating, Demographic, Language, MangaDexDateTime, MangaState, MangaStatus,
+    ContentRating, Demographic, Language, LanguageFallback, LanguageTag, MangaDexDateTime,
+    MangaState, MangaStatus,
 };
 use serde::Deserialize;
 use ts_rs::TS;
@@ -7,7 +8,7 @@ use uuid::Uuid;
 
 use crate::v5::{
     language_array_or_skip_null, localizedstring_array_or_map, manga_links_array_or_struct,
-    ApiObject, LocalizedString, MangaLinks, TagAttributes,
+    ApiObject, LocalizedString, MangaLinks, TagAttributes, TitleMatcher,
 };
 
 /// General manga information.
@@ -32,7 +33,10 @@ pub struct MangaAttributes {
     pub is_locked: bool,
     #[serde(with = "manga_links_array_or_struct")]
     pub links: Option<MangaLinks>,
-    pub original_language: Language,
+    /// Language the manga was first published in, as MangaDex gave it.
+    #[ts(type = "string")]
+    #[cfg_attr(feature = "specta", specta(type = String))]
+    pub original_language: LanguageTag,
     pub last_volume: Option<String>,
     pub last_chapter: Option<String>,
     pub publication_demographic: Option<Demographic>,
@@ -65,3 +69,95 @@ pub struct MangaAttributes {
     pub updated_at: Option<MangaDexDateTime>,
     pub version: u32,
 }
+
+impl MangaAttributes {
+    /// The main title followed by the alternative titles.
+    pub fn titles(&self) -> impl Iterator<Item = &LocalizedString> {
+        std::iter::once(&self.title).chain(&self.alt_titles)
+    }
+    /// The main title, completed with the first alternative title in each language it lacks.
+    pub fn merged_titles(&self) -> LocalizedString {
+        let mut titles = self.title.clone();
+        for alt_title in &self.alt_titles {
+            titles.merge(alt_title);
+        }
+        titles
+    }
+    /// The title in the first of `languages` it is available in, main titles first.
+    ///
+    /// Regional and romanized languages are followed by their parent language, see [`LanguageFallback`].
+    /// Falls back to the main title in English, in a romanized language or in any language,
+    /// see [`LocalizedString::best`].
+    pub fn best_title(&self, languages: &[Language]) -> Option<&str> {
+        let chain = LanguageFallback::new(languages.iter().copied());
+        chain
+            .languages()
+            .iter()
+            .find_map(|language| self.titles().find_map(|title| title.text(*language)))
+            .or_else(|| self.title.best(&[Language::English]))
+    }
+    /// Whether `query` is the main title or an alternative title, ignoring case, width and diacritics.
+    ///
+    /// See [`TitleMatcher`] to compare the same query against several manga.
+    pub fn matches_title(&self, query: &str) -> bool {
+        TitleMatcher::new(query).matches_any(self.titles())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn titles_are_picked_and_matched_across_alt_titles() {
+        let manga: MangaAttributes = serde_json::from_value(json!({
+            "title": { "ja-ro": "Boku no Hero Academia" },
+            "altTitles": [
+                { "en": "My Hero Academia" },
+                { "ja": "僕のヒーローアカデミア" },
+                { "en": "MHA" },
+                { "pt-br": "Minha Academia de Heróis" }
+            ],
+            "description": [],
+            "links": null,
+            "originalLanguage": "ja",
+            "lastVolume": null,
+            "lastChapter": null,
+            "publicationDemographic": "shounen",
+            "status": "ongoing",
+            "year": 2014,
+            "contentRating": "safe",
+            "latestUploadedChapter": null,
+            "availableTranslatedLanguages": [],
+            "tags": [],
+            "state": "published",
+            "createdAt": "2018-11-23T17:44:59+00:00",
+            "updatedAt": null,
+            "version": 1
+        }))
+        .unwrap();
+
+        assert_eq!(
+            manga.best_title(&[Language::English]),
+            Some("My Hero Academia")
+        );
+        assert_eq!(
+            manga.best_title(&[Language::PortugueseBrazilian]),
+            Some("Minha Academia de Heróis")
+        );
+        assert_eq!(
+            manga.best_title(&[Language::German]),
+            Some("Boku no Hero Academia")
+        );
+        assert_eq!(manga.merged_titles().len(), 4);
+        assert_eq!(
+            manga.merged_titles().text(Language::English),
+            Some("My Hero Academia")
+        );
+        assert!(manga.matches_title("minha academia de herois"));
+        assert!(manga.matches_title("ＭＨＡ"));
+        assert!(!manga.matches_title("Hero Academia"));
+    }
+}
@@ -1,7 +1,7 @@
 #[cfg(feature = "serialize")]
 use std::collections::HashMap;
 
-use mangadex_api_types::ResultType;
+use mangadex_api_types::{ChapterNumber, ResultType, VolumeNumber};
 #[cfg(feature = "serialize")]
 use serde::Serialize;
 
@@ -76,6 +76,13 @@ pub struct VolumeAggregate {
     pub chapters: Vec<ChapterAggregate>,
 }
 
+impl VolumeAggregate {
+    /// The parsed [`volume`](Self::volume).
+    pub fn number(&self) -> VolumeNumber {
+        VolumeNumber::new(self.volume.as_str())
+    }
+}
+
 #[cfg(feature = "serialize")]
 #[allow(clippy::from_over_into)]
 impl Into<VolumeAggregateSer> for VolumeAggregate{
@@ -101,3 +108,10 @@ pub struct ChapterAggregate {
     /// Number of translations for the chapter.
     pub count: u32,
 }
+
+impl ChapterAggregate {
+    /// The parsed [`chapter`](Self::chapter).
+    pub fn number(&self) -> ChapterNumber {
+        ChapterNumber::new(self.chapter.as_str())
+    }
+}
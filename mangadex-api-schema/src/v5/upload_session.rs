@@ -4,6 +4,7 @@ use mangadex_api_types::{MangaDexDateTime, RelationshipType};
 use serde::Deserialize;
 use uuid::Uuid;
 
+use crate::v5::Relationship;
 use crate::FromResponse;
 
 #[derive(Clone, Debug, Deserialize)]
@@ -32,6 +33,11 @@ pub struct UploadSessionResponse {
     #[serde(rename = "type")]
     pub type_: RelationshipType,
     pub attributes: UploadSessionAttributes,
+    /// The manga, groups and uploader of the session, and its files.
+    ///
+    /// In an edit session, the pages of the chapter are listed as [`remote`](mangadex_api_types::UploadSource::Remote) files.
+    #[serde(default)]
+    pub relationships: Vec<Relationship>,
 }
 
 impl FromResponse for UploadSessionResponse {
@@ -2,14 +2,16 @@
 
 use mangadex_api_types::UploadSource;
 use serde::Deserialize;
+use ts_rs::TS;
 
 use crate::v5::error::MangaDexError;
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, TS)]
 #[serde(rename_all = "camelCase")]
 #[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[cfg_attr(feature = "non_exhaustive", non_exhaustive)]
 #[cfg_attr(feature = "specta", derive(specta::Type))]
+#[ts(export)]
 pub struct UploadSessionFileAttributes {
     pub original_file_name: String,
     pub file_hash: String,
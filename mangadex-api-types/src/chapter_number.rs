@@ -0,0 +1,313 @@
+//! Chapter and volume numbers, parsed from their raw string.
+
+use std::cmp::Ordering;
+use std::convert::Infallible;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// What a raw chapter or volume number holds.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum NumberKind {
+    /// A number such as `10`, `10.5` or `10a`.
+    Numbered {
+        integer: u64,
+        /// Digits after the decimal point, `5` in `10.5`.
+        decimal: Option<String>,
+        /// Lowercase letter after the number, `a` in `10a`.
+        suffix: Option<char>,
+    },
+    /// `Oneshot`, in any case.
+    Oneshot,
+    /// An empty string, `none` in any case, or `null`.
+    Unnumbered,
+    /// Anything else, such as `Extra`.
+    Other,
+}
+
+impl NumberKind {
+    fn parse(raw: &str) -> Self {
+        let raw = raw.trim();
+        if raw.is_empty() || raw.eq_ignore_ascii_case("none") {
+            return Self::Unnumbered;
+        }
+        if raw.eq_ignore_ascii_case("oneshot") {
+            return Self::Oneshot;
+        }
+        let (number, suffix) = match raw.chars().last() {
+            Some(c) if c.is_ascii_lowercase() => (&raw[..raw.len() - 1], Some(c)),
+            _ => (raw, None),
+        };
+        let (integer, decimal) = match number.split_once('.') {
+            Some((integer, decimal)) => (integer, Some(decimal)),
+            None => (number, None),
+        };
+        let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+        match (integer.parse::<u64>(), decimal) {
+            (Ok(integer_part), None) if digits(integer) => Self::Numbered {
+                integer: integer_part,
+                decimal: None,
+                suffix,
+            },
+            (Ok(integer_part), Some(decimal)) if digits(integer) && digits(decimal) => {
+                Self::Numbered {
+                    integer: integer_part,
+                    decimal: Some(decimal.to_string()),
+                    suffix,
+                }
+            }
+            _ => Self::Other,
+        }
+    }
+    /// Position of the kind in the ordering: numbers, oneshots, others, then unnumbered.
+    fn rank(&self) -> u8 {
+        match self {
+            Self::Numbered { .. } => 0,
+            Self::Oneshot => 1,
+            Self::Other => 2,
+            Self::Unnumbered => 3,
+        }
+    }
+    fn cmp_values(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (
+                Self::Numbered {
+                    integer,
+                    decimal,
+                    suffix,
+                },
+                Self::Numbered {
+                    integer: other_integer,
+                    decimal: other_decimal,
+                    suffix: other_suffix,
+                },
+            ) => {
+                // Comparing the digits without the trailing zeros compares the decimal values
+                let decimal = decimal.as_deref().unwrap_or("").trim_end_matches('0');
+                let other_decimal = other_decimal.as_deref().unwrap_or("").trim_end_matches('0');
+                integer
+                    .cmp(other_integer)
+                    .then_with(|| decimal.cmp(other_decimal))
+                    .then_with(|| suffix.cmp(other_suffix))
+            }
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+macro_rules! number_type {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        ///
+        /// The raw string is kept as-is, it is what [`Display`](fmt::Display) and serde write back.
+        /// A value read from `null` or `None` is written back as `null`, and displayed as an empty string.
+        /// Numbers are ordered by value, `2` before `10` and `10` before `10a` and `10.5`,
+        /// then come oneshots, other labels and unnumbered values.
+        /// Values equal in number, such as `10.5` and `10.50`, are ordered by their raw string.
+        #[derive(Debug, Clone)]
+        pub struct $name {
+            /// `None` when read from `null`.
+            raw: Option<String>,
+            kind: NumberKind,
+        }
+
+        impl $name {
+            pub fn new<S: Into<String>>(raw: S) -> Self {
+                let raw = raw.into();
+                let kind = NumberKind::parse(&raw);
+                Self {
+                    raw: Some(raw),
+                    kind,
+                }
+            }
+            /// Read an optional raw value, `None` being unnumbered.
+            pub fn from_option<S: Into<String>>(raw: Option<S>) -> Self {
+                match raw {
+                    Some(raw) => Self::new(raw),
+                    None => Self {
+                        raw: None,
+                        kind: NumberKind::Unnumbered,
+                    },
+                }
+            }
+            /// The raw string, empty if the value was `null`.
+            pub fn as_str(&self) -> &str {
+                self.raw.as_deref().unwrap_or_default()
+            }
+            /// The raw string, `None` if the value was `null`.
+            pub fn as_option(&self) -> Option<&str> {
+                self.raw.as_deref()
+            }
+            pub fn kind(&self) -> &NumberKind {
+                &self.kind
+            }
+            pub fn is_numbered(&self) -> bool {
+                matches!(self.kind, NumberKind::Numbered { .. })
+            }
+            pub fn is_oneshot(&self) -> bool {
+                self.kind == NumberKind::Oneshot
+            }
+            pub fn is_unnumbered(&self) -> bool {
+                self.kind == NumberKind::Unnumbered
+            }
+            /// The whole part of the number.
+            pub fn integer(&self) -> Option<u64> {
+                match &self.kind {
+                    NumberKind::Numbered { integer, .. } => Some(*integer),
+                    _ => None,
+                }
+            }
+            /// The number as a float, without its suffix.
+            pub fn as_f64(&self) -> Option<f64> {
+                match &self.kind {
+                    NumberKind::Numbered {
+                        integer, decimal, ..
+                    } => format!("{integer}.{}", decimal.as_deref().unwrap_or("0"))
+                        .parse()
+                        .ok(),
+                    _ => None,
+                }
+            }
+        }
+
+        impl PartialEq for $name {
+            fn eq(&self, other: &Self) -> bool {
+                self.raw == other.raw
+            }
+        }
+
+        impl Eq for $name {}
+
+        impl Hash for $name {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.raw.hash(state)
+            }
+        }
+
+        impl PartialOrd for $name {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl Ord for $name {
+            fn cmp(&self, other: &Self) -> Ordering {
+                self.kind
+                    .cmp_values(&other.kind)
+                    .then_with(|| self.raw.cmp(&other.raw))
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = Infallible;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Ok(Self::new(s))
+            }
+        }
+
+        impl From<&str> for $name {
+            fn from(value: &str) -> Self {
+                Self::new(value)
+            }
+        }
+
+        impl From<String> for $name {
+            fn from(value: String) -> Self {
+                Self::new(value)
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                self.raw.serialize(serializer)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            /// Deserialize a string, `null` being unnumbered.
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                Ok(Self::from_option(Option::<String>::deserialize(deserializer)?))
+            }
+        }
+    };
+}
+
+number_type! {
+    /// A chapter number, such as `10.5`.
+    ChapterNumber
+}
+
+number_type! {
+    /// A volume number, such as `3`.
+    VolumeNumber
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chapter_numbers_are_parsed() {
+        assert_eq!(
+            ChapterNumber::new("10.5").kind(),
+            &NumberKind::Numbered {
+                integer: 10,
+                decimal: Some("5".to_string()),
+                suffix: None
+            }
+        );
+        assert_eq!(ChapterNumber::new("10a").integer(), Some(10));
+        assert_eq!(ChapterNumber::new("10.25").as_f64(), Some(10.25));
+        assert!(ChapterNumber::new("Oneshot").is_oneshot());
+        assert!(ChapterNumber::new("none").is_unnumbered());
+        assert!(ChapterNumber::from_option(None::<String>).is_unnumbered());
+        assert_eq!(ChapterNumber::new("Extra").kind(), &NumberKind::Other);
+        assert_eq!(ChapterNumber::new("1.").kind(), &NumberKind::Other);
+        assert_eq!(ChapterNumber::new("-1").kind(), &NumberKind::Other);
+    }
+
+    #[test]
+    fn chapter_numbers_are_ordered_by_value() {
+        let mut numbers: Vec<ChapterNumber> = [
+            "none", "10", "Extra", "2", "10.5", "Oneshot", "10a", "10.10", "1.50", "1.5", "",
+        ]
+        .into_iter()
+        .map(ChapterNumber::from)
+        .collect();
+        numbers.sort();
+        let sorted: Vec<&str> = numbers.iter().map(ChapterNumber::as_str).collect();
+        assert_eq!(
+            sorted,
+            ["1.5", "1.50", "2", "10", "10a", "10.10", "10.5", "Oneshot", "Extra", "", "none"]
+        );
+    }
+
+    #[test]
+    fn volume_numbers_round_trip_the_raw_string() {
+        let volumes: Vec<Option<VolumeNumber>> =
+            serde_json::from_str(r#"["01", null, "none"]"#).unwrap();
+        let volumes: Vec<VolumeNumber> = volumes.into_iter().flatten().collect();
+        assert_eq!(volumes[0].integer(), Some(1));
+        assert_eq!(volumes[0].to_string(), "01");
+        assert_eq!(serde_json::to_string(&volumes).unwrap(), r#"["01","none"]"#);
+
+        let volume: VolumeNumber = serde_json::from_str("null").unwrap();
+        assert!(volume.is_unnumbered());
+        assert_eq!(volume.as_str(), "");
+        assert_eq!(volume.as_option(), None);
+        assert_eq!(serde_json::to_string(&volume).unwrap(), "null");
+        assert_eq!(
+            serde_json::to_string(&VolumeNumber::new("")).unwrap(),
+            "\"\""
+        );
+    }
+}
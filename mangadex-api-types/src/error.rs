@@ -59,7 +59,18 @@ pub enum Error {
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 
-    
+    /// The chapter links to an external source, it has no pages to download.
+    #[error("the chapter {chapter_id} is hosted externally at {url}")]
+    ExternalChapter {
+        chapter_id: uuid::Uuid,
+        url: url::Url,
+    },
+
+    /// The pages of an upload were rejected before starting the upload session.
+    ///
+    /// The source lists every problem found, it can be downcast to the report that produced it.
+    #[error(transparent)]
+    InvalidUpload(Box<dyn std::error::Error + Send + Sync>),
 }
 
 impl serde::Serialize for Error{
@@ -82,6 +93,8 @@ impl serde::Serialize for Error{
             Error::BorrowMutError(e) => serializer.serialize_str(e.to_string().as_str()),
             Error::Io(e) => serializer.serialize_str(e.to_string().as_str()),
             Error::UnexpectedError(e) => serializer.serialize_str(e.to_string().as_str()),
+            Error::ExternalChapter { .. } => serializer.serialize_str(self.to_string().as_str()),
+            Error::InvalidUpload(e) => serializer.serialize_str(e.to_string().as_str()),
         }
     }
 }
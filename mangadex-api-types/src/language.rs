@@ -1,18 +1,26 @@
+//! Languages of MangaDex content, and the language tags they are read from.
+//!
+//! [`Language`] is the fixed list of languages MangaDex supports.
+//! [`LanguageTag`] keeps any BCP-47 tag as given and parses its subtags,
+//! and [`LanguageFallback`] orders the languages to look a localized value up in.
+
+use std::collections::HashMap;
+use std::fmt;
 use std::str::FromStr;
 use std::string::ParseError;
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use ts_rs::TS;
 
 macro_rules! languages {
     (
         $(
             $( #[$meta:meta] )*
-            $lang:ident => $code:literal,
+            $lang:ident => $code:literal, $english:literal, $native:literal,
         )*
     ) => {
         /// Languages supported by MangaDex.
-        #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, PartialOrd, Serialize, TS)]
+        #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Serialize, TS)]
         #[cfg_attr(feature = "non_exhaustive", non_exhaustive)]
         #[cfg_attr(feature = "specta", derive(specta::Type))]
         #[ts(export)]
@@ -36,103 +44,378 @@ macro_rules! languages {
                     Self::Unknown => "NULL",
                 }
             }
-        }
-
-        impl From<&str> for Language {
-            /// Parse a `Language` type from a string.
-            ///
-            /// This function's value parameter is case-insensitive.
-            fn from(value: &str) -> Self {
-                match value.to_lowercase().as_str() {
+            /// Name of the language in English, such as `Portuguese (Brazil)`.
+            pub fn english_name(&self) -> &'static str {
+                match self {
+                    $(
+                        Self::$lang => $english,
+                    )*
+                    Self::Unknown => "Unknown",
+                }
+            }
+            /// Name of the language in the language itself, such as `Português (Brasil)`.
+            pub fn native_name(&self) -> &'static str {
+                match self {
+                    $(
+                        Self::$lang => $native,
+                    )*
+                    Self::Unknown => "Unknown",
+                }
+            }
+            /// The language of a lowercase MangaDex code, such as `pt-br`.
+            fn from_code(code: &str) -> Option<Self> {
+                match code {
                     $(
-                        $code => Self::$lang,
+                        $code => Some(Self::$lang),
                     )*
-                    _ => Self::Unknown,
+                    _ => None,
                 }
             }
         }
+    };
+}
+
+impl Language {
+    /// The language a regional or romanized variant falls back to, `pt` for `pt-br`.
+    pub fn parent(&self) -> Option<Self> {
+        match self {
+            Self::PortugueseBrazilian => Some(Self::PortuguesePortugal),
+            Self::SpanishLatinAmerican => Some(Self::SpanishCastilian),
+            Self::ChineseTraditional | Self::ChineseRomanized => Some(Self::ChineseSimplified),
+            Self::JapaneseRomanized => Some(Self::Japanese),
+            Self::KoreanRomanized => Some(Self::Korean),
+            _ => None,
+        }
+    }
+    /// Whether the language is written in the Latin script rather than its own, as `ja-ro`.
+    pub fn is_romanized(&self) -> bool {
+        matches!(
+            self,
+            Self::ChineseRomanized | Self::JapaneseRomanized | Self::KoreanRomanized
+        )
+    }
+}
+
+impl From<&str> for Language {
+    /// Parse a `Language` type from a string.
+    ///
+    /// This function's value parameter is case-insensitive, and any BCP-47 tag is accepted,
+    /// see [`LanguageTag::language`].
+    fn from(value: &str) -> Self {
+        LanguageTag::new(value).language()
+    }
+}
+
+impl FromStr for Language {
+    type Err = ParseError;
+
+    /// Parse a `Language` type from a string.
+    ///
+    /// This function's value parameter is case-insensitive, and any BCP-47 tag is accepted,
+    /// see [`LanguageTag::language`].
+    fn from_str(value: &str) -> Result<Self, ParseError> {
+        Ok(Self::from(value))
+    }
+}
+
+impl Default for Language {
+    fn default() -> Self {
+        Self::Unknown
+    }
+}
+
+impl<'de> Deserialize<'de> for Language {
+    /// Deserialize a language tag, see [`LanguageTag::language`].
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(LanguageTag::deserialize(deserializer)?.language())
+    }
+}
 
-        impl FromStr for Language {
-            type Err = ParseError;
-
-            /// Parse a `Language` type from a string.
-            ///
-            /// This function's value parameter is case-insensitive.
-            fn from_str(value: &str) -> Result<Self, ParseError> {
-                Ok(
-                    match value.to_lowercase().as_str() {
-                        $(
-                            $code => Self::$lang,
-                        )*
-                        _ => Self::Unknown,
-                    }
-                )
+/// A BCP-47 language tag, such as `pt-BR`, `zh-Hant` or MangaDex's `ja-ro`.
+///
+/// The tag is kept as given, it is what [`Display`](fmt::Display) and serde write back.
+/// Subtags are split on `-` or `_`: 4 letters are a script, 2 letters or 3 digits a region,
+/// anything else a variant. MangaDex's `ro` subtag, as in `ja-ro`, means romanized
+/// and is read as the `Latn` script rather than the Romania region.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LanguageTag {
+    tag: String,
+    primary: String,
+    script: Option<String>,
+    region: Option<String>,
+    variants: Vec<String>,
+}
+
+impl LanguageTag {
+    pub fn new<S: Into<String>>(tag: S) -> Self {
+        let tag = tag.into();
+        let mut subtags = tag.trim().split(['-', '_']);
+        let primary = subtags.next().unwrap_or_default().to_ascii_lowercase();
+        let mut script = None;
+        let mut region = None;
+        let mut variants = Vec::new();
+        for (i, subtag) in subtags.enumerate() {
+            let letters = subtag.bytes().all(|b| b.is_ascii_alphabetic());
+            match subtag.len() {
+                2 if i == 0 && subtag == "ro" && primary != "ro" => {
+                    script = Some("Latn".to_string())
+                }
+                4 if letters && script.is_none() && region.is_none() => {
+                    script =
+                        Some(subtag[..1].to_ascii_uppercase() + &subtag[1..].to_ascii_lowercase())
+                }
+                2 if letters && region.is_none() => region = Some(subtag.to_ascii_uppercase()),
+                3 if subtag.bytes().all(|b| b.is_ascii_digit()) && region.is_none() => {
+                    region = Some(subtag.to_string())
+                }
+                _ => variants.push(subtag.to_ascii_lowercase()),
+            }
+        }
+        Self {
+            tag,
+            primary,
+            script,
+            region,
+            variants,
+        }
+    }
+    /// The tag as given.
+    pub fn as_str(&self) -> &str {
+        &self.tag
+    }
+    /// The lowercase primary language subtag, `pt` in `pt-BR`.
+    pub fn primary(&self) -> &str {
+        &self.primary
+    }
+    /// The titlecase script subtag, `Hant` in `zh-Hant`.
+    pub fn script(&self) -> Option<&str> {
+        self.script.as_deref()
+    }
+    /// The uppercase region subtag, `BR` in `pt-BR`.
+    pub fn region(&self) -> Option<&str> {
+        self.region.as_deref()
+    }
+    /// The lowercase remaining subtags.
+    pub fn variants(&self) -> &[String] {
+        &self.variants
+    }
+    /// The MangaDex language closest to the tag.
+    ///
+    /// MangaDex codes are matched first, then the script and region pick the variant
+    /// of the primary language, so `pt-BR` is [`Language::PortugueseBrazilian`],
+    /// `es-419` [`Language::SpanishLatinAmerican`] and `zh-TW` [`Language::ChineseTraditional`].
+    /// Unsupported languages are [`Language::Unknown`].
+    pub fn language(&self) -> Language {
+        if let Some(language) = Language::from_code(&self.tag.trim().to_ascii_lowercase()) {
+            return language;
+        }
+        let latin = self.script() == Some("Latn");
+        match (self.primary(), self.script(), self.region()) {
+            ("pt", _, Some("BR")) => Language::PortugueseBrazilian,
+            ("es", _, Some(region)) if region != "ES" => Language::SpanishLatinAmerican,
+            ("zh", _, _) if latin => Language::ChineseRomanized,
+            ("zh", Some("Hant"), _) | ("zh", None, Some("HK" | "TW" | "MO")) => {
+                Language::ChineseTraditional
             }
+            ("ja", _, _) if latin => Language::JapaneseRomanized,
+            ("ko", _, _) if latin => Language::KoreanRomanized,
+            ("fil", _, _) => Language::Filipino,
+            ("nb" | "nn", _, _) => Language::Norwegian,
+            (primary, _, _) => Language::from_code(primary).unwrap_or_default(),
         }
+    }
+    /// Name of the [`language`](Self::language) in English, `None` if it isn't supported.
+    pub fn english_name(&self) -> Option<&'static str> {
+        Some(self.language())
+            .filter(|language| *language != Language::Unknown)
+            .map(|language| language.english_name())
+    }
+    /// Name of the [`language`](Self::language) in itself, `None` if it isn't supported.
+    pub fn native_name(&self) -> Option<&'static str> {
+        Some(self.language())
+            .filter(|language| *language != Language::Unknown)
+            .map(|language| language.native_name())
+    }
+}
 
-        impl Default for Language {
-            fn default() -> Self{
-                Self::Unknown
+impl fmt::Display for LanguageTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.tag)
+    }
+}
+
+impl FromStr for LanguageTag {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self::new(s))
+    }
+}
+
+impl From<&str> for LanguageTag {
+    fn from(value: &str) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<String> for LanguageTag {
+    fn from(value: String) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<Language> for LanguageTag {
+    fn from(value: Language) -> Self {
+        Self::new(value.code2())
+    }
+}
+
+impl From<&LanguageTag> for Language {
+    fn from(value: &LanguageTag) -> Self {
+        value.language()
+    }
+}
+
+impl Serialize for LanguageTag {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.tag)
+    }
+}
+
+impl<'de> Deserialize<'de> for LanguageTag {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self::new(String::deserialize(deserializer)?))
+    }
+}
+
+/// Languages to look a localized value up in, in order.
+///
+/// Each preferred language is followed by its [parents](Language::parent),
+/// so the chain of `pt-br` is `pt-br`, `pt`, then the [`then`](Self::then) languages.
+/// The default chain is English alone.
+///
+/// ```
+/// use std::collections::HashMap;
+///
+/// use mangadex_api_types_rust::{Language, LanguageFallback};
+///
+/// let chain = LanguageFallback::new([Language::PortugueseBrazilian]).then(Language::English);
+/// assert_eq!(
+///     chain.languages(),
+///     [
+///         Language::PortugueseBrazilian,
+///         Language::PortuguesePortugal,
+///         Language::English
+///     ]
+/// );
+///
+/// let titles = HashMap::from([
+///     (Language::English, "Title"),
+///     (Language::PortuguesePortugal, "Título"),
+/// ]);
+/// assert_eq!(chain.resolve(&titles), Some(&"Título"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LanguageFallback {
+    languages: Vec<Language>,
+}
+
+impl LanguageFallback {
+    pub fn new<I: IntoIterator<Item = Language>>(preferred: I) -> Self {
+        preferred.into_iter().fold(
+            Self {
+                languages: Vec::new(),
+            },
+            Self::then,
+        )
+    }
+    /// Read the preferred languages from BCP-47 tags, skipping the unsupported ones.
+    pub fn from_tags<'a, I: IntoIterator<Item = &'a LanguageTag>>(preferred: I) -> Self {
+        Self::new(preferred.into_iter().map(LanguageTag::language))
+    }
+    /// Append `language` and its parents, unless they are already in the chain.
+    pub fn then(mut self, language: Language) -> Self {
+        let mut next = Some(language);
+        while let Some(language) = next {
+            if language != Language::Unknown && !self.languages.contains(&language) {
+                self.languages.push(language);
             }
+            next = language.parent();
         }
-    };
+        self
+    }
+    pub fn languages(&self) -> &[Language] {
+        &self.languages
+    }
+    /// The value of the first language of the chain present in `values`.
+    pub fn resolve<'a, V>(&self, values: &'a HashMap<Language, V>) -> Option<&'a V> {
+        self.languages
+            .iter()
+            .find_map(|language| values.get(language))
+    }
+}
+
+impl Default for LanguageFallback {
+    fn default() -> Self {
+        Self::new([Language::English])
+    }
 }
 
 languages! {
-    Arabic => "ar",
-    Azerbaijani => "az",
-    Bengali => "bn",
-    Bulgarian => "bg",
-    Burmese => "my",
-    Catalan => "ca",
-    ChineseRomanized => "zh-ro",
-    ChineseSimplified => "zh",
-    ChineseTraditional => "zh-hk",
-    Croatian => "hr",
-    Czech => "cs",
-    Danish => "da",
-    Dutch => "nl",
-    English => "en",
-    Esperanto => "eo",
-    Filipino => "tl",
-    Finnish => "fi",
-    French => "fr",
-    German => "de",
-    Greek => "el",
-    Hebrew => "he",
-    Hindi => "hi",
-    Hungarian => "hu",
-    Indonesian => "id",
-    Italian => "it",
-    Japanese => "ja",
-    JapaneseRomanized => "ja-ro",
-    Kazakh => "kk",
-    Korean => "ko",
-    KoreanRomanized => "ko-ro",
-    Latin => "la",
-    Lithuanian => "lt",
-    Malay => "ms",
-    Mongolian => "mn",
-    Nepali => "ne",
-    NiloSaharan => "kr",
-    Norwegian => "no",
-    Persian => "fa",
-    Polish => "pl",
-    PortugueseBrazilian => "pt-br",
-    PortuguesePortugal => "pt",
-    Romansh => "rm",
-    Romanian => "ro",
-    Russian => "ru",
-    SerboCroatian => "sr",
-    Slovak => "sk",
-    SpanishCastilian => "es",
-    SpanishLatinAmerican => "es-la",
-    Swedish => "sv",
-    Tamil => "ta",
-    Thai => "th",
-    Turkish => "tr",
-    Ukrainian => "uk",
-    Vietnamese => "vi",
+    Arabic => "ar", "Arabic", "العربية",
+    Azerbaijani => "az", "Azerbaijani", "Azərbaycan dili",
+    Bengali => "bn", "Bengali", "বাংলা",
+    Bulgarian => "bg", "Bulgarian", "Български",
+    Burmese => "my", "Burmese", "မြန်မာဘာသာ",
+    Catalan => "ca", "Catalan", "Català",
+    ChineseRomanized => "zh-ro", "Chinese (Romanized)", "Zhōngwén",
+    ChineseSimplified => "zh", "Chinese (Simplified)", "简体中文",
+    ChineseTraditional => "zh-hk", "Chinese (Traditional)", "繁體中文",
+    Croatian => "hr", "Croatian", "Hrvatski",
+    Czech => "cs", "Czech", "Čeština",
+    Danish => "da", "Danish", "Dansk",
+    Dutch => "nl", "Dutch", "Nederlands",
+    English => "en", "English", "English",
+    Esperanto => "eo", "Esperanto", "Esperanto",
+    Filipino => "tl", "Filipino", "Filipino",
+    Finnish => "fi", "Finnish", "Suomi",
+    French => "fr", "French", "Français",
+    German => "de", "German", "Deutsch",
+    Greek => "el", "Greek", "Ελληνικά",
+    Hebrew => "he", "Hebrew", "עברית",
+    Hindi => "hi", "Hindi", "हिन्दी",
+    Hungarian => "hu", "Hungarian", "Magyar",
+    Indonesian => "id", "Indonesian", "Bahasa Indonesia",
+    Italian => "it", "Italian", "Italiano",
+    Japanese => "ja", "Japanese", "日本語",
+    JapaneseRomanized => "ja-ro", "Japanese (Romanized)", "Nihongo",
+    Kazakh => "kk", "Kazakh", "Қазақ тілі",
+    Korean => "ko", "Korean", "한국어",
+    KoreanRomanized => "ko-ro", "Korean (Romanized)", "Hangugeo",
+    Latin => "la", "Latin", "Latina",
+    Lithuanian => "lt", "Lithuanian", "Lietuvių",
+    Malay => "ms", "Malay", "Bahasa Melayu",
+    Mongolian => "mn", "Mongolian", "Монгол",
+    Nepali => "ne", "Nepali", "नेपाली",
+    NiloSaharan => "kr", "Nilo-Saharan", "Kanuri",
+    Norwegian => "no", "Norwegian", "Norsk",
+    Persian => "fa", "Persian", "فارسی",
+    Polish => "pl", "Polish", "Polski",
+    PortugueseBrazilian => "pt-br", "Portuguese (Brazil)", "Português (Brasil)",
+    PortuguesePortugal => "pt", "Portuguese (Portugal)", "Português (Portugal)",
+    Romansh => "rm", "Romansh", "Rumantsch",
+    Romanian => "ro", "Romanian", "Română",
+    Russian => "ru", "Russian", "Русский",
+    SerboCroatian => "sr", "Serbo-Croatian", "Srpskohrvatski",
+    Slovak => "sk", "Slovak", "Slovenčina",
+    SpanishCastilian => "es", "Spanish (Castilian)", "Español (España)",
+    SpanishLatinAmerican => "es-la", "Spanish (Latin American)", "Español (Latinoamérica)",
+    Swedish => "sv", "Swedish", "Svenska",
+    Tamil => "ta", "Tamil", "தமிழ்",
+    Thai => "th", "Thai", "ไทย",
+    Turkish => "tr", "Turkish", "Türkçe",
+    Ukrainian => "uk", "Ukrainian", "Українська",
+    Vietnamese => "vi", "Vietnamese", "Tiếng Việt",
 }
 
 #[cfg(test)]
@@ -159,4 +442,88 @@ mod tests {
             assert_eq!(lang, Language::Unknown);
         }
     }
+
+    #[test]
+    fn language_tags_keep_the_tag_and_parse_the_subtags() {
+        let tag = LanguageTag::new("zh_Hant-TW");
+        assert_eq!(tag.to_string(), "zh_Hant-TW");
+        assert_eq!(tag.primary(), "zh");
+        assert_eq!(tag.script(), Some("Hant"));
+        assert_eq!(tag.region(), Some("TW"));
+        assert_eq!(tag.language(), Language::ChineseTraditional);
+        assert_eq!(tag.native_name(), Some("繁體中文"));
+
+        let tag = LanguageTag::new("ja-ro");
+        assert_eq!(tag.script(), Some("Latn"));
+        assert_eq!(tag.region(), None);
+        assert_eq!(tag.language(), Language::JapaneseRomanized);
+
+        assert_eq!(LanguageTag::new("ro-RO").script(), None);
+        assert_eq!(LanguageTag::new("ro-RO").language(), Language::Romanian);
+        assert_eq!(LanguageTag::new("de-CH-1901").variants(), ["1901"]);
+
+        let tag: LanguageTag = serde_json::from_str(r#""xx-Test""#).unwrap();
+        assert_eq!(tag.language(), Language::Unknown);
+        assert_eq!(tag.english_name(), None);
+        assert_eq!(serde_json::to_string(&tag).unwrap(), r#""xx-Test""#);
+    }
+
+    #[test]
+    fn string_produces_regional_variants_from_bcp47_tags() {
+        let test_cases = [
+            ("pt-BR", Language::PortugueseBrazilian),
+            ("pt-PT", Language::PortuguesePortugal),
+            ("es-419", Language::SpanishLatinAmerican),
+            ("es-MX", Language::SpanishLatinAmerican),
+            ("es-ES", Language::SpanishCastilian),
+            ("ES-LA", Language::SpanishLatinAmerican),
+            ("zh-Hans-CN", Language::ChineseSimplified),
+            ("zh-HK", Language::ChineseTraditional),
+            ("zh-Latn", Language::ChineseRomanized),
+            ("ko-Latn", Language::KoreanRomanized),
+            ("en-US", Language::English),
+            ("nb", Language::Norwegian),
+        ];
+        for (tag, language) in test_cases {
+            assert_eq!(Language::from(tag), language, "{tag}");
+        }
+    }
+
+    #[test]
+    fn languages_are_deserialized_from_any_tag() {
+        let languages: Vec<Language> =
+            serde_json::from_str(r#"["ja-ro", "pt-BR", "tlh", "NULL"]"#).unwrap();
+        assert_eq!(
+            languages,
+            [
+                Language::JapaneseRomanized,
+                Language::PortugueseBrazilian,
+                Language::Unknown,
+                Language::Unknown
+            ]
+        );
+        let titles: HashMap<Language, String> =
+            serde_json::from_str(r#"{"es-419": "Título"}"#).unwrap();
+        assert!(titles.contains_key(&Language::SpanishLatinAmerican));
+    }
+
+    #[test]
+    fn fallback_chain_includes_the_parent_languages_once() {
+        let chain = LanguageFallback::from_tags(&[
+            LanguageTag::new("ja-ro"),
+            LanguageTag::new("foo"),
+            LanguageTag::new("ja"),
+        ])
+        .then(Language::English);
+        assert_eq!(
+            chain.languages(),
+            [
+                Language::JapaneseRomanized,
+                Language::Japanese,
+                Language::English
+            ]
+        );
+        assert_eq!(LanguageFallback::default().languages(), [Language::English]);
+        assert_eq!(chain.resolve(&HashMap::from([(Language::Korean, 1)])), None);
+    }
 }
@@ -1,3 +1,4 @@
+pub mod chapter_number;
 pub mod error;
 pub mod include_future_updates;
 pub mod language;
@@ -22,8 +23,9 @@ pub mod include_future_publish_at;
 pub mod include_external_url;
 pub mod result;
 
+pub use chapter_number::{ChapterNumber, NumberKind, VolumeNumber};
 pub use include_future_updates::IncludeFutureUpdates;
-pub use language::Language;
+pub use language::{Language, LanguageFallback, LanguageTag};
 pub use legacy_mapping_type::LegacyMappingType;
 pub use manga_link::{MangaLink, MangaLinks};
 pub use manga_state::MangaState;
@@ -124,9 +124,9 @@ async fn run(args: Args) -> anyhow::Result<()> {
                 manga
                     .attributes
                     .title
-                    .get(&Language::English)
-                    .unwrap_or(&"<NO ENGLISH TITLE>".to_string())
-                    .clone(),
+                    .text(Language::English)
+                    .unwrap_or("<NO ENGLISH TITLE>")
+                    .to_string(),
             )
         })
         .collect();
@@ -100,13 +100,12 @@ async fn run(args: Args) -> anyhow::Result<()> {
             .send()
             .await?;
 
-        let no_title = "<NO ENGLISH TITLE>".to_string();
         let manga_title = manga
             .data
             .attributes
             .title
-            .get(&Language::English)
-            .unwrap_or(&no_title);
+            .text(Language::English)
+            .unwrap_or("<NO ENGLISH TITLE>");
 
         let file_name = cover_data.attributes.file_name;
 
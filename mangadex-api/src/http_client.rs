@@ -14,6 +14,8 @@ use reqwest::Client;
 use serde::de::DeserializeOwned;
 use url::Url;
 
+#[cfg(feature = "utils")]
+use crate::utils::store::EntityStore;
 use crate::v5::AuthTokens;
 use crate::{API_URL, API_DEV_URL};
 use mangadex_api_types::error::Result;
@@ -30,6 +32,9 @@ pub struct HttpClient {
     pub base_url: Url,
     auth_tokens: Option<AuthTokens>,
     captcha: Option<String>,
+    /// Store ingesting every entity of the responses passing through this client.
+    #[cfg(feature = "utils")]
+    store: Option<EntityStore>,
 }
 
 impl Default for HttpClient {
@@ -39,6 +44,8 @@ impl Default for HttpClient {
             base_url: Url::parse(API_URL).expect("error parsing the base url"),
             auth_tokens: None,
             captcha: None,
+            #[cfg(feature = "utils")]
+            store: None,
         }
     }
 }
@@ -127,6 +134,15 @@ impl HttpClient {
             return Err(Error::ServerError(status_code.as_u16(), res.text().await?));
         }
 
+        #[cfg(feature = "utils")]
+        if let Some(store) = &self.store {
+            let res = res
+                .json::<WithRawValue<<E::Response as FromResponse>::Response>>()
+                .await?;
+            store.ingest_value(&res.value);
+            return Ok(FromResponse::from_response(res.data));
+        }
+
         let res = res
             .json::<<E::Response as FromResponse>::Response>()
             .await?;
@@ -170,17 +186,59 @@ impl HttpClient {
     pub fn clear_captcha(&mut self) {
         self.captcha = None;
     }
+
+    /// Get the entity store fed by this client.
+    #[cfg(feature = "utils")]
+    pub fn get_store(&self) -> Option<&EntityStore> {
+        self.store.as_ref()
+    }
+
+    /// Set an entity store that will ingest every response passing through this client.
+    #[cfg(feature = "utils")]
+    pub fn set_store(&mut self, store: EntityStore) {
+        self.store = Some(store);
+    }
+
+    /// Stop feeding the entity store.
+    #[cfg(feature = "utils")]
+    pub fn clear_store(&mut self) {
+        self.store = None;
+    }
     /// Create a new client of api.mangadex.dev
     pub fn api_dev_client() -> Self{
         Self { 
             client: Client::new(), 
             base_url: Url::parse(API_DEV_URL).expect("error parsing the base url"), 
             auth_tokens: None, 
-            captcha: None 
+            captcha: None,
+            #[cfg(feature = "utils")]
+            store: None,
         }
     }
 }
 
+/// A response body decoded both as `T` and as raw JSON, for the entity store.
+///
+/// Decoding `T` happens inside the body deserialization
+/// so that failures are still reported as `reqwest` decode errors.
+#[cfg(feature = "utils")]
+struct WithRawValue<T> {
+    value: serde_json::Value,
+    data: T,
+}
+
+#[cfg(feature = "utils")]
+impl<'de, T: DeserializeOwned> serde::Deserialize<'de> for WithRawValue<T> {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        let data = T::deserialize(&value).map_err(serde::de::Error::custom)?;
+        Ok(Self { value, data })
+    }
+}
+
 /// Helper macro to quickly implement the `Endpoint` trait,
 /// and optionally a `send()` method for the input struct.
 ///
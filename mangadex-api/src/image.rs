@@ -0,0 +1,225 @@
+//! Identify page images from their header, without decoding them.
+
+/// Image formats accepted by MangaDex.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    Gif,
+    Webp,
+}
+
+impl ImageFormat {
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Self::Jpeg => "image/jpeg",
+            Self::Png => "image/png",
+            Self::Gif => "image/gif",
+            Self::Webp => "image/webp",
+        }
+    }
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Jpeg => "jpg",
+            Self::Png => "png",
+            Self::Gif => "gif",
+            Self::Webp => "webp",
+        }
+    }
+    /// Guess the format from the magic bytes.
+    pub fn from_magic_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(Self::Jpeg)
+        } else if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(Self::Png)
+        } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+            Some(Self::Gif)
+        } else if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+            Some(Self::Webp)
+        } else {
+            None
+        }
+    }
+}
+
+/// Format and dimensions of an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageInfo {
+    pub format: ImageFormat,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ImageInfo {
+    /// Read the format and dimensions from the image header.
+    ///
+    /// Returns `None` if the format isn't supported or the header is truncated.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let format = ImageFormat::from_magic_bytes(bytes)?;
+        let (width, height) = match format {
+            ImageFormat::Png => {
+                // The IHDR chunk always comes first
+                if bytes.get(12..16)? != b"IHDR" {
+                    return None;
+                }
+                (be_u32(bytes, 16)?, be_u32(bytes, 20)?)
+            }
+            ImageFormat::Gif => (
+                u16::from_le_bytes([*bytes.get(6)?, *bytes.get(7)?]).into(),
+                u16::from_le_bytes([*bytes.get(8)?, *bytes.get(9)?]).into(),
+            ),
+            ImageFormat::Jpeg => jpeg_dimensions(bytes)?,
+            ImageFormat::Webp => webp_dimensions(bytes)?,
+        };
+        Some(Self {
+            format,
+            width,
+            height,
+        })
+    }
+}
+
+fn be_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([
+        *bytes.get(offset)?,
+        *bytes.get(offset + 1)?,
+    ]))
+}
+
+fn be_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        bytes.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+/// Walk the JPEG segments up to the first start of frame.
+fn jpeg_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    let mut offset = 2;
+    loop {
+        if *bytes.get(offset)? != 0xFF {
+            return None;
+        }
+        let marker = *bytes.get(offset + 1)?;
+        match marker {
+            // Fill bytes
+            0xFF => offset += 1,
+            // Markers without a length
+            0x01 | 0xD0..=0xD7 => offset += 2,
+            // SOF markers, except DHT, JPG and DAC
+            0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) => {
+                let height = be_u16(bytes, offset + 5)?;
+                let width = be_u16(bytes, offset + 7)?;
+                return Some((width.into(), height.into()));
+            }
+            // End of image or start of scan without a frame
+            0xD9 | 0xDA => return None,
+            _ => offset += 2 + usize::from(be_u16(bytes, offset + 2)?),
+        }
+    }
+}
+
+fn le_u24(bytes: &[u8], offset: usize) -> Option<u32> {
+    let bytes = bytes.get(offset..offset + 3)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]))
+}
+
+/// Read the dimensions from the first chunk, which depends on the WebP flavour.
+fn webp_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    match bytes.get(12..16)? {
+        // Lossy, the frame header follows the 3 bytes frame tag and the start code
+        b"VP8 " => {
+            if bytes.get(23..26)? != [0x9D, 0x01, 0x2A] {
+                return None;
+            }
+            let width = u16::from_le_bytes([*bytes.get(26)?, *bytes.get(27)?]) & 0x3FFF;
+            let height = u16::from_le_bytes([*bytes.get(28)?, *bytes.get(29)?]) & 0x3FFF;
+            Some((width.into(), height.into()))
+        }
+        // Lossless, 14 bits per dimension after the signature byte
+        b"VP8L" => {
+            if *bytes.get(20)? != 0x2F {
+                return None;
+            }
+            let bits = u32::from_le_bytes(bytes.get(21..25)?.try_into().ok()?);
+            Some(((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1))
+        }
+        // Extended, with the canvas size
+        b"VP8X" => Some((le_u24(bytes, 24)? + 1, le_u24(bytes, 27)? + 1)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn png_dimensions() {
+        let mut png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR".to_vec();
+        png.extend_from_slice(&800u32.to_be_bytes());
+        png.extend_from_slice(&1200u32.to_be_bytes());
+        assert_eq!(
+            ImageInfo::from_bytes(&png),
+            Some(ImageInfo {
+                format: ImageFormat::Png,
+                width: 800,
+                height: 1200
+            })
+        );
+    }
+
+    #[test]
+    fn jpeg_dimensions_after_app_segments() {
+        let jpeg: Vec<u8> = vec![
+            0xFF, 0xD8, // SOI
+            0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00, // APP0 with 2 bytes of data
+            0xFF, 0xC0, 0x00, 0x0B, 0x08, 0x04, 0xB0, 0x03, 0x20, 0x01, // SOF0 1200x800
+        ];
+        assert_eq!(
+            ImageInfo::from_bytes(&jpeg),
+            Some(ImageInfo {
+                format: ImageFormat::Jpeg,
+                width: 800,
+                height: 1200
+            })
+        );
+    }
+
+    #[test]
+    fn webp_dimensions_of_each_flavour() {
+        let webp = |chunk: &[u8], data: &[u8]| {
+            let mut webp = b"RIFF\x00\x00\x00\x00WEBP".to_vec();
+            webp.extend_from_slice(chunk);
+            webp.extend_from_slice(&[0; 4]);
+            webp.extend_from_slice(data);
+            ImageInfo::from_bytes(&webp).map(|info| (info.format, info.width, info.height))
+        };
+        // 3 bytes frame tag, start code, then 800x1200
+        let lossy = [0, 0, 0, 0x9D, 0x01, 0x2A, 0x20, 0x03, 0xB0, 0x04];
+        assert_eq!(webp(b"VP8 ", &lossy), Some((ImageFormat::Webp, 800, 1200)));
+        // Signature, then 799 and 1199 on 14 bits each
+        let bits: u32 = 799 | (1199 << 14);
+        let mut lossless = vec![0x2F];
+        lossless.extend_from_slice(&bits.to_le_bytes());
+        assert_eq!(
+            webp(b"VP8L", &lossless),
+            Some((ImageFormat::Webp, 800, 1200))
+        );
+        // Flags, then the canvas size minus one on 24 bits each
+        let extended = [0, 0, 0, 0, 0x1F, 0x03, 0x00, 0xAF, 0x04, 0x00];
+        assert_eq!(
+            webp(b"VP8X", &extended),
+            Some((ImageFormat::Webp, 800, 1200))
+        );
+    }
+
+    #[test]
+    fn unsupported_or_truncated_images() {
+        assert_eq!(ImageInfo::from_bytes(b"GIF89a\x20"), None);
+        assert_eq!(ImageInfo::from_bytes(b"RIFF\x00\x00\x00\x00WEBP"), None);
+        assert_eq!(
+            ImageFormat::from_magic_bytes(b"GIF87a"),
+            Some(ImageFormat::Gif)
+        );
+    }
+}
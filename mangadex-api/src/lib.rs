@@ -3,6 +3,7 @@
 #![cfg_attr(not(feature = "multi-thread"), allow(clippy::await_holding_refcell_ref))]
 
 pub mod constants;
+pub mod image;
 #[macro_use]
 mod http_client;
 pub mod v5;
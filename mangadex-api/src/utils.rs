@@ -1,8 +1,66 @@
+use mangadex_api_schema::v5::ChapterObject;
+use mangadex_api_types::error::{Error, Result};
+use mangadex_api_types::ContentRating;
 use reqwest::Client;
 
+use crate::v5::manga::feed::GetMangaFeedBuilder;
 use crate::MangaDexClient;
 
 pub mod download;
+pub mod export;
+pub mod library;
+pub mod resolver;
+pub mod store;
+pub mod sync;
+pub mod upload;
+
+/// Turn the error of an endpoint builder into an [`Error::RequestBuilderError`].
+///
+/// ```rust, ignore
+/// client.manga().get().manga_id(id).build().map_err(request_builder_error)?
+/// ```
+pub(crate) fn request_builder_error<E: std::fmt::Display>(e: E) -> Error {
+    Error::RequestBuilderError(e.to_string())
+}
+
+/// Every content rating, the list endpoints leave some out by default.
+pub(crate) const CONTENT_RATINGS: [ContentRating; 4] = [
+    ContentRating::Safe,
+    ContentRating::Suggestive,
+    ContentRating::Erotica,
+    ContentRating::Pornographic,
+];
+
+/// Maximum number of chapters the feeds return per request.
+pub(crate) const FEED_LIMIT: u32 = 500;
+
+/// Fetch every page of a manga feed, in every content rating.
+///
+/// `builder` holds the other filters, its `limit` and `offset` are overwritten.
+pub(crate) async fn whole_manga_feed(
+    mut builder: GetMangaFeedBuilder,
+) -> Result<Vec<ChapterObject>> {
+    for content_rating in CONTENT_RATINGS {
+        builder = builder.add_content_rating(content_rating);
+    }
+    let mut chapters: Vec<ChapterObject> = Vec::new();
+    loop {
+        let res = builder
+            .clone()
+            .limit(FEED_LIMIT)
+            .offset(chapters.len() as u32)
+            .build()
+            .map_err(request_builder_error)?
+            .send()
+            .await??;
+        let total = res.total as usize;
+        let is_empty = res.data.is_empty();
+        chapters.extend(res.data);
+        if is_empty || chapters.len() >= total {
+            return Ok(chapters);
+        }
+    }
+}
 
 /// Gives you the `reqwest::Client` from the `MangaDexClient`
 /// Comes handy when you don't want to build a new `reqwest` Client
@@ -1,37 +1,170 @@
 pub mod chapter;
 pub mod cover;
+pub mod manga;
+pub mod progress;
+pub(crate) mod sink;
+pub mod throttle;
 
 use bytes::Bytes;
+use mangadex_api_schema::v5::ChapterObject;
+use mangadex_api_types::error::{Error, Result};
 use uuid::Uuid;
 
+use crate::utils::library::Library;
+use crate::utils::sync::LibrarySyncBuilder;
 use crate::HttpClientRef;
 
-use self::{chapter::ChapterDownloadBuilder, cover::{CoverDownloadBuilder, CoverQuality}};
-
+use self::{
+    chapter::{AtHomeReporter, ChapterDownloadBuilder},
+    cover::{CoverBulkDownloadBuilder, CoverDownloadBuilder, CoverQuality},
+    manga::MangaDownloadBuilder,
+    throttle::BandwidthLimiter,
+};
 
 pub type DownloadElement = (String, Option<Bytes>);
 
 #[derive(Debug)]
 pub struct DownloadBuilder {
     http_client: HttpClientRef,
+    bandwidth_limit: Option<BandwidthLimiter>,
+    reporter: Option<AtHomeReporter>,
 }
 
 impl DownloadBuilder {
     #[doc(hidden)]
     pub(crate) fn new(http_client: HttpClientRef) -> Self {
-        Self { http_client }
+        Self {
+            http_client,
+            bandwidth_limit: None,
+            reporter: None,
+        }
+    }
+    /// Share `limiter` between every download built from this builder.
+    ///
+    /// It applies on top of the limits set on each download.
+    pub fn bandwidth_limit(mut self, limiter: BandwidthLimiter) -> Self {
+        self.bandwidth_limit = Some(limiter);
+        self
+    }
+    /// Send the MangaDex@Home reports of every chapter download built from this builder from `reporter`.
+    ///
+    /// Reporting still has to be enabled on each download.
+    pub fn reporter(mut self, reporter: AtHomeReporter) -> Self {
+        self.reporter = Some(reporter);
+        self
+    }
+    fn bandwidth_limits(&self) -> Vec<BandwidthLimiter> {
+        self.bandwidth_limit.iter().cloned().collect()
     }
     pub fn chapter(&self, id: Uuid) -> ChapterDownloadBuilder {
-        ChapterDownloadBuilder::default()
+        let builder = ChapterDownloadBuilder::default()
             .http_client(self.http_client.clone())
             .id(id)
             .force_port_443(false)
             .report(false)
+            .bandwidth_limits(self.bandwidth_limits());
+        match &self.reporter {
+            Some(reporter) => builder.reporter(reporter.clone()),
+            None => builder,
+        }
+    }
+
+    /// Same as [`Self::chapter`], but external chapters are detected up front, without any request.
+    ///
+    /// The download doesn't fetch the chapter again.
+    ///
+    /// Returns [`Error::ExternalChapter`] if `chapter` links to an external source.
+    pub fn chapter_object(&self, chapter: &ChapterObject) -> Result<ChapterDownloadBuilder> {
+        if let Some(url) = &chapter.attributes.external_url {
+            return Err(Error::ExternalChapter {
+                chapter_id: chapter.id,
+                url: url.clone(),
+            });
+        }
+        Ok(self
+            .chapter(chapter.id)
+            .attributes(chapter.attributes.clone()))
+    }
+
+    /// Download the chapters of a manga, see [`MangaDownload`](manga::MangaDownload).
+    pub fn manga(&self, id: Uuid) -> MangaDownloadBuilder {
+        let builder = MangaDownloadBuilder::default()
+            .http_client(self.http_client.clone())
+            .manga_id(id)
+            .bandwidth_limits(self.bandwidth_limits());
+        match &self.reporter {
+            Some(reporter) => builder.reporter(reporter.clone()),
+            None => builder,
+        }
     }
 
     pub fn cover(&self) -> CoverDownloadBuilder {
         CoverDownloadBuilder::default()
             .http_client(self.http_client.clone())
             .quality(CoverQuality::Default)
+            .bandwidth_limits(self.bandwidth_limits())
+    }
+
+    /// Download every cover of one or more manga, see [`CoverBulkDownload`](cover::CoverBulkDownload).
+    pub fn covers(&self) -> CoverBulkDownloadBuilder {
+        CoverBulkDownloadBuilder::default()
+            .http_client(self.http_client.clone())
+            .bandwidth_limits(self.bandwidth_limits())
+    }
+
+    /// Keep `library` in step with the followed manga, see [`LibrarySync`](crate::utils::sync::LibrarySync).
+    pub fn library_sync(&self, library: Library) -> LibrarySyncBuilder {
+        let builder = LibrarySyncBuilder::default()
+            .http_client(self.http_client.clone())
+            .library(library)
+            .bandwidth_limits(self.bandwidth_limits());
+        match &self.reporter {
+            Some(reporter) => builder.reporter(reporter.clone()),
+            None => builder,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mangadex_api_types::error::Error;
+    use uuid::Uuid;
+
+    use crate::MangaDexClient;
+
+    #[test]
+    fn external_chapters_are_rejected_up_front() {
+        let chapter = serde_json::from_value(serde_json::json!({
+            "id": Uuid::new_v4(),
+            "type": "chapter",
+            "attributes": {
+                "title": "",
+                "volume": null,
+                "chapter": "1",
+                "pages": 0,
+                "translatedLanguage": "en",
+                "externalUrl": "https://mangaplus.shueisha.co.jp/viewer/1000000",
+                "version": 1,
+                "createdAt": "2023-01-01T00:00:00+00:00",
+                "updatedAt": null,
+                "publishAt": "2023-01-01T00:00:00+00:00",
+                "readableAt": "2023-01-01T00:00:00+00:00"
+            },
+            "relationships": []
+        }))
+        .unwrap();
+        match MangaDexClient::default()
+            .download()
+            .chapter_object(&chapter)
+        {
+            Err(Error::ExternalChapter { chapter_id, url }) => {
+                assert_eq!(chapter_id, chapter.id);
+                assert_eq!(
+                    url.as_str(),
+                    "https://mangaplus.shueisha.co.jp/viewer/1000000"
+                );
+            }
+            _ => panic!("expected an external chapter error"),
+        }
     }
 }
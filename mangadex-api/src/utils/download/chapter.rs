@@ -1,26 +1,38 @@
+mod manifest;
 mod mode;
 mod pre_download;
 mod report;
+use std::future::Future;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use async_stream::stream;
 use derive_builder::Builder;
-use mangadex_api_schema::v5::AtHomeServer;
+use futures::StreamExt;
+use mangadex_api_schema::v5::{AtHomeServer, ChapterAttributes};
 use mangadex_api_types::error::{Error, Result};
 use reqwest::Response;
+use tokio::io::AsyncWrite;
 use tokio::pin;
+use tokio::sync::Mutex;
 use tokio_stream::Stream;
-use tokio_stream::StreamExt;
+use url::Url;
 use uuid::Uuid;
 
-use crate::utils::get_reqwest_client;
+use crate::utils::{get_reqwest_client, request_builder_error};
 use crate::{HttpClientRef, MangaDexClient};
 
+use super::progress::{DownloadTarget, ProgressHandler, ProgressReporter};
+use super::throttle::BandwidthLimiter;
 use super::DownloadElement;
 
+pub use manifest::{page_hash_prefix, verify_page, ChapterManifest, MANIFEST_FILE_NAME};
 pub use mode::DownloadMode;
-pub use pre_download::AtHomePreDownloadImageData;
-pub use report::AtHomeReport;
+pub use pre_download::{is_retryable_error, AtHomePreDownloadImageData, UPLOADS_ORIGIN};
+pub use report::{
+    is_at_home_node, AtHomeReport, AtHomeReporter, ReporterConfig, ReporterMetrics,
+    AT_HOME_REPORT_URL,
+};
 
 #[derive(Clone, Builder)]
 #[builder(setter(into, strip_option), pattern = "owned")]
@@ -34,6 +46,11 @@ pub struct ChapterDownload {
     /// Enable reporting at `api.mangadex.network`. \
     /// More details at : https://api.mangadex.org/docs/retrieving-chapter/#mangadexhome-load-successes-failures-and-retries
     report: Option<bool>,
+    /// Background reporter the reports are sent from.
+    ///
+    /// A reporter is spawned for the download if reporting is enabled without one.
+    #[builder(default)]
+    reporter: Option<AtHomeReporter>,
     /// Force selecting from MangaDex@Home servers that use the standard HTTPS port 443.
     ///
     /// While the conventional port for HTTPS traffic is 443 and servers are encouraged to use it,
@@ -44,28 +61,251 @@ pub struct ChapterDownload {
     force_port_443: bool,
     /// Chapter Id
     id: Uuid,
+    /// Attributes of the chapter, if they were already fetched.
+    ///
+    /// They are checked for an external URL before the MangaDex@Home request.
+    /// Without them, the chapter is only fetched if MangaDex@Home has no pages for it.
+    #[builder(default)]
+    attributes: Option<ChapterAttributes>,
+    /// Maximum number of pages downloaded at the same time.
+    ///
+    /// The pages are still yielded in page order.
+    /// Defaults to `1`, downloading the pages one after another.
+    #[builder(default)]
+    concurrency: Option<usize>,
+    /// Number of times a page is retried against a new MangaDex@Home node
+    /// before falling back to the [`UPLOADS_ORIGIN`].
+    ///
+    /// Defaults to `2`.
+    #[builder(default)]
+    node_retries: Option<usize>,
+    /// Receives the byte-level download events of every page.
+    #[builder(default)]
+    progress: Option<ProgressHandler>,
+    /// Bandwidth limiters shared by the pages, see [`BandwidthLimiter`].
+    #[builder(setter(each = "bandwidth_limit"), default)]
+    bandwidth_limits: Vec<BandwidthLimiter>,
+}
+
+/// The MangaDex@Home node shared by the pages of a chapter download.
+struct AtHomeNode {
+    current: Mutex<Arc<AtHomeServer>>,
 }
 
 impl ChapterDownload {
-    pub async fn build_at_home_urls_as_stream(
-        &self,
-    ) -> Result<impl Stream<Item = AtHomePreDownloadImageData> + '_> {
+    async fn fetch_at_home(&self) -> Result<AtHomeServer> {
         let client = MangaDexClient::new_with_http_client_ref(self.http_client.clone());
-        let at_home: Arc<AtHomeServer> = Arc::new(
-            match client
-                .at_home()
-                .server()
-                .force_port_443(self.force_port_443)
+        client
+            .at_home()
+            .server()
+            .force_port_443(self.force_port_443)
+            .chapter_id(self.id)
+            .build()
+            .map_err(request_builder_error)?
+            .send()
+            .await
+    }
+    /// Replace the `stale` node with a new one.
+    ///
+    /// If another page already refreshed it, the current node is returned without fetching a new one.
+    async fn refresh_node(
+        &self,
+        node: &AtHomeNode,
+        stale: &Arc<AtHomeServer>,
+    ) -> Result<Arc<AtHomeServer>> {
+        let mut current = node.current.lock().await;
+        if !Arc::ptr_eq(&current, stale) {
+            return Ok(current.clone());
+        }
+        *current = Arc::new(self.fetch_at_home().await?);
+        Ok(current.clone())
+    }
+    /// Run `attempt` for a page, moving to a new MangaDex@Home node on network errors, `5xx` or `403`
+    /// and falling back to the [`UPLOADS_ORIGIN`] as a last resort.
+    ///
+    /// `attempt` gets the page with its current node and the URL to download from.
+    async fn with_failover<T, F, Fut>(
+        &self,
+        node: &AtHomeNode,
+        mut page: AtHomePreDownloadImageData,
+        mut attempt: F,
+    ) -> Result<T>
+    where
+        F: FnMut(AtHomePreDownloadImageData, Url) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        page.at_home = node.current.lock().await.clone();
+        let res = async {
+            let mut retries = self.node_retries.unwrap_or(2);
+            let mut page_url = page.build_page_url()?;
+            let mut retried = 0;
+            loop {
+                match attempt(page.clone(), page_url.clone()).await {
+                    Err(e)
+                        if is_retryable_error(&e)
+                            && !page_url.as_str().starts_with(UPLOADS_ORIGIN) =>
+                    {
+                        retried += 1;
+                        page.retried(retried, &e);
+                    }
+                    res => return res,
+                }
+                page_url = self.next_page_url(node, &mut page, &mut retries).await?;
+            }
+        }
+        .await;
+        page.failed(res)
+    }
+    /// Move `page` to a new MangaDex@Home node, or to the [`UPLOADS_ORIGIN`] once `retries` is exhausted
+    /// or no new node can be fetched.
+    async fn next_page_url(
+        &self,
+        node: &AtHomeNode,
+        page: &mut AtHomePreDownloadImageData,
+        retries: &mut usize,
+    ) -> Result<Url> {
+        let refreshed = if *retries == 0 {
+            None
+        } else {
+            *retries -= 1;
+            self.refresh_node(node, &page.at_home).await.ok()
+        };
+        match refreshed {
+            Some(at_home) => {
+                page.at_home = at_home;
+                page.build_page_url()
+            }
+            None => page.build_origin_page_url(),
+        }
+    }
+    async fn download_page<C>(
+        &self,
+        node: &AtHomeNode,
+        page: AtHomePreDownloadImageData,
+        should_skip: C,
+    ) -> Result<DownloadElement>
+    where
+        C: FnMut(&AtHomePreDownloadImageData, &Response) -> bool + Copy,
+    {
+        self.with_failover(node, page, |page, page_url| async move {
+            page.download_from_with_checker(page_url, should_skip).await
+        })
+        .await
+    }
+    async fn download_page_to_dir(
+        &self,
+        node: &AtHomeNode,
+        page: AtHomePreDownloadImageData,
+        dir: &Path,
+    ) -> Result<PathBuf> {
+        self.with_failover(node, page, |page, page_url| async move {
+            match page
+                .download_from_to_dir(page_url, dir, |_, _| false)
+                .await?
+            {
+                Some(path) => Ok(path),
+                None => unreachable!("the page is never skipped"),
+            }
+        })
+        .await
+    }
+    /// Same as [`Self::with_failover`], except that a page is not retried once bytes were written to `writer`.
+    async fn download_page_into<W>(
+        &self,
+        node: &AtHomeNode,
+        mut page: AtHomePreDownloadImageData,
+        writer: &mut W,
+    ) -> Result<usize>
+    where
+        W: AsyncWrite + Unpin + ?Sized,
+    {
+        page.at_home = node.current.lock().await.clone();
+        let res = async {
+            let mut retries = self.node_retries.unwrap_or(2);
+            let mut page_url = page.build_page_url()?;
+            let mut retried = 0;
+            loop {
+                let mut written = 0;
+                match page
+                    .download_from_into(page_url.clone(), |_, _| false, writer, &mut written)
+                    .await
+                {
+                    Ok(_) => return Ok(written),
+                    Err(e) if written != 0 || !is_retryable_error(&e) => return Err(e),
+                    Err(e) if page_url.as_str().starts_with(UPLOADS_ORIGIN) => return Err(e),
+                    Err(e) => {
+                        retried += 1;
+                        page.retried(retried, &e);
+                    }
+                }
+                page_url = self.next_page_url(node, &mut page, &mut retries).await?;
+            }
+        }
+        .await;
+        page.failed(res)
+    }
+    fn external_chapter_error(&self, url: Url) -> Error {
+        Error::ExternalChapter {
+            chapter_id: self.id,
+            url,
+        }
+    }
+    /// Fetch the external URL of the chapter.
+    async fn fetch_external_url(&self) -> Result<Option<Url>> {
+        Ok(
+            MangaDexClient::new_with_http_client_ref(self.http_client.clone())
+                .chapter()
+                .get()
                 .chapter_id(self.id)
                 .build()
-            {
-                Ok(d) => d,
-                Err(d) => return Result::Err(Error::RequestBuilderError(d.to_string())),
+                .map_err(request_builder_error)?
+                .send()
+                .await?
+                .data
+                .attributes
+                .external_url,
+        )
+    }
+    /// Fetch the first MangaDex@Home node of the download.
+    ///
+    /// External chapters return [`Error::ExternalChapter`]. Without the
+    /// [`attributes`](ChapterDownloadBuilder::attributes), the chapter is only fetched to check
+    /// for an external URL when the MangaDex@Home request fails or has no pages.
+    async fn fetch_first_at_home(&self) -> Result<Arc<AtHomeServer>> {
+        if let Some(attributes) = &self.attributes {
+            if let Some(url) = &attributes.external_url {
+                return Err(self.external_chapter_error(url.clone()));
             }
-            .send()
-            .await?,
-        );
+            return Ok(Arc::new(self.fetch_at_home().await?));
+        }
+        let res = self.fetch_at_home().await;
+        let has_pages = matches!(&res, Ok(at_home) if !at_home.chapter.data.is_empty());
+        if !has_pages {
+            // The lookup only explains the failure, its own error doesn't replace the original one
+            if let Ok(Some(url)) = self.fetch_external_url().await {
+                return Err(self.external_chapter_error(url));
+            }
+        }
+        Ok(Arc::new(res?))
+    }
+    pub async fn build_at_home_urls_as_stream(
+        &self,
+    ) -> Result<impl Stream<Item = AtHomePreDownloadImageData> + '_> {
+        let at_home = self.fetch_first_at_home().await?;
+        Ok(self.page_stream(at_home).await)
+    }
+    /// The pages of the chapter, all starting on the `at_home` node.
+    async fn page_stream(
+        &self,
+        at_home: Arc<AtHomeServer>,
+    ) -> impl Stream<Item = AtHomePreDownloadImageData> + '_ {
+        let client = MangaDexClient::new_with_http_client_ref(self.http_client.clone());
         let http_client = Arc::new(get_reqwest_client(&client).await);
+        let reporter = match (self.report, &self.reporter) {
+            (Some(true), Some(reporter)) => Some(reporter.clone()),
+            (Some(true), None) => Some(AtHomeReporter::new(http_client.clone())),
+            _ => None,
+        };
         let page_filenames = match match self.mode.clone() {
             None => Default::default(),
             Some(d) => d,
@@ -74,8 +314,18 @@ impl ChapterDownload {
             DownloadMode::DataSaver => Arc::clone(&at_home).chapter.data_saver.clone(),
         };
 
-        Ok(stream! {
-            for filename in page_filenames {
+        stream! {
+            for (index, filename) in page_filenames.into_iter().enumerate() {
+                let progress = self.progress.clone().map(|handler| {
+                    ProgressReporter::new(
+                        handler,
+                        DownloadTarget::Page {
+                            chapter_id: self.id,
+                            index: index + 1,
+                            filename: filename.clone(),
+                        },
+                    )
+                });
                 yield AtHomePreDownloadImageData {
                     http_client: http_client.clone(),
                     filename: filename.clone(),
@@ -85,40 +335,184 @@ impl ChapterDownload {
                     },
                     at_home: Arc::clone(&at_home),
                     report: self.report.unwrap_or(false),
+                    reporter: reporter.clone(),
+                    progress,
+                    bandwidth_limits: self.bandwidth_limits.clone(),
                 };
             }
-        })
+        }
     }
     pub async fn build_at_home_urls(&self) -> Result<Vec<AtHomePreDownloadImageData>> {
-        let mut datas: Vec<AtHomePreDownloadImageData> = Vec::new();
-        let stream_ = self.build_at_home_urls_as_stream().await?;
+        let at_home = self.fetch_first_at_home().await?;
+        Ok(self.pages(at_home).await)
+    }
+    async fn pages(&self, at_home: Arc<AtHomeServer>) -> Vec<AtHomePreDownloadImageData> {
+        let stream_ = self.page_stream(at_home).await;
         pin!(stream_);
-        while let Some(data) = stream_.next().await {
-            datas.push(data);
-        }
-        Ok(datas)
+        stream_.collect().await
+    }
+    fn concurrency(&self) -> usize {
+        self.concurrency.unwrap_or(1).max(1)
+    }
+    async fn build_node(&self) -> Result<(Arc<AtHomeNode>, Vec<AtHomePreDownloadImageData>)> {
+        let at_home = self.fetch_first_at_home().await?;
+        let file_names = self.pages(at_home.clone()).await;
+        Ok((
+            Arc::new(AtHomeNode {
+                current: Mutex::new(at_home),
+            }),
+            file_names,
+        ))
     }
     pub async fn download_element_vec(&self) -> Result<Vec<DownloadElement>> {
-        let file_names = self.build_at_home_urls().await?;
-        let mut datas: Vec<DownloadElement> = Vec::new();
-        for filename in file_names {
-            datas.push(filename.download().await?);
+        let (node, file_names) = self.build_node().await?;
+        let datas = futures::stream::iter(file_names)
+            .map(|filename| {
+                let node = node.clone();
+                async move { self.download_page(&node, filename, |_, _| false).await }
+            })
+            .buffered(self.concurrency());
+        pin!(datas);
+        let mut elements: Vec<DownloadElement> = Vec::new();
+        while let Some(data) = datas.next().await {
+            elements.push(data?);
         }
-        Ok(datas)
+        Ok(elements)
     }
+    /// Download the chapter pages to `dir`, each page being saved under its page filename.
+    ///
+    /// Pages are streamed to temporary files and renamed once complete,
+    /// so they are never held in memory.
+    /// Returns the page paths in page order.
+    pub async fn download_to_dir<P: AsRef<Path>>(&self, dir: P) -> Result<Vec<PathBuf>> {
+        let dir = dir.as_ref();
+        tokio::fs::create_dir_all(dir).await?;
+        let (node, file_names) = self.build_node().await?;
+        let paths = futures::stream::iter(file_names)
+            .map(|filename| {
+                let node = node.clone();
+                async move { self.download_page_to_dir(&node, filename, dir).await }
+            })
+            .buffered(self.concurrency());
+        pin!(paths);
+        let mut saved: Vec<PathBuf> = Vec::new();
+        while let Some(path) = paths.next().await {
+            saved.push(path?);
+        }
+        Ok(saved)
+    }
+    /// Download the chapter pages to `dir`, resuming a previous download of the same chapter.
+    ///
+    /// A [`ChapterManifest`] is kept in `dir` and updated after each page.
+    /// Pages already on disk are verified, against the SHA-256 prefix of their filename in [`DownloadMode::Normal`]
+    /// or against the recorded size otherwise, and only the missing or corrupt pages are downloaded again.
+    ///
+    /// Every page is attempted, the first error is returned once they are all done.
+    pub async fn download_to_dir_resumable<P: AsRef<Path>>(
+        &self,
+        dir: P,
+    ) -> Result<ChapterManifest> {
+        let dir = dir.as_ref();
+        tokio::fs::create_dir_all(dir).await?;
+        let (node, file_names) = self.build_node().await?;
+        let mode = self.mode.clone().unwrap_or_default();
+        let mut manifest = {
+            let at_home = node.current.lock().await;
+            ChapterManifest::new(self.id, &at_home, mode.clone())
+        };
+        let previous = ChapterManifest::load(dir)
+            .await?
+            .filter(|previous| previous.is_same_upload(&manifest));
+        for page in manifest.pages.clone() {
+            let expected_size = previous
+                .as_ref()
+                .and_then(|previous| previous.completed.get(&page).copied());
+            if let Some(size) = verify_page(dir.join(&page), &page, &mode, expected_size).await? {
+                manifest.completed.insert(page, size);
+            }
+        }
+        manifest.save(dir).await?;
+        let mut missing: Vec<AtHomePreDownloadImageData> = Vec::new();
+        for page in file_names {
+            match (manifest.completed.get(&page.filename), &page.progress) {
+                // Report the pages already on disk so progress bars still reach the end
+                (Some(size), Some(progress)) => progress.finished(*size),
+                (Some(_), None) => {}
+                (None, _) => missing.push(page),
+            }
+        }
+        let manifest = Mutex::new(manifest);
+        let mut error: Option<Error> = None;
+        {
+            let results = futures::stream::iter(missing)
+                .map(|page| {
+                    let node = node.clone();
+                    let manifest = &manifest;
+                    let mode = &mode;
+                    async move {
+                        let filename = page.filename.clone();
+                        let progress = page.progress.clone();
+                        let path = self.download_page_to_dir(&node, page, dir).await?;
+                        let size = tokio::fs::metadata(&path).await?.len();
+                        if verify_page(&path, &filename, mode, Some(size))
+                            .await?
+                            .is_none()
+                        {
+                            let _ = tokio::fs::remove_file(&path).await;
+                            let error = Error::UnexpectedError(anyhow::Error::msg(format!(
+                                "the page {filename} doesn't match its hash"
+                            )));
+                            if let Some(progress) = progress {
+                                progress.failed(&error);
+                            }
+                            return Err(error);
+                        }
+                        let mut manifest = manifest.lock().await;
+                        manifest.completed.insert(filename, size);
+                        manifest.save(dir).await
+                    }
+                })
+                .buffer_unordered(self.concurrency());
+            pin!(results);
+            while let Some(result) = results.next().await {
+                if let Err(e) = result {
+                    error.get_or_insert(e);
+                }
+            }
+        }
+        match error {
+            Some(e) => Err(e),
+            None => Ok(manifest.into_inner()),
+        }
+    }
+    /// Stream the chapter pages, one after another, to the writers returned by `writer_for`.
+    ///
+    /// `writer_for` is called with each page filename.
+    /// A page is only retried on another node if nothing was written to its writer yet.
+    /// Returns the page filenames with the number of bytes written.
+    pub async fn download_into<F, W>(&self, mut writer_for: F) -> Result<Vec<(String, usize)>>
+    where
+        F: FnMut(&str) -> W,
+        W: AsyncWrite + Unpin,
+    {
+        let (node, file_names) = self.build_node().await?;
+        let mut written: Vec<(String, usize)> = Vec::new();
+        for page in file_names {
+            let filename = page.filename.clone();
+            let mut writer = writer_for(&filename);
+            let bytes = self.download_page_into(&node, page, &mut writer).await?;
+            written.push((filename, bytes));
+        }
+        Ok(written)
+    }
+    /// Download chapter with stream output.
+    ///
+    /// Up to [`concurrency`](ChapterDownloadBuilder::concurrency) pages are downloaded at the same time,
+    /// the pages are yielded in page order with their 1-based index.
     pub async fn download_stream(
         &self,
     ) -> Result<impl Stream<Item = (Result<DownloadElement>, usize, usize, String)> + '_> {
-        let file_names = self.build_at_home_urls().await?;
-        let mut index: usize = 0;
-        let len = file_names.len();
-        Ok(stream! {
-            for filename in file_names {
-                let data = filename.download().await;
-                index += 1;
-                yield (data, index, len, filename.filename.clone());
-            }
-        })
+        self.download_stream_with_checker(|_, _| false).await
     }
     /// Download chapter with stream output 
     pub async fn download_stream_with_checker<C>(
@@ -128,29 +522,288 @@ impl ChapterDownload {
     where
         C: FnMut(&AtHomePreDownloadImageData, &Response) -> bool + std::marker::Copy,
     {
-        let file_names = self.build_at_home_urls().await?;
-        let mut index: usize = 0;
+        let (node, file_names) = self.build_node().await?;
         let len = file_names.len();
-        Ok(stream! {
-            for filename in file_names {
-                let data = filename.download_with_checker(should_check_).await;
-                index += 1;
-                yield (data, index, len, filename.filename.clone());
-            }
-        })
+        let this = self.clone();
+        Ok(futures::stream::iter(file_names.into_iter().enumerate())
+            .map(move |(index, filename)| {
+                let node = node.clone();
+                let this = this.clone();
+                async move {
+                    let page_filename = filename.filename.clone();
+                    let data = this.download_page(&node, filename, should_check_).await;
+                    (data, index + 1, len, page_filename)
+                }
+            })
+            .buffered(self.concurrency()))
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{utils::download::chapter::DownloadMode, MangaDexClient};
+    use crate::{utils::download::chapter::DownloadMode, HttpClient, MangaDexClient};
     use anyhow::{Ok, Result};
+    use mangadex_api_types::error::Error;
+    use serde_json::json;
     use std::{
         fs::{create_dir_all, File},
         io::Write,
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
     };
     use tokio::pin;
     use tokio_stream::StreamExt;
+    use url::Url;
+    use uuid::Uuid;
+    use wiremock::matchers::{method, path, path_regex, query_param};
+    use wiremock::{Mock, MockServer, Request, Respond, ResponseTemplate};
+
+    fn mock_client(mock_server: &MockServer) -> Result<MangaDexClient> {
+        let http_client = HttpClient::builder()
+            .base_url(Url::parse(&mock_server.uri())?)
+            .build()?;
+        Ok(MangaDexClient::new_with_http_client(http_client))
+    }
+
+    /// Serve the chapter entities `expected` times, linking to `external_url` if any.
+    async fn mock_chapter(mock_server: &MockServer, external_url: Option<&str>, expected: u64) {
+        Mock::given(method("GET"))
+            .and(path_regex(r"^/chapter/[0-9a-fA-F-]+$"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "result": "ok",
+                "response": "entity",
+                "data": {
+                    "id": Uuid::new_v4(),
+                    "type": "chapter",
+                    "attributes": {
+                        "title": "",
+                        "volume": null,
+                        "chapter": "1",
+                        "pages": if external_url.is_some() { 0 } else { 1 },
+                        "translatedLanguage": "en",
+                        "externalUrl": external_url,
+                        "version": 1,
+                        "createdAt": "2023-01-01T00:00:00+00:00",
+                        "updatedAt": null,
+                        "publishAt": "2023-01-01T00:00:00+00:00",
+                        "readableAt": "2023-01-01T00:00:00+00:00"
+                    },
+                    "relationships": []
+                }
+            })))
+            .expect(expected)
+            .mount(mock_server)
+            .await;
+    }
+
+    /// Serve `pages` of the chapter `hash` from a MangaDex@Home node on `mock_server`.
+    async fn mock_at_home(mock_server: &MockServer, hash: &str, pages: &[String]) {
+        Mock::given(method("GET"))
+            .and(path_regex(r"^/at-home/server/[0-9a-fA-F-]+$"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "result": "ok",
+                "baseUrl": mock_server.uri(),
+                "chapter": { "hash": hash, "data": pages, "dataSaver": pages }
+            })))
+            .mount(mock_server)
+            .await;
+    }
+
+    /// Answers with the page filename after `delay`, recording when each request came in.
+    struct PageResponder {
+        delay: Duration,
+        requests: Arc<Mutex<Vec<(Instant, Duration)>>>,
+    }
+
+    impl Respond for PageResponder {
+        fn respond(&self, request: &Request) -> ResponseTemplate {
+            self.requests
+                .lock()
+                .unwrap()
+                .push((Instant::now(), self.delay));
+            let filename = request.url.path_segments().unwrap().next_back().unwrap();
+            ResponseTemplate::new(200)
+                .set_body_bytes(filename.as_bytes())
+                .set_delay(self.delay)
+        }
+    }
+
+    /// The highest number of requests waiting for their response at the same time.
+    fn max_in_flight(requests: &[(Instant, Duration)]) -> usize {
+        // Some slack for the time between the response and the next request
+        let slack = Duration::from_millis(20);
+        requests
+            .iter()
+            .map(|(arrival, _)| {
+                requests
+                    .iter()
+                    .filter(|(other, delay)| other <= arrival && *arrival + slack < *other + *delay)
+                    .count()
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[tokio::test]
+    async fn concurrent_downloads_are_bounded_and_keep_the_page_order() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        let client = mock_client(&mock_server)?;
+        let pages: Vec<String> = (1..=6).map(|page| format!("{page}.png")).collect();
+        mock_chapter(&mock_server, None, 0).await;
+        mock_at_home(&mock_server, "hash", &pages).await;
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        for (index, page) in pages.iter().enumerate() {
+            // The first pages are the slowest to answer
+            let delay = Duration::from_millis(100 + 40 * (pages.len() - index) as u64);
+            Mock::given(method("GET"))
+                .and(path(format!("/data/hash/{page}")))
+                .respond_with(PageResponder {
+                    delay,
+                    requests: requests.clone(),
+                })
+                .expect(1)
+                .mount(&mock_server)
+                .await;
+        }
+
+        let elements = client
+            .download()
+            .chapter(Uuid::new_v4())
+            .mode(DownloadMode::Normal)
+            .concurrency(2usize)
+            .build()?
+            .download_element_vec()
+            .await?;
+
+        let filenames: Vec<String> = elements
+            .iter()
+            .map(|(filename, _)| filename.clone())
+            .collect();
+        assert_eq!(filenames, pages);
+        for (filename, bytes) in &elements {
+            assert_eq!(bytes.as_deref(), Some(filename.as_bytes()));
+        }
+        assert_eq!(max_in_flight(&requests.lock().unwrap()), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn concurrency_defaults_to_sequential_downloads() -> Result<()> {
+        let client = MangaDexClient::default();
+        let chapter_id = uuid::Uuid::new_v4();
+        let download = client
+            .download()
+            .chapter(chapter_id)
+            .mode(DownloadMode::Normal)
+            .build()?;
+        assert_eq!(download.concurrency(), 1);
+        let download = client
+            .download()
+            .chapter(chapter_id)
+            .mode(DownloadMode::Normal)
+            .concurrency(0usize)
+            .build()?;
+        assert_eq!(download.concurrency(), 1);
+        let download = client
+            .download()
+            .chapter(chapter_id)
+            .mode(DownloadMode::Normal)
+            .concurrency(4usize)
+            .build()?;
+        assert_eq!(download.concurrency(), 4);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn pages_move_to_a_new_node_on_server_errors() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        let first_node = MockServer::start().await;
+        let second_node = MockServer::start().await;
+        let client = mock_client(&mock_server)?;
+        mock_chapter(&mock_server, None, 0).await;
+        for node in [&first_node, &second_node] {
+            Mock::given(method("GET"))
+                .and(path_regex(r"^/at-home/server/[0-9a-fA-F-]+$"))
+                .and(query_param("forcePort443", "true"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                    "result": "ok",
+                    "baseUrl": node.uri(),
+                    "chapter": { "hash": "hash", "data": ["1.png"], "dataSaver": ["1.png"] }
+                })))
+                .up_to_n_times(1)
+                .expect(1)
+                .mount(&mock_server)
+                .await;
+        }
+        Mock::given(method("GET"))
+            .and(path("/data/hash/1.png"))
+            .respond_with(ResponseTemplate::new(503))
+            .expect(1)
+            .mount(&first_node)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/data/hash/1.png"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(b"page".as_slice()))
+            .expect(1)
+            .mount(&second_node)
+            .await;
+
+        let elements = client
+            .download()
+            .chapter(Uuid::new_v4())
+            .mode(DownloadMode::Normal)
+            .force_port_443(true)
+            .build()?
+            .download_element_vec()
+            .await?;
+
+        assert_eq!(elements.len(), 1);
+        assert_eq!(elements[0].0, "1.png");
+        assert_eq!(elements[0].1.as_deref(), Some(b"page".as_slice()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn external_chapters_are_detected_when_the_at_home_request_fails() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        let client = mock_client(&mock_server)?;
+        let chapter_id = Uuid::new_v4();
+        mock_chapter(
+            &mock_server,
+            Some("https://mangaplus.shueisha.co.jp/viewer/1000000"),
+            1,
+        )
+        .await;
+        Mock::given(method("GET"))
+            .and(path_regex(r"^/at-home/server/[0-9a-fA-F-]+$"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let res = client
+            .download()
+            .chapter(chapter_id)
+            .mode(DownloadMode::Normal)
+            .build()?
+            .download_element_vec()
+            .await;
+
+        match res {
+            Err(Error::ExternalChapter {
+                chapter_id: id,
+                url,
+            }) => {
+                assert_eq!(id, chapter_id);
+                assert_eq!(
+                    url.as_str(),
+                    "https://mangaplus.shueisha.co.jp/viewer/1000000"
+                );
+            }
+            other => panic!("expected an external chapter error, got {other:?}"),
+        }
+        Ok(())
+    }
 
     /// It's from this manga called [`The Grim Reaper Falls In Love With A Human`](https://mangadex.org/title/be2efc56-1669-4e42-9f27-3bd232bca8ea/the-grim-reaper-falls-in-love-with-a-human)
     ///
@@ -0,0 +1,208 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use mangadex_api_schema::v5::AtHomeServer;
+use mangadex_api_types::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use uuid::Uuid;
+
+use crate::utils::download::sink::AtomicFile;
+
+use super::DownloadMode;
+
+/// File name of the manifest written in the chapter download directory.
+pub const MANIFEST_FILE_NAME: &str = "mangadex-manifest.json";
+
+/// State of a resumable chapter download, stored as JSON next to the pages.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChapterManifest {
+    pub chapter_id: Uuid,
+    /// The at-home chapter `hash`.
+    pub hash: String,
+    pub mode: DownloadMode,
+    /// Page filenames in page order.
+    pub pages: Vec<String>,
+    /// Completed page filenames with their size in bytes.
+    pub completed: BTreeMap<String, u64>,
+}
+
+impl ChapterManifest {
+    pub fn new(chapter_id: Uuid, at_home: &AtHomeServer, mode: DownloadMode) -> Self {
+        let pages = match mode {
+            DownloadMode::Normal => at_home.chapter.data.clone(),
+            DownloadMode::DataSaver => at_home.chapter.data_saver.clone(),
+        };
+        Self {
+            chapter_id,
+            hash: at_home.chapter.hash.clone(),
+            mode,
+            pages,
+            completed: BTreeMap::new(),
+        }
+    }
+    pub fn path<P: AsRef<Path>>(dir: P) -> PathBuf {
+        dir.as_ref().join(MANIFEST_FILE_NAME)
+    }
+    /// Read the manifest of `dir`, if any.
+    pub async fn load<P: AsRef<Path>>(dir: P) -> Result<Option<Self>> {
+        let content = match tokio::fs::read(Self::path(dir)).await {
+            Ok(d) => d,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(Error::Io(e)),
+        };
+        match serde_json::from_slice(&content) {
+            Ok(d) => Ok(Some(d)),
+            Err(e) => Err(Error::ParseError(e.to_string())),
+        }
+    }
+    /// Write the manifest to `dir`, replacing the previous one atomically.
+    pub async fn save<P: AsRef<Path>>(&self, dir: P) -> Result<()> {
+        let content = match serde_json::to_vec_pretty(self) {
+            Ok(d) => d,
+            Err(e) => return Err(Error::ParseError(e.to_string())),
+        };
+        let mut file = AtomicFile::create(Self::path(dir)).await?;
+        if let Err(e) = file.file().write_all(&content).await {
+            file.discard().await;
+            return Err(Error::Io(e));
+        }
+        file.persist().await?;
+        Ok(())
+    }
+    /// Check if the previous manifest describes the same chapter upload.
+    pub fn is_same_upload(&self, other: &Self) -> bool {
+        self.chapter_id == other.chapter_id && self.hash == other.hash && self.mode == other.mode
+    }
+    /// Pages not completed yet, in page order.
+    pub fn missing_pages(&self) -> Vec<String> {
+        self.pages
+            .iter()
+            .filter(|page| !self.completed.contains_key(*page))
+            .cloned()
+            .collect()
+    }
+    pub fn is_complete(&self) -> bool {
+        self.pages
+            .iter()
+            .all(|page| self.completed.contains_key(page))
+    }
+}
+
+/// Get the SHA-256 prefix MangaDex embeds in a page filename, like `1-<sha256>.png`.
+pub fn page_hash_prefix(filename: &str) -> Option<&str> {
+    let stem = filename.split('.').next()?;
+    let (_, hash) = stem.split_once('-')?;
+    if hash.is_empty() || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some(hash)
+}
+
+/// Compute the lowercase hexadecimal SHA-256 digest of a file.
+pub async fn sha256_file<P: AsRef<Path>>(path: P) -> Result<String> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buf).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+/// Check a downloaded page, returns its size if it is intact.
+///
+/// Pages downloaded in [`DownloadMode::Normal`] are verified against the SHA-256 prefix of their filename.
+/// Other pages can only be checked against the size recorded in the manifest.
+pub async fn verify_page<P: AsRef<Path>>(
+    path: P,
+    filename: &str,
+    mode: &DownloadMode,
+    expected_size: Option<u64>,
+) -> Result<Option<u64>> {
+    let path = path.as_ref();
+    let size = match tokio::fs::metadata(path).await {
+        Ok(d) if d.is_file() => d.len(),
+        Ok(_) => return Ok(None),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(Error::Io(e)),
+    };
+    if let Some(expected_size) = expected_size {
+        if expected_size != size {
+            return Ok(None);
+        }
+    }
+    match (mode, page_hash_prefix(filename)) {
+        (DownloadMode::Normal, Some(prefix)) => {
+            let digest = sha256_file(path).await?;
+            if digest.starts_with(&prefix.to_ascii_lowercase()) {
+                Ok(Some(size))
+            } else {
+                Ok(None)
+            }
+        }
+        _ if expected_size.is_some() => Ok(Some(size)),
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn page_hash_prefix_from_filename() {
+        assert_eq!(
+            page_hash_prefix("1-f7b3b1a6c7e5d3a0.png"),
+            Some("f7b3b1a6c7e5d3a0")
+        );
+        assert_eq!(page_hash_prefix("cover.png"), None);
+        assert_eq!(page_hash_prefix("1-not_a_hash.png"), None);
+    }
+
+    #[tokio::test]
+    async fn verify_page_checks_the_filename_hash() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("mangadex-api-manifest-{}", Uuid::new_v4()));
+        tokio::fs::create_dir_all(&dir).await?;
+        // SHA-256 of "page"
+        let filename = "1-3660315a9af3df255d8f19ab077e4797822b41488a0e2a04bc6af71213c23274.png";
+        let path = dir.join(filename);
+        tokio::fs::write(&path, b"page").await?;
+
+        assert_eq!(
+            verify_page(&path, filename, &DownloadMode::Normal, None).await?,
+            Some(4)
+        );
+        tokio::fs::write(&path, b"corrupted").await?;
+        assert_eq!(
+            verify_page(&path, filename, &DownloadMode::Normal, None).await?,
+            None
+        );
+        assert_eq!(
+            verify_page(&path, filename, &DownloadMode::DataSaver, Some(9)).await?,
+            Some(9)
+        );
+        assert_eq!(
+            verify_page(
+                dir.join("2-abcd.png"),
+                "2-abcd.png",
+                &DownloadMode::Normal,
+                None
+            )
+            .await?,
+            None
+        );
+
+        tokio::fs::remove_dir_all(&dir).await?;
+        Ok(())
+    }
+}
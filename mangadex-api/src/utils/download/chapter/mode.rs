@@ -1,9 +1,11 @@
 /// Chapter Download Mode
 /// Normal = "data"
 /// DataSaver = "data-saver"
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum DownloadMode {
+    #[serde(rename = "data")]
     Normal,
+    #[serde(rename = "data-saver")]
     DataSaver,
 }
 
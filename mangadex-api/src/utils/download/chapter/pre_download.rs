@@ -1,20 +1,39 @@
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
 use bytes::Bytes;
-use bytes::BytesMut;
 use mangadex_api_schema::v5::AtHomeServer;
 use mangadex_api_types::error::{Error, Result};
 use reqwest::{Client, Response};
-use tokio::pin;
+use tokio::io::AsyncWrite;
 use tokio::time::Instant;
-use tokio_stream::StreamExt;
 use url::Url;
 
+use crate::utils::download::progress::ProgressReporter;
+use crate::utils::download::sink::{write_body, AtomicFile};
+use crate::utils::download::throttle::BandwidthLimiter;
+
 use super::DownloadMode;
-use super::AtHomeReport;
+use super::{AtHomeReport, AtHomeReporter};
 
 use super::DownloadElement;
 
+/// The MangaDex origin, used as a last resort when no MangaDex@Home node can serve a page.
+pub const UPLOADS_ORIGIN: &str = crate::CDN_URL;
+
+/// Check if a failed page download is worth retrying against another MangaDex@Home node.
+///
+/// This is the case for network errors, `5xx` responses and `403` responses
+/// (the node URL token expired).
+pub fn is_retryable_error(error: &Error) -> bool {
+    match error {
+        Error::RequestError(e) => !e.is_builder() && !e.is_redirect(),
+        Error::ServerError(status, _) => *status == 403 || *status >= 500,
+        _ => false,
+    }
+}
+
 #[derive(Clone)]
 pub struct AtHomePreDownloadImageData {
     pub http_client: Arc<Client>,
@@ -22,25 +41,49 @@ pub struct AtHomePreDownloadImageData {
     pub quality: DownloadMode,
     pub at_home: Arc<AtHomeServer>,
     pub report: bool,
+    /// Background reporter the reports are queued to when `report` is set.
+    pub reporter: Option<AtHomeReporter>,
+    /// Receives the download events of the page.
+    pub progress: Option<ProgressReporter>,
+    /// Bandwidth limiters the page body waits for.
+    pub bandwidth_limits: Vec<BandwidthLimiter>,
 }
 
 impl AtHomePreDownloadImageData {
-    async fn report(&self, start: Instant, page_url: Url, bytes : usize, success : bool, cached : bool) {
-        if self.report {
+    /// Queue a report to the [`reporter`](Self::reporter), without waiting for it to be sent.
+    fn report(&self, start: Instant, page_url: Url, bytes: usize, success: bool, cached: bool) {
+        if let (true, Some(reporter)) = (self.report, &self.reporter) {
             let end = Instant::now();
-            let _ = AtHomeReport {
+            reporter.report(AtHomeReport {
                 url: page_url,
                 success,
                 cached,
                 bytes,
                 duration: end.duration_since(start).as_millis(),
-            }
-            .send(self.http_client.clone())
-            .await;
+            });
+        }
+    }
+    /// Send a failed event if `res` is an error.
+    pub(crate) fn failed<T>(&self, res: Result<T>) -> Result<T> {
+        if let (Err(e), Some(progress)) = (&res, &self.progress) {
+            progress.failed(e);
+        }
+        res
+    }
+    pub(crate) fn retried(&self, attempt: usize, error: &Error) {
+        if let Some(progress) = &self.progress {
+            progress.retried(attempt, error);
         }
     }
     pub fn build_page_url(&self) -> Result<Url> {
-        match self.at_home.base_url.join(&format!(
+        self.build_page_url_from(&self.at_home.base_url)
+    }
+    /// Build the page URL on the [`UPLOADS_ORIGIN`].
+    pub fn build_origin_page_url(&self) -> Result<Url> {
+        self.build_page_url_from(&Url::parse(UPLOADS_ORIGIN)?)
+    }
+    fn build_page_url_from(&self, base_url: &Url) -> Result<Url> {
+        match base_url.join(&format!(
             "/{quality_mode}/{chapter_hash}/{page_filename}",
             quality_mode = Into::<String>::into(self.quality.clone()),
             chapter_hash = self.at_home.chapter.hash,
@@ -53,22 +96,143 @@ impl AtHomePreDownloadImageData {
     pub async fn download(&self) -> Result<DownloadElement> {
         self.download_with_checker(|_, _| false).await
     }
-    pub async fn download_with_checker<C>(&self, mut should_skip: C) -> Result<DownloadElement>
+    /// Download the page from the MangaDex@Home node.
+    ///
+    /// This makes a single attempt, see [`is_retryable_error`] to know if it can be retried.
+    pub async fn download_with_checker<C>(&self, should_skip: C) -> Result<DownloadElement>
+    where
+        C: FnMut(&Self, &Response) -> bool,
+    {
+        let res = match self.build_page_url() {
+            Ok(page_url) => self.download_from_with_checker(page_url, should_skip).await,
+            Err(e) => Err(e),
+        };
+        self.failed(res)
+    }
+    /// Download the page from the [`UPLOADS_ORIGIN`].
+    pub async fn download_from_origin_with_checker<C>(
+        &self,
+        should_skip: C,
+    ) -> Result<DownloadElement>
     where
         C: FnMut(&Self, &Response) -> bool,
     {
-        let page_url = self.build_page_url()?;
+        let res = match self.build_origin_page_url() {
+            Ok(page_url) => self.download_from_with_checker(page_url, should_skip).await,
+            Err(e) => Err(e),
+        };
+        self.failed(res)
+    }
+    /// Stream the page to `writer`.
+    ///
+    /// This makes a single attempt against the MangaDex@Home node and returns the number of bytes written.
+    pub async fn download_into<W>(&self, writer: &mut W) -> Result<usize>
+    where
+        W: AsyncWrite + Unpin + ?Sized,
+    {
+        let mut written = 0;
+        let res = match self.build_page_url() {
+            Ok(page_url) => {
+                self.download_from_into(page_url, |_, _| false, writer, &mut written)
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        self.failed(res).map(|_| written)
+    }
+    /// Stream the page to `dir`, under its page filename.
+    ///
+    /// The page is written to a temporary file first and renamed once complete.
+    pub async fn download_to_dir<P: AsRef<Path>>(&self, dir: P) -> Result<PathBuf> {
+        let res = match self.build_page_url() {
+            Ok(page_url) => self.download_from_to_dir(page_url, dir, |_, _| false).await,
+            Err(e) => Err(e),
+        };
+        match self.failed(res)? {
+            Some(path) => Ok(path),
+            None => unreachable!("the page is never skipped"),
+        }
+    }
+    pub(crate) async fn download_from_with_checker<C>(
+        &self,
+        page_url: Url,
+        should_skip: C,
+    ) -> Result<DownloadElement>
+    where
+        C: FnMut(&Self, &Response) -> bool,
+    {
+        let mut bytes: Vec<u8> = Vec::new();
+        let mut written = 0;
+        if self
+            .download_from_into(page_url, should_skip, &mut bytes, &mut written)
+            .await?
+        {
+            Ok((self.filename.clone(), Some(Bytes::from(bytes))))
+        } else {
+            Ok((self.filename.clone(), None))
+        }
+    }
+    /// Stream the page to `dir`, returns `None` if the page was skipped.
+    pub(crate) async fn download_from_to_dir<C, P>(
+        &self,
+        page_url: Url,
+        dir: P,
+        should_skip: C,
+    ) -> Result<Option<PathBuf>>
+    where
+        C: FnMut(&Self, &Response) -> bool,
+        P: AsRef<Path>,
+    {
+        let mut file = AtomicFile::create(dir.as_ref().join(&self.filename)).await?;
+        let mut written = 0;
+        match self
+            .download_from_into(page_url, should_skip, file.file(), &mut written)
+            .await
+        {
+            Ok(true) => Ok(Some(file.persist().await?)),
+            Ok(false) => {
+                file.discard().await;
+                Ok(None)
+            }
+            Err(e) => {
+                file.discard().await;
+                Err(e)
+            }
+        }
+    }
+    /// Stream the page at `page_url` to `writer`, returns `false` if the page was skipped.
+    ///
+    /// `written` holds the number of bytes written so far, even if an error occurs.
+    pub(crate) async fn download_from_into<C, W>(
+        &self,
+        page_url: Url,
+        mut should_skip: C,
+        writer: &mut W,
+        written: &mut usize,
+    ) -> Result<bool>
+    where
+        C: FnMut(&Self, &Response) -> bool,
+        W: AsyncWrite + Unpin + ?Sized,
+    {
         let page_url_clone = page_url.clone();
         let start = tokio::time::Instant::now();
         let res: Response = match self.http_client.get(page_url).send().await {
             Ok(d) => d,
             Err(e) => {
-                self.report(start, page_url_clone, 0, false, false).await;
+                self.report(start, page_url_clone, 0, false, false);
                 return Err(Error::RequestError(e));
             }
         };
+        let status = res.status();
+        if !status.is_success() {
+            self.report(start, page_url_clone.clone(), 0, false, false);
+            return Err(Error::ServerError(
+                status.as_u16(),
+                page_url_clone.to_string(),
+            ));
+        }
         if should_skip(self, &res) {
-            return Ok((self.filename.clone(), None));
+            return Ok(false);
         }
         let is_cache: bool = match res.headers().get("X-Cache") {
             None => false,
@@ -77,22 +241,71 @@ impl AtHomePreDownloadImageData {
                 Err(_) => false,
             },
         };
-
-        let mut bytes: BytesMut = BytesMut::new();
-        let byte_stream = res.bytes_stream();
-        pin!(byte_stream);
-        while let Some(chunk) = byte_stream.next().await {
-            match chunk {
-                Ok(chunk_bytes) => {
-                    bytes.extend(chunk_bytes);
-                },
-                Err(chunk_error) => {
-                    self.report(start, page_url_clone, bytes.len(), false, is_cache).await;
-                    return Err(Error::RequestError(chunk_error));
-                }
+        let mut throttled = Duration::ZERO;
+        let res = write_body(
+            res,
+            writer,
+            written,
+            self.progress.as_ref(),
+            &self.bandwidth_limits,
+            &mut throttled,
+        )
+        .await;
+        // The time spent waiting for the bandwidth limiters isn't the node's
+        let start = start + throttled;
+        match res {
+            Ok(()) => {
+                self.report(start, page_url_clone, *written, true, is_cache);
+                Ok(true)
+            }
+            // Failing to write locally isn't the node's fault
+            Err(Error::Io(e)) => Err(Error::Io(e)),
+            Err(e) => {
+                self.report(start, page_url_clone, *written, false, is_cache);
+                Err(e)
             }
         }
-        self.report(start, page_url_clone, bytes.len(), true, is_cache).await;
-        Ok((self.filename.clone(), Some(Bytes::from(bytes))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retryable_server_errors() {
+        assert!(is_retryable_error(&Error::ServerError(403, String::new())));
+        assert!(is_retryable_error(&Error::ServerError(502, String::new())));
+        assert!(!is_retryable_error(&Error::ServerError(404, String::new())));
+        assert!(!is_retryable_error(&Error::PingError));
+    }
+
+    #[test]
+    fn origin_page_url_keeps_the_chapter_path() -> Result<()> {
+        let at_home: AtHomeServer = serde_json::from_value(serde_json::json!({
+            "result": "ok",
+            "baseUrl": "https://abc.xyz.mangadex.network:44300/token",
+            "chapter": {
+                "hash": "chapter-hash",
+                "data": ["1-page.png"],
+                "dataSaver": ["1-page.jpg"]
+            }
+        }))
+        .unwrap();
+        let page = AtHomePreDownloadImageData {
+            http_client: Arc::new(Client::new()),
+            filename: "1-page.jpg".to_string(),
+            quality: DownloadMode::DataSaver,
+            at_home: Arc::new(at_home),
+            report: false,
+            reporter: None,
+            progress: None,
+            bandwidth_limits: Vec::new(),
+        };
+        assert_eq!(
+            page.build_origin_page_url()?.as_str(),
+            "https://uploads.mangadex.org/data-saver/chapter-hash/1-page.jpg"
+        );
+        Ok(())
     }
 }
@@ -1,15 +1,29 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
+use futures::StreamExt;
 use mangadex_api_types::error::{Error, Result};
 use reqwest::{Client, Response};
 use serde::Serialize;
+use tokio::sync::{mpsc, Notify};
 use url::Url;
 
+/// The MangaDex@Home report endpoint.
+pub const AT_HOME_REPORT_URL: &str = "https://api.mangadex.network/report";
+
+/// Check if `url` is served by a MangaDex@Home node, the only servers that should be reported.
+pub fn is_at_home_node(url: &Url) -> bool {
+    match url.host_str() {
+        Some(host) => host.ends_with(".mangadex.network") && host != "api.mangadex.network",
+        None => false,
+    }
+}
 
 /// Send a report to `https://api.mangadex.network/report`.
 ///
 /// More details at : https://api.mangadex.org/docs/retrieving-chapter/#the-mangadexhome-report-endpoint
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Clone, Debug)]
 pub struct AtHomeReport {
     pub url: Url,
     pub success: bool,
@@ -20,20 +34,257 @@ pub struct AtHomeReport {
 
 impl AtHomeReport {
     pub async fn send(&self, client: Arc<Client>) -> Result<Response> {
-        if !self.url.as_str().contains("mangadex.org") {
-            match client
-                .post("https://api.mangadex.network/report")
-                .json(self)
-                .send()
-                .await
-            {
+        if is_at_home_node(&self.url) {
+            match client.post(AT_HOME_REPORT_URL).json(self).send().await {
                 Ok(d) => Result::Ok(d),
                 Err(e) => Result::Err(Error::RequestError(e)),
             }
         } else {
-            Result::Err(Error::UnexpectedError(anyhow::Error::msg(
-                "the mangadex.org pattern found!",
-            )))
+            Result::Err(Error::UnexpectedError(anyhow::Error::msg(format!(
+                "{} is not a MangaDex@Home node",
+                self.url
+            ))))
+        }
+    }
+}
+
+/// Settings of an [`AtHomeReporter`].
+#[derive(Debug, Clone, Copy)]
+pub struct ReporterConfig {
+    /// Maximum number of reports waiting to be sent, new reports are dropped once it is full.
+    pub queue_size: usize,
+    /// Maximum number of reports sent at the same time.
+    pub concurrency: usize,
+    /// Number of times a report is retried after a network error or a `5xx` response.
+    pub retries: usize,
+    /// Delay before the first retry, doubled on each retry.
+    pub retry_delay: Duration,
+}
+
+impl Default for ReporterConfig {
+    fn default() -> Self {
+        Self {
+            queue_size: 256,
+            concurrency: 4,
+            retries: 2,
+            retry_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Counters of an [`AtHomeReporter`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReporterMetrics {
+    /// Reports accepted in the queue.
+    pub queued: u64,
+    /// Reports accepted by the report endpoint.
+    pub sent: u64,
+    /// Reports that couldn't be sent, even after retrying.
+    pub failed: u64,
+    /// Retried report attempts.
+    pub retried: u64,
+    /// Reports dropped because the queue was full or the reporter was shut down.
+    pub dropped: u64,
+    /// Reports of servers that aren't MangaDex@Home nodes, which are never sent.
+    pub skipped: u64,
+}
+
+#[derive(Default)]
+struct Shared {
+    queued: AtomicU64,
+    sent: AtomicU64,
+    failed: AtomicU64,
+    retried: AtomicU64,
+    dropped: AtomicU64,
+    skipped: AtomicU64,
+    /// Reports queued or being sent.
+    pending: AtomicU64,
+    idle: Notify,
+    shut_down: AtomicBool,
+}
+
+impl Shared {
+    fn done(&self) {
+        if self.pending.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.idle.notify_waiters();
+        }
+    }
+}
+
+/// Sends the MangaDex@Home reports from a background task, so the downloads never wait for them.
+///
+/// Reports are queued in a bounded queue and sent concurrently, with retries.
+/// Clones share the same queue, the task stops once every clone is dropped and the queue is empty.
+#[derive(Clone)]
+pub struct AtHomeReporter {
+    sender: mpsc::Sender<AtHomeReport>,
+    shared: Arc<Shared>,
+}
+
+impl std::fmt::Debug for AtHomeReporter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AtHomeReporter")
+            .field("metrics", &self.metrics())
+            .finish_non_exhaustive()
+    }
+}
+
+impl AtHomeReporter {
+    /// Spawn a reporter with the default [`ReporterConfig`].
+    ///
+    /// This must be called from a Tokio runtime.
+    pub fn new(client: Arc<Client>) -> Self {
+        Self::with_config(client, ReporterConfig::default())
+    }
+    /// Spawn a reporter.
+    ///
+    /// This must be called from a Tokio runtime.
+    pub fn with_config(client: Arc<Client>, config: ReporterConfig) -> Self {
+        let (sender, mut receiver) = mpsc::channel::<AtHomeReport>(config.queue_size.max(1));
+        let shared = Arc::new(Shared::default());
+        let worker = shared.clone();
+        tokio::spawn(async move {
+            futures::stream::poll_fn(move |cx| receiver.poll_recv(cx))
+                .for_each_concurrent(config.concurrency.max(1), |report| {
+                    let client = client.clone();
+                    let shared = worker.clone();
+                    async move {
+                        if shared.shut_down.load(Ordering::Acquire) {
+                            shared.dropped.fetch_add(1, Ordering::Relaxed);
+                        } else {
+                            send_with_retry(&report, client, &config, &shared).await;
+                        }
+                        shared.done();
+                    }
+                })
+                .await;
+        });
+        Self { sender, shared }
+    }
+    /// Queue a report without waiting, returns `false` if it was dropped or skipped.
+    pub fn report(&self, report: AtHomeReport) -> bool {
+        if !is_at_home_node(&report.url) {
+            self.shared.skipped.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        if self.shared.shut_down.load(Ordering::Acquire) {
+            self.shared.dropped.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        self.shared.pending.fetch_add(1, Ordering::AcqRel);
+        match self.sender.try_send(report) {
+            Ok(()) => {
+                self.shared.queued.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+            Err(_) => {
+                self.shared.dropped.fetch_add(1, Ordering::Relaxed);
+                self.shared.done();
+                false
+            }
+        }
+    }
+    pub fn metrics(&self) -> ReporterMetrics {
+        let shared = &self.shared;
+        ReporterMetrics {
+            queued: shared.queued.load(Ordering::Relaxed),
+            sent: shared.sent.load(Ordering::Relaxed),
+            failed: shared.failed.load(Ordering::Relaxed),
+            retried: shared.retried.load(Ordering::Relaxed),
+            dropped: shared.dropped.load(Ordering::Relaxed),
+            skipped: shared.skipped.load(Ordering::Relaxed),
+        }
+    }
+    /// Wait until every queued report is sent or dropped.
+    pub async fn flush(&self) {
+        loop {
+            let idle = self.shared.idle.notified();
+            if self.shared.pending.load(Ordering::Acquire) == 0 {
+                return;
+            }
+            idle.await;
+        }
+    }
+    /// Stop sending reports, the queued ones and the new ones are dropped.
+    ///
+    /// Reports being sent are not interrupted.
+    pub fn shutdown(&self) {
+        self.shared.shut_down.store(true, Ordering::Release);
+    }
+}
+
+async fn send_with_retry(
+    report: &AtHomeReport,
+    client: Arc<Client>,
+    config: &ReporterConfig,
+    shared: &Shared,
+) {
+    let mut delay = config.retry_delay;
+    for attempt in 0..=config.retries {
+        if attempt != 0 {
+            shared.retried.fetch_add(1, Ordering::Relaxed);
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+        match report.send(client.clone()).await {
+            Ok(res) if res.status().is_success() => {
+                shared.sent.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+            // Only server errors are worth retrying
+            Ok(res) if !res.status().is_server_error() => break,
+            Ok(_) | Err(_) => {}
+        }
+    }
+    shared.failed.fetch_add(1, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(url: &str) -> AtHomeReport {
+        AtHomeReport {
+            url: Url::parse(url).unwrap(),
+            success: true,
+            cached: false,
+            bytes: 1024,
+            duration: 100,
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn only_at_home_nodes_are_reported() {
+        let node =
+            Url::parse("https://abc.xyz.mangadex.network:44300/token/data/hash/1.png").unwrap();
+        assert!(is_at_home_node(&node));
+        assert!(!is_at_home_node(
+            &Url::parse("https://uploads.mangadex.org/data/hash/1.png").unwrap()
+        ));
+        assert!(!is_at_home_node(
+            &Url::parse("https://mangadex.network.example.com/1.png").unwrap()
+        ));
+        assert!(!is_at_home_node(
+            &Url::parse("https://api.mangadex.network/report").unwrap()
+        ));
+    }
+
+    #[tokio::test]
+    async fn reporter_skips_other_servers_and_drops_after_shutdown() {
+        let reporter = AtHomeReporter::new(Arc::new(Client::new()));
+        assert!(!reporter.report(report("https://uploads.mangadex.org/data/hash/1.png")));
+        reporter.shutdown();
+        assert!(!reporter.report(report(
+            "https://abc.xyz.mangadex.network/token/data/hash/1.png"
+        )));
+        reporter.flush().await;
+        assert_eq!(
+            reporter.metrics(),
+            ReporterMetrics {
+                skipped: 1,
+                dropped: 1,
+                ..Default::default()
+            }
+        );
+    }
+}
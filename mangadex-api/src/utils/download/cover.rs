@@ -1,4 +1,7 @@
+mod bulk;
+
 use crate::{utils::get_reqwest_client, HttpClientRef, MangaDexClient, CDN_URL};
+use bytes::Bytes;
 use derive_builder::Builder;
 use mangadex_api_schema::{
     v5::{CoverAttributes, MangaAttributes, RelatedAttributes},
@@ -6,14 +9,22 @@ use mangadex_api_schema::{
 };
 use mangadex_api_types::{
     error::{Error, Result},
-    ReferenceExpansionResource, RelationshipType, ChapterSortOrder, OrderDirection,
+    CoverSortOrder, OrderDirection, ReferenceExpansionResource, RelationshipType,
 };
-use reqwest::Client;
+use reqwest::{Client, Response};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::AsyncWrite;
 use url::Url;
 use uuid::Uuid;
 
+use super::progress::{DownloadTarget, ProgressHandler, ProgressReporter};
+use super::sink::{write_body, AtomicFile};
+use super::throttle::BandwidthLimiter;
 use super::DownloadElement;
 
+pub use bulk::{cover_file_name, CoverBulkDownload, CoverBulkDownloadBuilder};
+
 #[derive(Clone)]
 pub enum CoverQuality {
     Default = 0,
@@ -29,15 +40,11 @@ impl Default for CoverQuality {
     }
 }
 
-/// Download a Mangadex Manga Cover Image vie :
-/// - The filename
-/// - The manga_id
-pub async fn download_cover(
-    client: &Client,
+fn cover_file_url(
     file_name: String,
     manga_id: Uuid,
     cover_quality: CoverQuality,
-) -> Result<DownloadElement> {
+) -> Result<(String, Url)> {
     let file_name = match cover_quality {
         CoverQuality::Default => {
             file_name
@@ -59,23 +66,202 @@ pub async fn download_cover(
                 Ok(d) => d,
                 Err(e) => return Err(Error::ParseError(e.to_string())),
             };
-    let res = match client.get(cover_url).send().await {
+    Ok((file_name, cover_url))
+}
+
+async fn send_cover_request(client: &Client, cover_url: Url) -> Result<Response> {
+    let res = match client.get(cover_url.clone()).send().await {
         Err(e) => return Err(Error::RequestError(e)),
         Ok(d) => d,
     };
-    let bytes = match res.bytes().await {
-        Err(e) => return Err(Error::RequestError(e)),
-        Ok(d) => d,
+    if !res.status().is_success() {
+        return Err(Error::ServerError(
+            res.status().as_u16(),
+            cover_url.to_string(),
+        ));
+    }
+    Ok(res)
+}
+
+/// How a cover body is streamed.
+#[derive(Clone, Copy, Default)]
+struct StreamOptions<'a> {
+    progress: Option<&'a ProgressHandler>,
+    bandwidth_limits: &'a [BandwidthLimiter],
+}
+
+/// Stream the cover at `cover_url` to `writer`, returns the number of bytes written.
+async fn write_cover<W>(
+    client: &Client,
+    manga_id: Uuid,
+    file_name: &str,
+    cover_url: Url,
+    writer: &mut W,
+    options: StreamOptions<'_>,
+) -> Result<usize>
+where
+    W: AsyncWrite + Unpin + ?Sized,
+{
+    let progress = options.progress.map(|handler| {
+        ProgressReporter::new(
+            handler.clone(),
+            DownloadTarget::Cover {
+                manga_id,
+                filename: file_name.to_string(),
+            },
+        )
+    });
+    let mut written = 0;
+    let mut throttled = Duration::ZERO;
+    let res = match send_cover_request(client, cover_url).await {
+        Ok(res) => {
+            write_body(
+                res,
+                writer,
+                &mut written,
+                progress.as_ref(),
+                options.bandwidth_limits,
+                &mut throttled,
+            )
+            .await
+        }
+        Err(e) => Err(e),
     };
-    Ok((file_name, Some(bytes)))
+    match res {
+        Ok(()) => Ok(written),
+        Err(e) => {
+            if let Some(progress) = &progress {
+                progress.failed(&e);
+            }
+            Err(e)
+        }
+    }
 }
 
-pub async fn download_via_cover_api_object(
-    http_client: HttpClientRef,
-    cover: ApiObject<CoverAttributes>,
+async fn cover_bytes(
+    client: &Client,
+    file_name: String,
+    manga_id: Uuid,
     cover_quality: CoverQuality,
+    options: StreamOptions<'_>,
 ) -> Result<DownloadElement> {
-    let mangadex_api_client = MangaDexClient::new_with_http_client_ref(http_client);
+    let (file_name, cover_url) = cover_file_url(file_name, manga_id, cover_quality)?;
+    let mut bytes: Vec<u8> = Vec::new();
+    write_cover(client, manga_id, &file_name, cover_url, &mut bytes, options).await?;
+    Ok((file_name, Some(Bytes::from(bytes))))
+}
+
+async fn cover_into<W>(
+    client: &Client,
+    file_name: String,
+    manga_id: Uuid,
+    cover_quality: CoverQuality,
+    writer: &mut W,
+    options: StreamOptions<'_>,
+) -> Result<(String, usize)>
+where
+    W: AsyncWrite + Unpin + ?Sized,
+{
+    let (file_name, cover_url) = cover_file_url(file_name, manga_id, cover_quality)?;
+    let written = write_cover(client, manga_id, &file_name, cover_url, writer, options).await?;
+    Ok((file_name, written))
+}
+
+async fn cover_to_dir<P: AsRef<Path>>(
+    client: &Client,
+    file_name: String,
+    manga_id: Uuid,
+    cover_quality: CoverQuality,
+    dir: P,
+    options: StreamOptions<'_>,
+) -> Result<PathBuf> {
+    let (file_name, cover_url) = cover_file_url(file_name, manga_id, cover_quality)?;
+    tokio::fs::create_dir_all(dir.as_ref()).await?;
+    let mut file = AtomicFile::create(dir.as_ref().join(&file_name)).await?;
+    match write_cover(
+        client,
+        manga_id,
+        &file_name,
+        cover_url,
+        file.file(),
+        options,
+    )
+    .await
+    {
+        Ok(_) => file.persist().await,
+        Err(e) => {
+            file.discard().await;
+            Err(e)
+        }
+    }
+}
+
+/// Download a Mangadex Manga Cover Image vie :
+/// - The filename
+/// - The manga_id
+pub async fn download_cover(
+    client: &Client,
+    file_name: String,
+    manga_id: Uuid,
+    cover_quality: CoverQuality,
+) -> Result<DownloadElement> {
+    cover_bytes(
+        client,
+        file_name,
+        manga_id,
+        cover_quality,
+        StreamOptions::default(),
+    )
+    .await
+}
+
+/// Stream a Mangadex Manga Cover Image to `writer`.
+///
+/// Returns the cover filename with the number of bytes written.
+pub async fn download_cover_into<W>(
+    client: &Client,
+    file_name: String,
+    manga_id: Uuid,
+    cover_quality: CoverQuality,
+    writer: &mut W,
+) -> Result<(String, usize)>
+where
+    W: AsyncWrite + Unpin + ?Sized,
+{
+    cover_into(
+        client,
+        file_name,
+        manga_id,
+        cover_quality,
+        writer,
+        StreamOptions::default(),
+    )
+    .await
+}
+
+/// Stream a Mangadex Manga Cover Image to `dir`, under its cover filename.
+///
+/// The cover is written to a temporary file first and renamed once complete.
+pub async fn download_cover_to_dir<P: AsRef<Path>>(
+    client: &Client,
+    file_name: String,
+    manga_id: Uuid,
+    cover_quality: CoverQuality,
+    dir: P,
+) -> Result<PathBuf> {
+    cover_to_dir(
+        client,
+        file_name,
+        manga_id,
+        cover_quality,
+        dir,
+        StreamOptions::default(),
+    )
+    .await
+}
+
+/// Get the manga id and the file name of a cover.
+fn cover_file_via_cover_api_object(cover: ApiObject<CoverAttributes>) -> Result<(Uuid, String)> {
     let file_name = cover.attributes.file_name;
     // Check if the manga id available in the relationship
     let manga_id = match cover
@@ -91,16 +277,25 @@ pub async fn download_via_cover_api_object(
             ))))
         }
     };
+    Ok((manga_id, file_name))
+}
+
+pub async fn download_via_cover_api_object(
+    http_client: HttpClientRef,
+    cover: ApiObject<CoverAttributes>,
+    cover_quality: CoverQuality,
+) -> Result<DownloadElement> {
+    let mangadex_api_client = MangaDexClient::new_with_http_client_ref(http_client);
+    let (manga_id, file_name) = cover_file_via_cover_api_object(cover)?;
     let client = get_reqwest_client(&mangadex_api_client).await;
     download_cover(&client, file_name, manga_id, cover_quality).await
 }
 
-pub async fn download_via_cover_id(
+async fn fetch_cover(
     http_client: HttpClientRef,
     cover_id: Uuid,
-    cover_quality: CoverQuality,
-) -> Result<DownloadElement> {
-    let mangadex_api_client = MangaDexClient::new_with_http_client_ref(http_client.clone());
+) -> Result<ApiObject<CoverAttributes>> {
+    let mangadex_api_client = MangaDexClient::new_with_http_client_ref(http_client);
     let cover = match mangadex_api_client
         .cover()
         .view()
@@ -112,14 +307,23 @@ pub async fn download_via_cover_id(
     }
     .send()
     .await?;
-    download_via_cover_api_object(http_client, cover.data, cover_quality).await
+    Ok(cover.data)
 }
 
-pub async fn download_via_manga_api_object(
+pub async fn download_via_cover_id(
     http_client: HttpClientRef,
-    manga: ApiObject<MangaAttributes>,
+    cover_id: Uuid,
     cover_quality: CoverQuality,
 ) -> Result<DownloadElement> {
+    let cover = fetch_cover(http_client.clone(), cover_id).await?;
+    download_via_cover_api_object(http_client, cover, cover_quality).await
+}
+
+/// Get the manga id and the file name of the manga cover.
+async fn cover_file_via_manga_api_object(
+    http_client: HttpClientRef,
+    manga: ApiObject<MangaAttributes>,
+) -> Result<(Uuid, String)> {
     let mangadex_api_client = MangaDexClient::new_with_http_client_ref(http_client.clone());
     let file_name: String = 
     // Search if there is a cover relationship object in the MangaObject
@@ -174,7 +378,7 @@ pub async fn download_via_manga_api_object(
         }
         // Getting the file name via the list of the manga cover ordered by volume `desc` otherwise
         None => {
-            match mangadex_api_client.cover().list().add_manga_id(&manga.id).order(ChapterSortOrder::Volume(OrderDirection::Descending)).build(){
+            match mangadex_api_client.cover().list().add_manga_id(&manga.id).order(CoverSortOrder::Volume(OrderDirection::Descending)).build(){
                 Ok(d) => match d.send().await?.data.first() {
                     None => return Err(Error::UnexpectedError(anyhow::Error::msg("can't find the first cover of this manga"))),
                     Some(cover) => cover.attributes.file_name.clone()
@@ -183,25 +387,85 @@ pub async fn download_via_manga_api_object(
             }
         }
     };
-    let client : Client = get_reqwest_client(&mangadex_api_client).await;
-    download_cover(&client, file_name, manga.id, cover_quality).await
+    Ok((manga.id, file_name))
 }
 
-pub async fn download_via_manga_id(
+pub async fn download_via_manga_api_object(
     http_client: HttpClientRef,
-    manga_id: Uuid,
+    manga: ApiObject<MangaAttributes>,
     cover_quality: CoverQuality,
 ) -> Result<DownloadElement> {
     let mangadex_api_client = MangaDexClient::new_with_http_client_ref(http_client.clone());
-    let manga : ApiObject<MangaAttributes> = match mangadex_api_client.manga().get().manga_id(manga_id).includes(vec![ReferenceExpansionResource::CoverArt]).build() {
-        Ok(res) => {
-            res.send().await?.data
-        },
+    let (manga_id, file_name) = cover_file_via_manga_api_object(http_client, manga).await?;
+    let client : Client = get_reqwest_client(&mangadex_api_client).await;
+    download_cover(&client, file_name, manga_id, cover_quality).await
+}
+
+async fn fetch_manga(
+    http_client: HttpClientRef,
+    manga_id: Uuid,
+) -> Result<ApiObject<MangaAttributes>> {
+    let mangadex_api_client = MangaDexClient::new_with_http_client_ref(http_client);
+    let manga: ApiObject<MangaAttributes> = match mangadex_api_client
+        .manga()
+        .get()
+        .manga_id(manga_id)
+        .includes(vec![ReferenceExpansionResource::CoverArt])
+        .build()
+    {
+        Ok(res) => res.send().await?.data,
         Err(e) => return Err(Error::RequestBuilderError(e.to_string())),
     };
+    Ok(manga)
+}
+
+pub async fn download_via_manga_id(
+    http_client: HttpClientRef,
+    manga_id: Uuid,
+    cover_quality: CoverQuality,
+) -> Result<DownloadElement> {
+    let manga = fetch_manga(http_client.clone(), manga_id).await?;
     download_via_manga_api_object(http_client, manga, cover_quality).await
 }
 
+/// What to download a cover from.
+#[derive(Clone)]
+pub enum CoverSource {
+    CoverId(Uuid),
+    Cover(Box<ApiObject<CoverAttributes>>),
+    /// The manga cover, the latest volume cover is used if the manga has no cover relationship.
+    MangaId(Uuid),
+    /// The manga cover, the latest volume cover is used if the manga has no cover relationship.
+    Manga(Box<ApiObject<MangaAttributes>>),
+}
+
+impl From<ApiObject<CoverAttributes>> for CoverSource {
+    fn from(value: ApiObject<CoverAttributes>) -> Self {
+        Self::Cover(Box::new(value))
+    }
+}
+
+impl From<ApiObject<MangaAttributes>> for CoverSource {
+    fn from(value: ApiObject<MangaAttributes>) -> Self {
+        Self::Manga(Box::new(value))
+    }
+}
+
+/// Get the manga id and the file name of a cover.
+async fn cover_file(http_client: HttpClientRef, source: CoverSource) -> Result<(Uuid, String)> {
+    match source {
+        CoverSource::CoverId(cover_id) => {
+            cover_file_via_cover_api_object(fetch_cover(http_client, cover_id).await?)
+        }
+        CoverSource::Cover(cover) => cover_file_via_cover_api_object(*cover),
+        CoverSource::MangaId(manga_id) => {
+            let manga = fetch_manga(http_client.clone(), manga_id).await?;
+            cover_file_via_manga_api_object(http_client, manga).await
+        }
+        CoverSource::Manga(manga) => cover_file_via_manga_api_object(http_client, *manga).await,
+    }
+}
+
 #[derive(Clone, Builder)]
 #[builder(setter(into, strip_option), pattern = "owned")]
 #[non_exhaustive]
@@ -210,21 +474,94 @@ pub struct CoverDownload{
     #[builder(pattern = "immutable")]
     http_client: HttpClientRef,
 
-    quality : CoverQuality
+    quality: CoverQuality,
+    /// Receives the byte-level download events of the covers.
+    #[builder(default)]
+    progress: Option<ProgressHandler>,
+    /// Bandwidth limiters shared by the covers, see [`BandwidthLimiter`].
+    #[builder(setter(each = "bandwidth_limit"), default)]
+    bandwidth_limits: Vec<BandwidthLimiter>,
 }
 
 impl CoverDownload{
+    fn stream_options(&self) -> StreamOptions<'_> {
+        StreamOptions {
+            progress: self.progress.as_ref(),
+            bandwidth_limits: &self.bandwidth_limits,
+        }
+    }
+    async fn download(&self, source: CoverSource) -> Result<DownloadElement> {
+        let (manga_id, file_name) = cover_file(self.http_client.clone(), source).await?;
+        let client = get_reqwest_client(&MangaDexClient::new_with_http_client_ref(
+            self.http_client.clone(),
+        ))
+        .await;
+        cover_bytes(
+            &client,
+            file_name,
+            manga_id,
+            self.quality.clone(),
+            self.stream_options(),
+        )
+        .await
+    }
     pub async fn via_cover_api_object(&self, cover: ApiObject<CoverAttributes>) -> Result<DownloadElement> {
-        download_via_cover_api_object(self.http_client.clone(), cover, self.quality.clone()).await
+        self.download(CoverSource::Cover(Box::new(cover))).await
     }
     pub async fn via_cover_id(&self, cover_id: Uuid) -> Result<DownloadElement> {
-        download_via_cover_id(self.http_client.clone(), cover_id, self.quality.clone()).await
+        self.download(CoverSource::CoverId(cover_id)).await
     }
     pub async fn via_manga_api_object(&self, manga: ApiObject<MangaAttributes>) -> Result<DownloadElement> {
-        download_via_manga_api_object(self.http_client.clone(), manga, self.quality.clone()).await
+        self.download(CoverSource::Manga(Box::new(manga))).await
     }
     pub async fn via_manga_id(&self, manga_id: Uuid) -> Result<DownloadElement> {
-        download_via_manga_id(self.http_client.clone(), manga_id, self.quality.clone()).await
+        self.download(CoverSource::MangaId(manga_id)).await
+    }
+    /// Stream the cover to `dir`, under its cover filename.
+    ///
+    /// The cover is written to a temporary file first and renamed once complete.
+    pub async fn download_to_dir<S, P>(&self, source: S, dir: P) -> Result<PathBuf>
+    where
+        S: Into<CoverSource>,
+        P: AsRef<Path>,
+    {
+        let (manga_id, file_name) = cover_file(self.http_client.clone(), source.into()).await?;
+        let client = get_reqwest_client(&MangaDexClient::new_with_http_client_ref(
+            self.http_client.clone(),
+        ))
+        .await;
+        cover_to_dir(
+            &client,
+            file_name,
+            manga_id,
+            self.quality.clone(),
+            dir,
+            self.stream_options(),
+        )
+        .await
+    }
+    /// Stream the cover to `writer`.
+    ///
+    /// Returns the cover filename with the number of bytes written.
+    pub async fn download_into<S, W>(&self, source: S, writer: &mut W) -> Result<(String, usize)>
+    where
+        S: Into<CoverSource>,
+        W: AsyncWrite + Unpin + ?Sized,
+    {
+        let (manga_id, file_name) = cover_file(self.http_client.clone(), source.into()).await?;
+        let client = get_reqwest_client(&MangaDexClient::new_with_http_client_ref(
+            self.http_client.clone(),
+        ))
+        .await;
+        cover_into(
+            &client,
+            file_name,
+            manga_id,
+            self.quality.clone(),
+            writer,
+            self.stream_options(),
+        )
+        .await
     }
 }
 
@@ -235,6 +572,22 @@ mod tests{
     use crate::MangaDexClient;
     use std::{io::Write, fs::File};
 
+    #[test]
+    fn cover_file_url_uses_the_quality_suffix() -> Result<()> {
+        let manga_id = Uuid::new_v4();
+        let (file_name, url) = super::cover_file_url(
+            "cover.png".to_string(),
+            manga_id,
+            super::CoverQuality::Size256,
+        )?;
+        assert_eq!(file_name, "cover.png.256.jpg");
+        assert_eq!(
+            url.as_str(),
+            format!("https://uploads.mangadex.org/covers/{manga_id}/cover.png.256.jpg")
+        );
+        Ok(())
+    }
+
     /// Download the volume 2 cover of [Lycoris Recoil](https://mangadex.org/title/9c21fbcd-e22e-4e6d-8258-7d580df9fc45/lycoris-recoil)
     #[tokio::test]
     pub async fn via_cover_id() -> Result<()>{
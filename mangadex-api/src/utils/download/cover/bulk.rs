@@ -0,0 +1,237 @@
+//! Download every cover of one or more manga.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use mangadex_api::utils::download::cover::CoverQuality;
+//! use mangadex_api::MangaDexClient;
+//! use mangadex_api_types::Language;
+//! use uuid::Uuid;
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let client = MangaDexClient::default();
+//!
+//! let paths = client
+//!     .download()
+//!     .covers()
+//!     .add_manga_id(Uuid::new_v4())
+//!     .add_locale(Language::Japanese)
+//!     .volumes(1.0..=5.0)
+//!     .quality(CoverQuality::Size512)
+//!     .build()?
+//!     .download_to_dir("./covers")
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use derive_builder::Builder;
+use futures::StreamExt;
+use mangadex_api_schema::{v5::CoverAttributes, ApiObject};
+use mangadex_api_types::{
+    error::{Error, Result},
+    CoverSortOrder, Language, OrderDirection, VolumeNumber,
+};
+use tokio::pin;
+use uuid::Uuid;
+
+use crate::utils::download::manga::NumberRange;
+use crate::utils::download::progress::ProgressHandler;
+use crate::utils::download::throttle::BandwidthLimiter;
+use crate::utils::library::sanitize_file_name;
+use crate::utils::{get_reqwest_client, request_builder_error};
+use crate::{HttpClientRef, MangaDexClient};
+
+use super::{
+    cover_file_url, cover_file_via_cover_api_object, cover_to_dir, CoverQuality, StreamOptions,
+};
+
+/// Maximum number of covers the cover list returns per request.
+const LIST_LIMIT: u32 = 100;
+
+#[derive(Clone, Builder)]
+#[builder(setter(into, strip_option), pattern = "owned")]
+#[non_exhaustive]
+pub struct CoverBulkDownload {
+    #[doc(hidden)]
+    #[builder(pattern = "immutable")]
+    http_client: HttpClientRef,
+    #[builder(setter(each = "add_manga_id"))]
+    manga_ids: Vec<Uuid>,
+    /// Only download the covers of these volumes.
+    ///
+    /// Covers without a volume are only kept if the range is unbounded.
+    #[builder(default)]
+    volumes: NumberRange,
+    /// Only download the covers in these locales, every locale if empty.
+    #[builder(setter(each = "add_locale"), default)]
+    locales: Vec<Language>,
+    #[builder(default)]
+    quality: CoverQuality,
+    /// Maximum number of covers downloaded at the same time.
+    ///
+    /// Defaults to `1`.
+    #[builder(default)]
+    concurrency: Option<usize>,
+    /// Receives the byte-level download events of the covers.
+    #[builder(default)]
+    progress: Option<ProgressHandler>,
+    /// Bandwidth limiters shared by the covers, see [`BandwidthLimiter`].
+    #[builder(setter(each = "bandwidth_limit"), default)]
+    bandwidth_limits: Vec<BandwidthLimiter>,
+}
+
+/// Name a cover file by its volume and locale, like `Vol. 2 (ja).jpg`.
+///
+/// `extension` is the extension of the downloaded file.
+/// The volume and the locale tag go through [`sanitize_file_name`], they are free text on MangaDex.
+pub fn cover_file_name(cover: &CoverAttributes, extension: &str) -> String {
+    format!("{}.{extension}", cover_file_stem(cover))
+}
+
+fn cover_file_stem(cover: &CoverAttributes) -> String {
+    let locale = match &cover.locale {
+        Some(tag) => sanitize_file_name(tag.as_str()),
+        None => Language::Unknown.code2().to_string(),
+    };
+    match &cover.volume {
+        Some(volume) => format!("Vol. {} ({locale})", sanitize_file_name(volume)),
+        None => format!("Cover ({locale})"),
+    }
+}
+
+impl CoverBulkDownload {
+    /// List the covers of the manga, filtered by locale and volume.
+    ///
+    /// The covers are ordered by volume.
+    pub async fn covers(&self) -> Result<Vec<ApiObject<CoverAttributes>>> {
+        let client = MangaDexClient::new_with_http_client_ref(self.http_client.clone());
+        let mut covers: Vec<ApiObject<CoverAttributes>> = Vec::new();
+        let mut offset = 0;
+        loop {
+            let res = client
+                .cover()
+                .list()
+                .manga_ids(self.manga_ids.clone())
+                .locales(self.locales.clone())
+                .order(CoverSortOrder::Volume(OrderDirection::Ascending))
+                .limit(LIST_LIMIT)
+                .offset(offset)
+                .build()
+                .map_err(request_builder_error)?
+                .send()
+                .await?;
+            offset += res.data.len() as u32;
+            let is_last = res.data.is_empty() || offset >= res.total;
+            covers.extend(res.data.into_iter().filter(|cover| {
+                let volume = VolumeNumber::from_option(cover.attributes.volume.as_deref());
+                self.volumes.contains(volume.as_f64())
+            }));
+            if is_last {
+                break;
+            }
+        }
+        Ok(covers)
+    }
+    /// Download the covers to `dir`, named by volume and locale with [`cover_file_name`].
+    ///
+    /// With several manga, each manga gets a sub-directory named after its id.
+    /// Every cover is attempted, the first error is returned once they are all done.
+    /// Returns the cover paths in the [`covers`](Self::covers) order.
+    pub async fn download_to_dir<P: AsRef<Path>>(&self, dir: P) -> Result<Vec<PathBuf>> {
+        let dir = dir.as_ref();
+        let covers = self.covers().await?;
+        let client = get_reqwest_client(&MangaDexClient::new_with_http_client_ref(
+            self.http_client.clone(),
+        ))
+        .await;
+        let mut targets: Vec<(Uuid, String, PathBuf, String)> = Vec::new();
+        let mut names: HashSet<PathBuf> = HashSet::new();
+        for cover in covers {
+            let cover_id = cover.id;
+            let attributes = cover.attributes.clone();
+            let (manga_id, file_name) = cover_file_via_cover_api_object(cover)?;
+            let (downloaded_name, _) =
+                cover_file_url(file_name.clone(), manga_id, self.quality.clone())?;
+            let extension = downloaded_name.rsplit('.').next().unwrap_or("jpg");
+            let manga_dir = if self.manga_ids.len() > 1 {
+                dir.join(manga_id.to_string())
+            } else {
+                dir.to_path_buf()
+            };
+            let mut name = cover_file_name(&attributes, extension);
+            // Some volumes have several covers in the same locale
+            if !names.insert(manga_dir.join(&name)) {
+                name = format!("{} {cover_id}.{extension}", cover_file_stem(&attributes));
+                names.insert(manga_dir.join(&name));
+            }
+            targets.push((manga_id, file_name, manga_dir, name));
+        }
+        let options = StreamOptions {
+            progress: self.progress.as_ref(),
+            bandwidth_limits: &self.bandwidth_limits,
+        };
+        let results = futures::stream::iter(targets)
+            .map(|(manga_id, file_name, manga_dir, name)| {
+                let client = &client;
+                async move {
+                    let path = cover_to_dir(
+                        client,
+                        file_name,
+                        manga_id,
+                        self.quality.clone(),
+                        &manga_dir,
+                        options,
+                    )
+                    .await?;
+                    let renamed = manga_dir.join(name);
+                    tokio::fs::rename(&path, &renamed).await?;
+                    Ok::<PathBuf, Error>(renamed)
+                }
+            })
+            .buffered(self.concurrency.unwrap_or(1).max(1));
+        pin!(results);
+        let mut paths: Vec<PathBuf> = Vec::new();
+        let mut error: Option<Error> = None;
+        while let Some(result) = results.next().await {
+            match result {
+                Ok(path) => paths.push(path),
+                Err(e) => {
+                    error.get_or_insert(e);
+                }
+            }
+        }
+        match error {
+            Some(e) => Err(e),
+            None => Ok(paths),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cover_files_are_named_by_volume_and_locale() {
+        let cover: CoverAttributes = serde_json::from_value(serde_json::json!({
+            "description": "",
+            "locale": "ja",
+            "volume": "2",
+            "fileName": "f1e2d3.png",
+            "createdAt": "2021-01-01T00:00:00+00:00",
+            "updatedAt": null,
+            "version": 1
+        }))
+        .unwrap();
+        assert_eq!(cover_file_name(&cover, "png"), "Vol. 2 (ja).png");
+        let mut cover = cover;
+        cover.volume = Some("1/2".to_string());
+        assert_eq!(cover_file_name(&cover, "png"), "Vol. 1_2 (ja).png");
+        cover.volume = None;
+        assert_eq!(cover_file_name(&cover, "jpg"), "Cover (ja).jpg");
+    }
+}
@@ -0,0 +1,428 @@
+//! Download every chapter of a manga, picking one translation per chapter number.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use mangadex_api::MangaDexClient;
+//! use mangadex_api_types::Language;
+//! use tokio::pin;
+//! use tokio_stream::StreamExt;
+//! use uuid::Uuid;
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let client = MangaDexClient::default();
+//!
+//! let download = client
+//!     .download()
+//!     .manga(Uuid::new_v4())
+//!     .add_language(Language::English)
+//!     .add_language(Language::French)
+//!     .chapters(1.0..=10.0)
+//!     .build()?;
+//! let progress = download.download_stream("./manga").await?;
+//! pin!(progress);
+//! while let Some(progress) = progress.next().await {
+//!     println!("{} - {} : {:?}", progress.index, progress.len, progress.result.is_ok());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::ops::{RangeFrom, RangeInclusive, RangeToInclusive};
+use std::path::{Path, PathBuf};
+
+use async_stream::stream;
+use derive_builder::Builder;
+use mangadex_api_schema::v5::ChapterObject;
+use mangadex_api_types::error::Result;
+use mangadex_api_types::{Language, MangaFeedSortOrder, OrderDirection, RelationshipType};
+use time::OffsetDateTime;
+use tokio_stream::Stream;
+use uuid::Uuid;
+
+use crate::utils::library::sanitize_file_name;
+use crate::utils::{request_builder_error, whole_manga_feed};
+use crate::{HttpClientRef, MangaDexClient};
+
+use super::chapter::{AtHomeReporter, ChapterManifest, DownloadMode};
+use super::progress::ProgressHandler;
+use super::throttle::BandwidthLimiter;
+
+/// An inclusive range of volume or chapter numbers, each bound being optional.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct NumberRange {
+    pub start: Option<f64>,
+    pub end: Option<f64>,
+}
+
+impl NumberRange {
+    pub fn new(start: Option<f64>, end: Option<f64>) -> Self {
+        Self { start, end }
+    }
+    pub fn is_unbounded(&self) -> bool {
+        self.start.is_none() && self.end.is_none()
+    }
+    /// Check if a volume or chapter number, as given by
+    /// [`ChapterNumber::as_f64`](mangadex_api_types::ChapterNumber::as_f64), is in the range.
+    ///
+    /// Missing or non-numeric numbers are only in unbounded ranges.
+    pub fn contains(&self, number: Option<f64>) -> bool {
+        if self.is_unbounded() {
+            return true;
+        }
+        let number = match number {
+            Some(d) => d,
+            None => return false,
+        };
+        !self.start.is_some_and(|start| number < start) && !self.end.is_some_and(|end| number > end)
+    }
+}
+
+impl From<RangeInclusive<f64>> for NumberRange {
+    fn from(value: RangeInclusive<f64>) -> Self {
+        Self::new(Some(*value.start()), Some(*value.end()))
+    }
+}
+
+impl From<RangeFrom<f64>> for NumberRange {
+    fn from(value: RangeFrom<f64>) -> Self {
+        Self::new(Some(value.start), None)
+    }
+}
+
+impl From<RangeToInclusive<f64>> for NumberRange {
+    fn from(value: RangeToInclusive<f64>) -> Self {
+        Self::new(None, Some(value.end))
+    }
+}
+
+/// Progress of a [`MangaDownload`], yielded once per chapter.
+pub struct MangaDownloadProgress {
+    pub chapter: ChapterObject,
+    /// 1-based index of the chapter.
+    pub index: usize,
+    /// Number of chapters to download.
+    pub len: usize,
+    pub result: Result<ChapterManifest>,
+}
+
+#[derive(Clone, Builder)]
+#[builder(setter(into, strip_option), pattern = "owned")]
+#[non_exhaustive]
+pub struct MangaDownload {
+    #[doc(hidden)]
+    #[builder(pattern = "immutable")]
+    http_client: HttpClientRef,
+    manga_id: Uuid,
+    /// Chapter languages, in preference order.
+    ///
+    /// Every language is accepted if empty.
+    #[builder(setter(each = "add_language"), default)]
+    languages: Vec<Language>,
+    /// Scanlation groups picked first when a chapter has several translations, in preference order.
+    #[builder(setter(each = "prefer_group"), default)]
+    preferred_groups: Vec<Uuid>,
+    /// Chapters of these scanlation groups are never downloaded.
+    #[builder(setter(each = "exclude_group"), default)]
+    excluded_groups: Vec<Uuid>,
+    #[builder(default)]
+    volumes: NumberRange,
+    #[builder(default)]
+    chapters: NumberRange,
+    #[builder(default)]
+    mode: Option<DownloadMode>,
+    /// Maximum number of pages of a chapter downloaded at the same time.
+    #[builder(default)]
+    concurrency: Option<usize>,
+    /// Enable reporting at `api.mangadex.network`.
+    #[builder(default)]
+    report: Option<bool>,
+    /// Background reporter shared by every chapter.
+    #[builder(default)]
+    reporter: Option<AtHomeReporter>,
+    /// Receives the byte-level download events of every page.
+    #[builder(default)]
+    progress: Option<ProgressHandler>,
+    /// Bandwidth limiters shared by every chapter.
+    #[builder(setter(each = "bandwidth_limit"), default)]
+    bandwidth_limits: Vec<BandwidthLimiter>,
+}
+
+impl MangaDownload {
+    /// Rank of a chapter, lower is better.
+    fn rank(&self, chapter: &ChapterObject) -> (usize, usize, i128) {
+        let language = self
+            .languages
+            .iter()
+            .position(|language| *language == chapter.attributes.translated_language.language())
+            .unwrap_or(self.languages.len());
+        let group = chapter
+            .relationships
+            .iter()
+            .filter(|relationship| relationship.type_ == RelationshipType::ScanlationGroup)
+            .filter_map(|relationship| {
+                self.preferred_groups
+                    .iter()
+                    .position(|group| *group == relationship.id)
+            })
+            .min()
+            .unwrap_or(self.preferred_groups.len());
+        // The earliest translation wins the ties
+        let published = chapter
+            .attributes
+            .publish_at
+            .as_ref()
+            .unix_timestamp_nanos();
+        (language, group, published)
+    }
+    fn is_wanted(&self, chapter: &ChapterObject, now: OffsetDateTime) -> bool {
+        let attributes = &chapter.attributes;
+        // External and not yet readable chapters can't be downloaded
+        attributes.is_readable_at(&now)
+            && attributes.pages > 0
+            && (self.languages.is_empty()
+                || self
+                    .languages
+                    .contains(&attributes.translated_language.language()))
+            && !chapter.relationships.iter().any(|relationship| {
+                relationship.type_ == RelationshipType::ScanlationGroup
+                    && self.excluded_groups.contains(&relationship.id)
+            })
+            && self.volumes.contains(attributes.volume_number().as_f64())
+            && self.chapters.contains(attributes.chapter_number().as_f64())
+    }
+    /// Keep the best translation of each chapter number, in reading order.
+    ///
+    /// Chapters are told apart by their volume too when `per_volume` is set,
+    /// for manga whose chapter numbers reset on each volume.
+    /// Chapters without a number, like oneshots, are all kept.
+    pub fn select(&self, chapters: Vec<ChapterObject>, per_volume: bool) -> Vec<ChapterObject> {
+        let now = OffsetDateTime::now_utc();
+        let mut best: HashMap<(Option<String>, String), ChapterObject> = HashMap::new();
+        for chapter in chapters {
+            if !self.is_wanted(&chapter, now) {
+                continue;
+            }
+            let number = match &chapter.attributes.chapter {
+                Some(d) => d.clone(),
+                None => chapter.id.to_string(),
+            };
+            let volume = if per_volume {
+                chapter.attributes.volume.clone()
+            } else {
+                None
+            };
+            match best.get(&(volume.clone(), number.clone())) {
+                Some(current) if self.rank(current) <= self.rank(&chapter) => {}
+                _ => {
+                    best.insert((volume, number), chapter);
+                }
+            }
+        }
+        let mut selected: Vec<ChapterObject> = best.into_values().collect();
+        selected.sort_by_cached_key(|chapter| {
+            (
+                chapter.attributes.volume_number(),
+                chapter.attributes.chapter_number(),
+            )
+        });
+        selected
+    }
+    /// Fetch the whole manga feed in the wanted languages.
+    pub async fn feed(&self) -> Result<Vec<ChapterObject>> {
+        let client = MangaDexClient::new_with_http_client_ref(self.http_client.clone());
+        let mut builder = client
+            .manga()
+            .feed()
+            .manga_id(self.manga_id)
+            .order(MangaFeedSortOrder::Chapter(OrderDirection::Ascending));
+        for language in &self.languages {
+            builder = builder.add_translated_language(*language);
+        }
+        for group in &self.excluded_groups {
+            builder = builder.excluded_group(*group);
+        }
+        whole_manga_feed(builder).await
+    }
+    /// Resolve the chapters to download, in reading order.
+    pub async fn resolve(&self) -> Result<Vec<ChapterObject>> {
+        let client = MangaDexClient::new_with_http_client_ref(self.http_client.clone());
+        let manga = client
+            .manga()
+            .get()
+            .manga_id(self.manga_id)
+            .build()
+            .map_err(request_builder_error)?
+            .send()
+            .await?;
+        let per_volume = manga.data.attributes.chapter_numbers_reset_on_new_volume;
+        Ok(self.select(self.feed().await?, per_volume))
+    }
+    /// Directory of a chapter, like `Vol. 1 Ch. 2`.
+    ///
+    /// The volume and chapter go through [`sanitize_file_name`], they are free text on MangaDex.
+    pub fn chapter_dir_name(chapter: &ChapterObject) -> String {
+        let attributes = &chapter.attributes;
+        match (&attributes.volume, &attributes.chapter) {
+            (Some(volume), Some(number)) => format!(
+                "Vol. {} Ch. {}",
+                sanitize_file_name(volume),
+                sanitize_file_name(number)
+            ),
+            (None, Some(number)) => format!("Ch. {}", sanitize_file_name(number)),
+            _ => format!("Oneshot {}", chapter.id),
+        }
+    }
+    async fn download_chapter(
+        &self,
+        chapter: &ChapterObject,
+        dir: &Path,
+    ) -> Result<ChapterManifest> {
+        let client = MangaDexClient::new_with_http_client_ref(self.http_client.clone());
+        let mut download = client
+            .download()
+            .chapter_object(chapter)?
+            .mode(self.mode.clone().unwrap_or_default())
+            .report(self.report.unwrap_or(false))
+            .bandwidth_limits(self.bandwidth_limits.clone());
+        if let Some(concurrency) = self.concurrency {
+            download = download.concurrency(concurrency);
+        }
+        if let Some(reporter) = self.reporter.clone() {
+            download = download.reporter(reporter);
+        }
+        if let Some(progress) = self.progress.clone() {
+            download = download.progress(progress);
+        }
+        download
+            .build()
+            .map_err(request_builder_error)?
+            .download_to_dir_resumable(dir)
+            .await
+    }
+    /// Download the chapters to `dir`, one sub-directory per chapter.
+    ///
+    /// Chapters are downloaded one after another with [`ChapterDownload::download_to_dir_resumable`](super::chapter::ChapterDownload::download_to_dir_resumable),
+    /// so an interrupted download can be resumed. The progress is yielded once per chapter.
+    pub async fn download_stream<P: AsRef<Path>>(
+        &self,
+        dir: P,
+    ) -> Result<impl Stream<Item = MangaDownloadProgress> + '_> {
+        let dir: PathBuf = dir.as_ref().to_path_buf();
+        let chapters = self.resolve().await?;
+        let len = chapters.len();
+        Ok(stream! {
+            for (index, chapter) in chapters.into_iter().enumerate() {
+                let result = self
+                    .download_chapter(&chapter, &dir.join(Self::chapter_dir_name(&chapter)))
+                    .await;
+                yield MangaDownloadProgress {
+                    chapter,
+                    index: index + 1,
+                    len,
+                    result,
+                };
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mangadex_api_types::ChapterNumber;
+    use serde_json::json;
+
+    use super::*;
+
+    fn chapter(
+        number: Option<&str>,
+        language: &str,
+        group: Uuid,
+        publish_at: &str,
+    ) -> ChapterObject {
+        serde_json::from_value(json!({
+            "id": Uuid::new_v4(),
+            "type": "chapter",
+            "attributes": {
+                "title": "",
+                "volume": "1",
+                "chapter": number,
+                "pages": 20,
+                "translatedLanguage": language,
+                "externalUrl": null,
+                "version": 1,
+                "createdAt": publish_at,
+                "updatedAt": null,
+                "publishAt": publish_at,
+                "readableAt": publish_at
+            },
+            "relationships": [{ "id": group, "type": "scanlation_group" }]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn number_range_bounds() {
+        let range: NumberRange = (2.0..=5.0).into();
+        let number = |raw: &str| ChapterNumber::new(raw).as_f64();
+        assert!(range.contains(number("2")));
+        assert!(range.contains(number("4.5")));
+        assert!(range.contains(number("5a")));
+        assert!(!range.contains(number("5.5")));
+        assert!(!range.contains(number("oneshot")));
+        assert!(!range.contains(None));
+        assert!(NumberRange::default().contains(None));
+        assert!(NumberRange::from(3.0..).contains(number("100")));
+    }
+
+    #[test]
+    fn select_one_translation_per_chapter() -> anyhow::Result<()> {
+        let preferred = Uuid::new_v4();
+        let excluded = Uuid::new_v4();
+        let other = Uuid::new_v4();
+        let download = MangaDexClient::default()
+            .download()
+            .manga(Uuid::new_v4())
+            .add_language(Language::English)
+            .add_language(Language::French)
+            .prefer_group(preferred)
+            .exclude_group(excluded)
+            .chapters(..=3.0)
+            .build()?;
+
+        let chapters = vec![
+            chapter(Some("1"), "fr", preferred, "2021-01-01T00:00:00+00:00"),
+            chapter(Some("1"), "en", other, "2021-01-02T00:00:00+00:00"),
+            chapter(Some("2"), "en", other, "2021-01-01T00:00:00+00:00"),
+            chapter(Some("2"), "en", preferred, "2021-01-03T00:00:00+00:00"),
+            chapter(Some("3"), "en", excluded, "2021-01-01T00:00:00+00:00"),
+            chapter(Some("3"), "de", other, "2021-01-01T00:00:00+00:00"),
+            chapter(Some("4"), "en", other, "2021-01-01T00:00:00+00:00"),
+            chapter(Some("10"), "en", other, "2021-01-01T00:00:00+00:00"),
+        ];
+        let expected = vec![chapters[1].id, chapters[3].id];
+
+        let selected: Vec<Uuid> = download
+            .select(chapters, false)
+            .into_iter()
+            .map(|chapter| chapter.id)
+            .collect();
+        assert_eq!(selected, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn chapter_dir_names_stay_in_the_output_directory() {
+        let mut chapter = chapter(
+            Some("1/2"),
+            "en",
+            Uuid::new_v4(),
+            "2021-01-01T00:00:00+00:00",
+        );
+        assert_eq!(MangaDownload::chapter_dir_name(&chapter), "Vol. 1 Ch. 1_2");
+        chapter.attributes.volume = None;
+        chapter.attributes.chapter = Some("1/../../x".to_string());
+        assert_eq!(MangaDownload::chapter_dir_name(&chapter), "Ch. 1_.._.._x");
+    }
+}
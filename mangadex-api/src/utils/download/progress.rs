@@ -0,0 +1,194 @@
+//! Byte-level progress of the page and cover downloads.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use mangadex_api::utils::download::chapter::DownloadMode;
+//! use mangadex_api::utils::download::progress::{DownloadEventKind, ProgressHandler};
+//! use mangadex_api::MangaDexClient;
+//! use uuid::Uuid;
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let client = MangaDexClient::default();
+//! let (progress, mut events) = ProgressHandler::channel();
+//! tokio::spawn(async move {
+//!     while let Some(event) = events.recv().await {
+//!         if let DownloadEventKind::Bytes { received, content_length } = event.kind {
+//!             println!("{:?} : {received} / {content_length:?}", event.target);
+//!         }
+//!     }
+//! });
+//! client
+//!     .download()
+//!     .chapter(Uuid::new_v4())
+//!     .mode(DownloadMode::Normal)
+//!     .progress(progress)
+//!     .build()?
+//!     .download_to_dir("./chapter")
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use mangadex_api_types::error::Error;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use uuid::Uuid;
+
+/// What a [`DownloadEvent`] is about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DownloadTarget {
+    Page {
+        chapter_id: Uuid,
+        /// 1-based index of the page, the same as in the chapter `download_stream`.
+        index: usize,
+        filename: String,
+    },
+    Cover {
+        manga_id: Uuid,
+        filename: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DownloadEventKind {
+    /// The server answered, with the `Content-Length` of the body when it is known.
+    Started { content_length: Option<u64> },
+    /// `received` bytes of the body were received so far.
+    Bytes {
+        received: u64,
+        content_length: Option<u64>,
+    },
+    /// The download is complete.
+    Finished { bytes: u64 },
+    /// The download failed with `error` and is retried, `attempt` being the 1-based retry number.
+    Retried { attempt: usize, error: String },
+    /// The download failed for good.
+    Failed { error: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DownloadEvent {
+    pub target: DownloadTarget,
+    pub kind: DownloadEventKind,
+}
+
+/// Receives the [`DownloadEvent`]s of a download.
+///
+/// The callback is called inline by the download, so it should return quickly.
+#[derive(Clone)]
+pub struct ProgressHandler(Arc<dyn Fn(DownloadEvent) + Send + Sync>);
+
+impl ProgressHandler {
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: Fn(DownloadEvent) + Send + Sync + 'static,
+    {
+        Self(Arc::new(callback))
+    }
+    /// Send the events to a channel.
+    ///
+    /// Events are dropped once the receiver is closed.
+    pub fn channel() -> (Self, UnboundedReceiver<DownloadEvent>) {
+        let (sender, receiver) = unbounded_channel();
+        (
+            Self::new(move |event| {
+                let _ = sender.send(event);
+            }),
+            receiver,
+        )
+    }
+    pub fn emit(&self, event: DownloadEvent) {
+        (self.0)(event)
+    }
+}
+
+impl Debug for ProgressHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProgressHandler").finish_non_exhaustive()
+    }
+}
+
+/// A [`ProgressHandler`] bound to a [`DownloadTarget`].
+#[derive(Debug, Clone)]
+pub struct ProgressReporter {
+    pub handler: ProgressHandler,
+    pub target: DownloadTarget,
+}
+
+impl ProgressReporter {
+    pub fn new(handler: ProgressHandler, target: DownloadTarget) -> Self {
+        Self { handler, target }
+    }
+    pub fn emit(&self, kind: DownloadEventKind) {
+        self.handler.emit(DownloadEvent {
+            target: self.target.clone(),
+            kind,
+        })
+    }
+    pub(crate) fn started(&self, content_length: Option<u64>) {
+        self.emit(DownloadEventKind::Started { content_length })
+    }
+    pub(crate) fn bytes(&self, received: usize, content_length: Option<u64>) {
+        self.emit(DownloadEventKind::Bytes {
+            received: received as u64,
+            content_length,
+        })
+    }
+    pub(crate) fn finished(&self, bytes: u64) {
+        self.emit(DownloadEventKind::Finished { bytes })
+    }
+    pub(crate) fn retried(&self, attempt: usize, error: &Error) {
+        self.emit(DownloadEventKind::Retried {
+            attempt,
+            error: error.to_string(),
+        })
+    }
+    pub(crate) fn failed(&self, error: &Error) {
+        self.emit(DownloadEventKind::Failed {
+            error: error.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn channel_receives_the_events_in_order() {
+        let (handler, mut events) = ProgressHandler::channel();
+        let target = DownloadTarget::Page {
+            chapter_id: Uuid::new_v4(),
+            index: 1,
+            filename: "1-page.png".to_string(),
+        };
+        let reporter = ProgressReporter::new(handler, target.clone());
+        reporter.started(Some(4));
+        reporter.bytes(4, Some(4));
+        reporter.finished(4);
+        drop(reporter);
+
+        let mut kinds = Vec::new();
+        while let Some(event) = events.recv().await {
+            assert_eq!(event.target, target);
+            kinds.push(event.kind);
+        }
+        assert_eq!(
+            kinds,
+            vec![
+                DownloadEventKind::Started {
+                    content_length: Some(4)
+                },
+                DownloadEventKind::Bytes {
+                    received: 4,
+                    content_length: Some(4)
+                },
+                DownloadEventKind::Finished { bytes: 4 },
+            ]
+        );
+    }
+}
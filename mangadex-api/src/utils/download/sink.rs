@@ -0,0 +1,135 @@
+//! Write downloaded files without keeping them in memory.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use mangadex_api_types::error::{Error, Result};
+use reqwest::Response;
+use tokio::fs::File;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::pin;
+use tokio_stream::StreamExt;
+use uuid::Uuid;
+
+use super::progress::ProgressReporter;
+use super::throttle::{acquire_all, BandwidthLimiter};
+
+/// A file written next to its destination and renamed over it once complete.
+///
+/// Readers never see a partially written file at `path`.
+pub(crate) struct AtomicFile {
+    file: File,
+    temp_path: PathBuf,
+    path: PathBuf,
+}
+
+impl AtomicFile {
+    pub(crate) async fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file_name = match path.file_name() {
+            Some(d) => d.to_string_lossy().into_owned(),
+            None => {
+                return Err(Error::UnexpectedError(anyhow::Error::msg(format!(
+                    "{} is not a file path",
+                    path.display()
+                ))))
+            }
+        };
+        let temp_path = path.with_file_name(format!(".{file_name}.{}.part", Uuid::new_v4()));
+        let file = File::create(&temp_path).await?;
+        Ok(Self {
+            file,
+            temp_path,
+            path,
+        })
+    }
+    pub(crate) fn file(&mut self) -> &mut File {
+        &mut self.file
+    }
+    /// Flush the temporary file and move it to its destination.
+    pub(crate) async fn persist(mut self) -> Result<PathBuf> {
+        if let Err(e) = self.sync().await {
+            self.discard().await;
+            return Err(e);
+        }
+        if let Err(e) = tokio::fs::rename(&self.temp_path, &self.path).await {
+            self.discard().await;
+            return Err(Error::Io(e));
+        }
+        Ok(self.path)
+    }
+    async fn sync(&mut self) -> Result<()> {
+        self.file.flush().await?;
+        self.file.sync_all().await?;
+        Ok(())
+    }
+    /// Remove the temporary file.
+    pub(crate) async fn discard(self) {
+        drop(self.file);
+        let _ = tokio::fs::remove_file(&self.temp_path).await;
+    }
+}
+
+/// Stream the body of `res` into `writer`.
+///
+/// `written` holds the number of bytes written so far, even if an error occurs.
+/// The started, bytes and finished events are sent to `progress`.
+/// Each chunk waits for every limiter of `limiters`, `throttled` holds the time spent waiting.
+pub(crate) async fn write_body<W>(
+    res: Response,
+    writer: &mut W,
+    written: &mut usize,
+    progress: Option<&ProgressReporter>,
+    limiters: &[BandwidthLimiter],
+    throttled: &mut Duration,
+) -> Result<()>
+where
+    W: AsyncWrite + Unpin + ?Sized,
+{
+    let content_length = res.content_length();
+    if let Some(progress) = progress {
+        progress.started(content_length);
+    }
+    let byte_stream = res.bytes_stream();
+    pin!(byte_stream);
+    while let Some(chunk) = byte_stream.next().await {
+        let chunk = chunk?;
+        *throttled += acquire_all(limiters, chunk.len()).await;
+        writer.write_all(&chunk).await?;
+        *written += chunk.len();
+        if let Some(progress) = progress {
+            progress.bytes(*written, content_length);
+        }
+    }
+    writer.flush().await?;
+    if let Some(progress) = progress {
+        progress.finished(*written as u64);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn atomic_file_only_appears_once_persisted() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("mangadex-api-sink-{}", Uuid::new_v4()));
+        tokio::fs::create_dir_all(&dir).await?;
+        let path = dir.join("1.png");
+
+        let mut file = AtomicFile::create(&path).await?;
+        file.file().write_all(b"page").await?;
+        assert!(!path.exists());
+        assert_eq!(file.persist().await?, path);
+        assert_eq!(tokio::fs::read(&path).await?, b"page");
+
+        let mut file = AtomicFile::create(dir.join("2.png")).await?;
+        file.file().write_all(b"partial").await?;
+        file.discard().await;
+        assert_eq!(std::fs::read_dir(&dir)?.count(), 1);
+
+        tokio::fs::remove_dir_all(&dir).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+//! Keep the downloads below a bandwidth limit.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use mangadex_api::utils::download::chapter::DownloadMode;
+//! use mangadex_api::utils::download::throttle::BandwidthLimiter;
+//! use mangadex_api::MangaDexClient;
+//! use uuid::Uuid;
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let client = MangaDexClient::default();
+//! // Every download built from `download` shares 1 MiB/s
+//! let download = client
+//!     .download()
+//!     .bandwidth_limit(BandwidthLimiter::new(1024 * 1024));
+//! download
+//!     .chapter(Uuid::new_v4())
+//!     .mode(DownloadMode::Normal)
+//!     // and this chapter is kept below 256 KiB/s
+//!     .bandwidth_limit(BandwidthLimiter::new(256 * 1024))
+//!     .build()?
+//!     .download_to_dir("./chapter")
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+#[derive(Debug)]
+struct Bucket {
+    /// Available bytes, negative when the received chunks exceeded the burst.
+    tokens: f64,
+    bytes_per_second: f64,
+    burst: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.bytes_per_second).min(self.burst);
+        self.last = now;
+    }
+    /// Take `bytes` from the bucket, returns how long to wait for the bucket to be back in credit.
+    fn take(&mut self, bytes: usize, now: Instant) -> Duration {
+        self.refill(now);
+        self.tokens -= bytes as f64;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.bytes_per_second)
+        }
+    }
+}
+
+/// A token-bucket bandwidth limiter.
+///
+/// Clones share the same bucket, so a limiter can cap several downloads at once.
+#[derive(Debug, Clone)]
+pub struct BandwidthLimiter {
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+impl BandwidthLimiter {
+    /// Allow `bytes_per_second`, with bursts of up to one second of traffic.
+    pub fn new(bytes_per_second: u64) -> Self {
+        Self::with_burst(bytes_per_second, bytes_per_second)
+    }
+    /// Allow `bytes_per_second`, with bursts of up to `burst` bytes.
+    pub fn with_burst(bytes_per_second: u64, burst: u64) -> Self {
+        let burst = burst.max(1) as f64;
+        Self {
+            bucket: Arc::new(Mutex::new(Bucket {
+                tokens: burst,
+                bytes_per_second: bytes_per_second.max(1) as f64,
+                burst,
+                last: Instant::now(),
+            })),
+        }
+    }
+    pub fn bytes_per_second(&self) -> u64 {
+        self.lock().bytes_per_second as u64
+    }
+    /// Change the limit of every download sharing this limiter.
+    pub fn set_bytes_per_second(&self, bytes_per_second: u64) {
+        let mut bucket = self.lock();
+        bucket.refill(Instant::now());
+        bucket.bytes_per_second = bytes_per_second.max(1) as f64;
+    }
+    fn lock(&self) -> std::sync::MutexGuard<'_, Bucket> {
+        // The bucket is always left consistent, so a poisoned lock is still usable
+        self.bucket.lock().unwrap_or_else(|e| e.into_inner())
+    }
+    /// Account for `bytes` received, waiting as long as needed to stay below the limit.
+    ///
+    /// Returns the time spent waiting.
+    pub async fn acquire(&self, bytes: usize) -> Duration {
+        let wait = self.lock().take(bytes, Instant::now());
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+        wait
+    }
+}
+
+/// Wait for every limiter in turn, returns the total time spent waiting.
+pub(crate) async fn acquire_all(limiters: &[BandwidthLimiter], bytes: usize) -> Duration {
+    let mut waited = Duration::ZERO;
+    for limiter in limiters {
+        waited += limiter.acquire(bytes).await;
+    }
+    waited
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket_waits_for_the_bytes_over_the_burst() {
+        let start = Instant::now();
+        let mut bucket = Bucket {
+            tokens: 1000.0,
+            bytes_per_second: 1000.0,
+            burst: 1000.0,
+            last: start,
+        };
+        assert_eq!(bucket.take(1000, start), Duration::ZERO);
+        assert_eq!(bucket.take(500, start), Duration::from_millis(500));
+        // The debt is paid off after the wait
+        let later = start + Duration::from_millis(500);
+        assert_eq!(bucket.take(0, later), Duration::ZERO);
+        // Idle time never refills more than the burst
+        let idle = later + Duration::from_secs(10);
+        assert_eq!(bucket.take(2000, idle), Duration::from_secs(1));
+    }
+}
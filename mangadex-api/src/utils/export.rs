@@ -0,0 +1,61 @@
+//! Export downloaded chapters to formats used by offline readers.
+
+pub mod cbz;
+pub mod epub;
+
+use mangadex_api_schema::v5::{ChapterAttributes, LocalizedString, MangaAttributes};
+use mangadex_api_schema::ApiObject;
+use mangadex_api_types::Language;
+
+use crate::HttpClientRef;
+
+use self::cbz::CbzExportBuilder;
+use self::epub::EpubExportBuilder;
+
+#[derive(Debug)]
+pub struct ExportBuilder {
+    http_client: HttpClientRef,
+}
+
+impl ExportBuilder {
+    #[doc(hidden)]
+    pub(crate) fn new(http_client: HttpClientRef) -> Self {
+        Self { http_client }
+    }
+    /// Export a chapter as CBZ, crediting the groups, authors and artists of the expanded relationships.
+    pub fn cbz(
+        &self,
+        chapter: ApiObject<ChapterAttributes>,
+        manga: ApiObject<MangaAttributes>,
+    ) -> CbzExportBuilder {
+        CbzExportBuilder::from_expanded(chapter, manga)
+    }
+    /// Export volumes or the whole manga as EPUB.
+    pub fn epub(&self, manga: ApiObject<MangaAttributes>) -> EpubExportBuilder {
+        EpubExportBuilder::default()
+            .http_client(self.http_client.clone())
+            .manga(manga)
+    }
+}
+
+/// Pick the English text of a [`LocalizedString`],
+/// the one in `fallback` otherwise or the best one, see [`LocalizedString::best`].
+pub(crate) fn localized_text(value: &LocalizedString, fallback: Language) -> Option<String> {
+    value.best(&[Language::English, fallback]).map(String::from)
+}
+
+/// Escape the XML special characters of a text node or attribute value.
+pub(crate) fn escape_xml(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,441 @@
+//! Export a chapter as a CBZ archive with a `ComicInfo.xml`.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use mangadex_api::MangaDexClient;
+//! use mangadex_api::utils::export::cbz::CbzExportBuilder;
+//! use mangadex_api_types::{ReferenceExpansionResource, RelationshipType};
+//! use uuid::Uuid;
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let client = MangaDexClient::default();
+//! let chapter_id = Uuid::new_v4();
+//!
+//! let chapter = client
+//!     .chapter()
+//!     .get()
+//!     .chapter_id(chapter_id)
+//!     .includes(vec![ReferenceExpansionResource::ScanlationGroup])
+//!     .build()?
+//!     .send()
+//!     .await?
+//!     .data;
+//! let manga_id = chapter
+//!     .relationships
+//!     .iter()
+//!     .find(|relationship| relationship.type_ == RelationshipType::Manga)
+//!     .map(|manga| manga.id)
+//!     .unwrap();
+//! let manga = client
+//!     .manga()
+//!     .get()
+//!     .manga_id(manga_id)
+//!     .includes(vec![
+//!         ReferenceExpansionResource::Author,
+//!         ReferenceExpansionResource::Artist,
+//!     ])
+//!     .build()?
+//!     .send()
+//!     .await?
+//!     .data;
+//!
+//! let download = client.download().chapter(chapter_id).report(true).build()?;
+//! let path = CbzExportBuilder::from_expanded(chapter, manga)
+//!     .build()?
+//!     .export(&download, "chapter.cbz")
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fs::File;
+use std::io::{Seek, Write};
+use std::path::{Path, PathBuf};
+
+use derive_builder::Builder;
+use mangadex_api_schema::v5::{
+    AuthorAttributes, ChapterAttributes, MangaAttributes, RelatedAttributes,
+    ScanlationGroupAttributes,
+};
+use mangadex_api_schema::ApiObject;
+use mangadex_api_types::error::{Error, Result};
+use mangadex_api_types::{Language, TagGroup};
+use uuid::Uuid;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::utils::download::chapter::ChapterDownload;
+use crate::utils::download::sink::AtomicFile;
+
+use super::{escape_xml, localized_text};
+
+/// `ComicInfo.xml` metadata, following the [Anansi Project schema](https://anansi-project.github.io/docs/comicinfo/intro).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ComicInfo {
+    pub title: Option<String>,
+    pub series: Option<String>,
+    pub number: Option<String>,
+    pub volume: Option<String>,
+    pub summary: Option<String>,
+    pub year: Option<i32>,
+    pub month: Option<u8>,
+    pub day: Option<u8>,
+    pub writers: Vec<String>,
+    pub pencillers: Vec<String>,
+    pub translators: Vec<String>,
+    pub genres: Vec<String>,
+    pub tags: Vec<String>,
+    pub web: Option<String>,
+    pub page_count: Option<usize>,
+    pub language_iso: Option<String>,
+    /// `YesAndRightToLeft` for manga read from right to left.
+    pub manga: Option<String>,
+}
+
+impl ComicInfo {
+    /// Map the chapter and manga attributes to the `ComicInfo.xml` fields.
+    pub fn new(
+        chapter_id: Uuid,
+        chapter: &ChapterAttributes,
+        manga: &MangaAttributes,
+        groups: &[ScanlationGroupAttributes],
+        authors: &[AuthorAttributes],
+        artists: &[AuthorAttributes],
+    ) -> Self {
+        let publish_at = chapter.publish_at.as_ref();
+        let tag_names = |genre: bool| -> Vec<String> {
+            manga
+                .tags
+                .iter()
+                .filter(|tag| (tag.attributes.group == TagGroup::Genre) == genre)
+                .filter_map(|tag| localized_text(&tag.attributes.name, Language::English))
+                .collect()
+        };
+        Self {
+            title: Some(chapter.title.clone()).filter(|title| !title.is_empty()),
+            series: localized_text(&manga.title, manga.original_language.language()),
+            number: chapter.chapter.clone(),
+            volume: chapter.volume.clone(),
+            summary: localized_text(&manga.description, manga.original_language.language()),
+            year: Some(publish_at.year()),
+            month: Some(publish_at.month().into()),
+            day: Some(publish_at.day()),
+            writers: authors.iter().map(|author| author.name.clone()).collect(),
+            pencillers: artists.iter().map(|artist| artist.name.clone()).collect(),
+            translators: groups.iter().map(|group| group.name.clone()).collect(),
+            genres: tag_names(true),
+            tags: tag_names(false),
+            web: Some(format!("https://mangadex.org/chapter/{chapter_id}")),
+            page_count: Some(chapter.pages as usize),
+            language_iso: Some(chapter.translated_language.to_string()),
+            manga: (manga.original_language.language() == Language::Japanese)
+                .then(|| "YesAndRightToLeft".to_string()),
+        }
+    }
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::from(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<ComicInfo xmlns:xsd=\"http://www.w3.org/2001/XMLSchema\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">\n",
+        );
+        let mut element = |name: &str, value: Option<String>| {
+            if let Some(value) = value.filter(|value| !value.is_empty()) {
+                xml.push_str(&format!("  <{name}>{}</{name}>\n", escape_xml(&value)));
+            }
+        };
+        let join = |values: &[String]| Some(values.join(", "));
+        element("Title", self.title.clone());
+        element("Series", self.series.clone());
+        element("Number", self.number.clone());
+        // `Volume` is an integer in the schema
+        element(
+            "Volume",
+            self.volume
+                .as_ref()
+                .filter(|volume| volume.parse::<i32>().is_ok())
+                .cloned(),
+        );
+        element("Summary", self.summary.clone());
+        element("Year", self.year.map(|year| year.to_string()));
+        element("Month", self.month.map(|month| month.to_string()));
+        element("Day", self.day.map(|day| day.to_string()));
+        element("Writer", join(&self.writers));
+        element("Penciller", join(&self.pencillers));
+        element("Translator", join(&self.translators));
+        element("Genre", join(&self.genres));
+        element("Tags", join(&self.tags));
+        element("Web", self.web.clone());
+        element("PageCount", self.page_count.map(|count| count.to_string()));
+        element("LanguageISO", self.language_iso.clone());
+        element("Manga", self.manga.clone());
+        xml.push_str("</ComicInfo>\n");
+        xml
+    }
+}
+
+/// Zero-padded name of a page in the archive, keeping the extension of the original filename.
+///
+/// Pages are padded to at least 3 digits so they sort correctly in every reader.
+pub fn page_file_name(index: usize, page_count: usize, original: &str) -> String {
+    let width = page_count.to_string().len().max(3);
+    match Path::new(original).extension() {
+        Some(extension) => format!(
+            "{:0width$}.{}",
+            index + 1,
+            extension.to_string_lossy(),
+            width = width
+        ),
+        None => format!("{:0width$}", index + 1, width = width),
+    }
+}
+
+/// Write a CBZ archive made of the `ComicInfo.xml` and the given pages, in page order.
+///
+/// Images are already compressed so they are stored as is.
+pub fn write_cbz<W, P>(writer: W, comic_info: &ComicInfo, pages: &[P]) -> Result<W>
+where
+    W: Write + Seek,
+    P: AsRef<Path>,
+{
+    let mut zip = ZipWriter::new(writer);
+    let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+    let to_error = |e: zip::result::ZipError| Error::UnexpectedError(anyhow::Error::new(e));
+    zip.start_file("ComicInfo.xml", options).map_err(to_error)?;
+    zip.write_all(comic_info.to_xml().as_bytes())?;
+    for (index, page) in pages.iter().enumerate() {
+        let page = page.as_ref();
+        let original = page
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        zip.start_file(page_file_name(index, pages.len(), &original), options)
+            .map_err(to_error)?;
+        std::io::copy(&mut File::open(page)?, &mut zip)?;
+    }
+    zip.finish().map_err(to_error)
+}
+
+/// Export a chapter as a CBZ archive.
+#[derive(Clone, Builder)]
+#[builder(setter(into, strip_option), pattern = "owned")]
+#[non_exhaustive]
+pub struct CbzExport {
+    chapter: ApiObject<ChapterAttributes>,
+    manga: ApiObject<MangaAttributes>,
+    /// Scanlation groups, credited as translators.
+    #[builder(default)]
+    groups: Vec<ScanlationGroupAttributes>,
+    /// Credited as writers.
+    #[builder(default)]
+    authors: Vec<AuthorAttributes>,
+    /// Credited as pencillers.
+    #[builder(default)]
+    artists: Vec<AuthorAttributes>,
+}
+
+impl CbzExportBuilder {
+    /// Take the groups from the expanded chapter relationships
+    /// and the authors and artists from the expanded manga relationships.
+    pub fn from_expanded(
+        chapter: ApiObject<ChapterAttributes>,
+        manga: ApiObject<MangaAttributes>,
+    ) -> Self {
+        let mut groups = Vec::new();
+        for relationship in &chapter.relationships {
+            if let Some(RelatedAttributes::ScanlationGroup(group)) = &relationship.attributes {
+                groups.push(group.clone());
+            }
+        }
+        let mut authors = Vec::new();
+        let mut artists = Vec::new();
+        for relationship in &manga.relationships {
+            match &relationship.attributes {
+                Some(RelatedAttributes::Author(author)) => authors.push(author.clone()),
+                Some(RelatedAttributes::Artist(artist)) => artists.push(artist.clone()),
+                _ => {}
+            }
+        }
+        Self::default()
+            .chapter(chapter)
+            .manga(manga)
+            .groups(groups)
+            .authors(authors)
+            .artists(artists)
+    }
+}
+
+impl CbzExport {
+    pub fn comic_info(&self) -> ComicInfo {
+        ComicInfo::new(
+            self.chapter.id,
+            &self.chapter.attributes,
+            &self.manga.attributes,
+            &self.groups,
+            &self.authors,
+            &self.artists,
+        )
+    }
+    /// Write a CBZ archive from pages already on disk, in page order.
+    pub fn write<W, P>(&self, writer: W, pages: &[P]) -> Result<W>
+    where
+        W: Write + Seek,
+        P: AsRef<Path>,
+    {
+        let mut comic_info = self.comic_info();
+        comic_info.page_count = Some(pages.len());
+        write_cbz(writer, &comic_info, pages)
+    }
+    /// Download the chapter and export it to `output`.
+    ///
+    /// The pages are downloaded with [`ChapterDownload::download_to_dir_resumable`]
+    /// to a `<output>.pages` directory, removed once the archive is written.
+    /// The archive is written to a temporary file first and renamed once complete.
+    pub async fn export<P: AsRef<Path>>(
+        &self,
+        download: &ChapterDownload,
+        output: P,
+    ) -> Result<PathBuf> {
+        let output = output.as_ref();
+        let mut pages_dir = output.as_os_str().to_owned();
+        pages_dir.push(".pages");
+        let pages_dir = PathBuf::from(pages_dir);
+        let manifest = download.download_to_dir_resumable(&pages_dir).await?;
+        let pages: Vec<PathBuf> = manifest
+            .pages
+            .iter()
+            .map(|page| pages_dir.join(page))
+            .collect();
+
+        let mut file = AtomicFile::create(output).await?;
+        let std_file = file.file().try_clone().await?.into_std().await;
+        let export = self.clone();
+        let written =
+            match tokio::task::spawn_blocking(move || export.write(std_file, &pages)).await {
+                Ok(d) => d,
+                Err(e) => Err(Error::UnexpectedError(anyhow::Error::new(e))),
+            };
+        if let Err(e) = written {
+            file.discard().await;
+            return Err(e);
+        }
+        let path = file.persist().await?;
+        tokio::fs::remove_dir_all(&pages_dir).await?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read};
+
+    use serde_json::json;
+
+    use super::*;
+
+    fn chapter() -> ChapterAttributes {
+        serde_json::from_value(json!({
+            "title": "Tom & Jerry",
+            "volume": "2",
+            "chapter": "13.5",
+            "pages": 2,
+            "translatedLanguage": "en",
+            "externalUrl": null,
+            "version": 1,
+            "createdAt": "2021-05-01T10:00:00+00:00",
+            "updatedAt": null,
+            "publishAt": "2021-06-07T10:00:00+00:00",
+            "readableAt": "2021-06-07T10:00:00+00:00"
+        }))
+        .unwrap()
+    }
+
+    fn manga() -> MangaAttributes {
+        serde_json::from_value(json!({
+            "title": { "ja-ro": "Kimi tte Watashi no Koto Suki Nandesho?", "en": "You Like Me, Don't You?" },
+            "altTitles": [],
+            "description": {},
+            "isLocked": false,
+            "links": null,
+            "originalLanguage": "ja",
+            "lastVolume": null,
+            "lastChapter": null,
+            "publicationDemographic": null,
+            "status": "ongoing",
+            "year": null,
+            "contentRating": "safe",
+            "chapterNumbersResetOnNewVolume": false,
+            "latestUploadedChapter": null,
+            "availableTranslatedLanguages": [],
+            "tags": [
+                {
+                    "id": Uuid::new_v4(),
+                    "type": "tag",
+                    "attributes": { "name": { "en": "Romance" }, "description": {}, "group": "genre", "version": 1 },
+                    "relationships": []
+                },
+                {
+                    "id": Uuid::new_v4(),
+                    "type": "tag",
+                    "attributes": { "name": { "en": "School Life" }, "description": {}, "group": "theme", "version": 1 },
+                    "relationships": []
+                }
+            ],
+            "state": "published",
+            "createdAt": "2021-05-01T10:00:00+00:00",
+            "updatedAt": null,
+            "version": 1
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn comic_info_maps_chapter_and_manga_attributes() {
+        let chapter_id = Uuid::new_v4();
+        let comic_info = ComicInfo::new(chapter_id, &chapter(), &manga(), &[], &[], &[]);
+        let xml = comic_info.to_xml();
+
+        assert!(xml.contains("<Title>Tom &amp; Jerry</Title>"));
+        assert!(xml.contains("<Series>You Like Me, Don&apos;t You?</Series>"));
+        assert!(xml.contains("<Number>13.5</Number>"));
+        assert!(xml.contains("<Volume>2</Volume>"));
+        assert!(xml.contains("<Year>2021</Year>"));
+        assert!(xml.contains("<Month>6</Month>"));
+        assert!(xml.contains("<Genre>Romance</Genre>"));
+        assert!(xml.contains("<Tags>School Life</Tags>"));
+        assert!(xml.contains("<LanguageISO>en</LanguageISO>"));
+        assert!(xml.contains("<Manga>YesAndRightToLeft</Manga>"));
+        assert!(xml.contains(&format!(
+            "<Web>https://mangadex.org/chapter/{chapter_id}</Web>"
+        )));
+        assert!(!xml.contains("<Summary>"));
+    }
+
+    #[test]
+    fn page_file_names_are_zero_padded() {
+        assert_eq!(page_file_name(0, 20, "1-abcdef.png"), "001.png");
+        assert_eq!(page_file_name(1233, 1500, "x-abcdef.jpg"), "1234.jpg");
+    }
+
+    #[test]
+    fn write_cbz_stores_comic_info_and_pages() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("mangadex-api-cbz-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir)?;
+        let pages = vec![dir.join("1-aaaa.png"), dir.join("2-bbbb.jpg")];
+        std::fs::write(&pages[0], b"first")?;
+        std::fs::write(&pages[1], b"second")?;
+
+        let cursor = write_cbz(Cursor::new(Vec::new()), &ComicInfo::default(), &pages)?;
+        let mut archive = zip::ZipArchive::new(cursor).unwrap();
+        let names: Vec<&str> = archive.file_names().collect();
+        assert_eq!(names.len(), 3);
+        let mut second = String::new();
+        archive
+            .by_name("002.jpg")
+            .unwrap()
+            .read_to_string(&mut second)?;
+        assert_eq!(second, "second");
+        assert!(archive.by_name("ComicInfo.xml").is_ok());
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,906 @@
+//! Export volumes or whole manga as fixed-layout EPUB 3 books.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use mangadex_api::MangaDexClient;
+//! use mangadex_api_types::Language;
+//! use uuid::Uuid;
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let client = MangaDexClient::default();
+//!
+//! let manga = client
+//!     .manga()
+//!     .get()
+//!     .manga_id(Uuid::new_v4())
+//!     .build()?
+//!     .send()
+//!     .await?
+//!     .data;
+//!
+//! let books = client
+//!     .export()
+//!     .epub(manga)
+//!     .translated_language(Language::English)
+//!     .build()?
+//!     .export_volumes("./books")
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::io::{Seek, Write};
+use std::path::{Path, PathBuf};
+
+use derive_builder::Builder;
+use mangadex_api_schema::v5::manga_aggregate::VolumeAggregate;
+use mangadex_api_schema::v5::{
+    AuthorAttributes, ChapterAttributes, CoverAttributes, MangaAttributes,
+};
+use mangadex_api_schema::ApiObject;
+use mangadex_api_types::error::{Error, Result};
+use mangadex_api_types::{Language, LanguageTag};
+use time::OffsetDateTime;
+use uuid::Uuid;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::image::ImageInfo;
+use crate::utils::download::chapter::DownloadMode;
+use crate::utils::download::cover::CoverSource;
+use crate::utils::download::sink::AtomicFile;
+use crate::utils::library::sanitize_file_name;
+use crate::utils::{request_builder_error, CONTENT_RATINGS};
+use crate::{HttpClientRef, MangaDexClient};
+
+use super::{escape_xml, localized_text};
+
+/// OPF metadata of a book.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EpubMetadata {
+    /// Unique identifier, like `urn:uuid:<manga id>`.
+    pub identifier: String,
+    pub title: String,
+    pub language: String,
+    pub creators: Vec<String>,
+    pub contributors: Vec<String>,
+    pub description: Option<String>,
+    pub subjects: Vec<String>,
+    pub year: Option<u16>,
+    /// Read from right to left.
+    pub rtl: bool,
+    /// Last modification, in `CCYY-MM-DDThh:mm:ssZ` format.
+    pub modified: String,
+}
+
+impl EpubMetadata {
+    pub fn new(
+        manga_id: Uuid,
+        manga: &MangaAttributes,
+        authors: &[AuthorAttributes],
+        artists: &[AuthorAttributes],
+        language: Language,
+    ) -> Self {
+        let now = OffsetDateTime::now_utc();
+        Self {
+            identifier: format!("urn:uuid:{manga_id}"),
+            title: localized_text(&manga.title, manga.original_language.language())
+                .unwrap_or_default(),
+            language: language.code2().to_string(),
+            creators: authors.iter().map(|author| author.name.clone()).collect(),
+            contributors: artists.iter().map(|artist| artist.name.clone()).collect(),
+            description: localized_text(&manga.description, manga.original_language.language()),
+            subjects: manga
+                .tags
+                .iter()
+                .filter_map(|tag| localized_text(&tag.attributes.name, Language::English))
+                .collect(),
+            year: manga.year,
+            rtl: manga.original_language.language() == Language::Japanese,
+            modified: format!(
+                "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+                now.year(),
+                u8::from(now.month()),
+                now.day(),
+                now.hour(),
+                now.minute(),
+                now.second()
+            ),
+        }
+    }
+}
+
+/// A chapter of a book, listed in the navigation document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EpubChapter {
+    pub title: String,
+    /// Page images, in page order.
+    pub pages: Vec<PathBuf>,
+}
+
+struct EpubPage {
+    id: String,
+    image: String,
+    mime_type: &'static str,
+    width: u32,
+    height: u32,
+}
+
+fn page_xhtml(title: &str, page: &EpubPage) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+<head>
+  <title>{title}</title>
+  <meta name="viewport" content="width={width}, height={height}"/>
+  <style>html, body {{ margin: 0; padding: 0; }} img {{ display: block; width: 100%; height: 100%; }}</style>
+</head>
+<body>
+  <img src="../images/{image}" alt=""/>
+</body>
+</html>
+"#,
+        title = escape_xml(title),
+        width = page.width,
+        height = page.height,
+        image = page.image,
+    )
+}
+
+fn nav_xhtml(title: &str, entries: &[(String, String)]) -> String {
+    let items: String = entries
+        .iter()
+        .map(|(label, href)| {
+            format!(
+                "      <li><a href=\"{}\">{}</a></li>\n",
+                escape_xml(href),
+                escape_xml(label)
+            )
+        })
+        .collect();
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+<head>
+  <title>{title}</title>
+</head>
+<body>
+  <nav epub:type="toc" id="toc">
+    <h1>{title}</h1>
+    <ol>
+{items}    </ol>
+  </nav>
+</body>
+</html>
+"#,
+        title = escape_xml(title),
+        items = items,
+    )
+}
+
+fn content_opf(metadata: &EpubMetadata, cover: Option<&EpubPage>, pages: &[EpubPage]) -> String {
+    let mut opf = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="book-id" prefix="rendition: http://www.idpf.org/vocab/rendition/#">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+"#,
+    );
+    let mut element = |name: &str, value: &str| {
+        opf.push_str(&format!("    <{name}>{}</{name}>\n", escape_xml(value)));
+    };
+    element("dc:title", &metadata.title);
+    element("dc:language", &metadata.language);
+    for creator in &metadata.creators {
+        element("dc:creator", creator);
+    }
+    for contributor in &metadata.contributors {
+        element("dc:contributor", contributor);
+    }
+    if let Some(description) = &metadata.description {
+        element("dc:description", description);
+    }
+    for subject in &metadata.subjects {
+        element("dc:subject", subject);
+    }
+    if let Some(year) = metadata.year {
+        element("dc:date", &year.to_string());
+    }
+    opf.push_str(&format!(
+        "    <dc:identifier id=\"book-id\">{}</dc:identifier>\n",
+        escape_xml(&metadata.identifier)
+    ));
+    opf.push_str(&format!(
+        "    <meta property=\"dcterms:modified\">{}</meta>\n",
+        escape_xml(&metadata.modified)
+    ));
+    opf.push_str("    <meta property=\"rendition:layout\">pre-paginated</meta>\n");
+    opf.push_str("    <meta property=\"rendition:spread\">none</meta>\n");
+    if cover.is_some() {
+        opf.push_str("    <meta name=\"cover\" content=\"img-cover\"/>\n");
+    }
+    opf.push_str("  </metadata>\n  <manifest>\n");
+    opf.push_str(
+        "    <item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n",
+    );
+    let mut spine = String::new();
+    for (page, is_cover) in cover
+        .into_iter()
+        .map(|page| (page, true))
+        .chain(pages.iter().map(|page| (page, false)))
+    {
+        opf.push_str(&format!(
+            "    <item id=\"img-{id}\" href=\"images/{image}\" media-type=\"{mime_type}\"{properties}/>\n",
+            id = page.id,
+            image = page.image,
+            mime_type = page.mime_type,
+            properties = if is_cover { " properties=\"cover-image\"" } else { "" },
+        ));
+        opf.push_str(&format!(
+            "    <item id=\"page-{id}\" href=\"pages/{id}.xhtml\" media-type=\"application/xhtml+xml\"/>\n",
+            id = page.id,
+        ));
+        spine.push_str(&format!("    <itemref idref=\"page-{}\"/>\n", page.id));
+    }
+    opf.push_str(&format!(
+        "  </manifest>\n  <spine page-progression-direction=\"{}\">\n{}  </spine>\n</package>\n",
+        if metadata.rtl { "rtl" } else { "ltr" },
+        spine
+    ));
+    opf
+}
+
+const CONTAINER_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#;
+
+/// Write a fixed-layout EPUB 3 book, with one image per page.
+pub fn write_epub<W>(
+    writer: W,
+    metadata: &EpubMetadata,
+    cover: Option<&Path>,
+    chapters: &[EpubChapter],
+) -> Result<W>
+where
+    W: Write + Seek,
+{
+    let to_error = |e: zip::result::ZipError| Error::UnexpectedError(anyhow::Error::new(e));
+    let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+    let mut zip = ZipWriter::new(writer);
+    // The mimetype must be the first, uncompressed, entry
+    zip.start_file("mimetype", stored).map_err(to_error)?;
+    zip.write_all(b"application/epub+zip")?;
+    zip.start_file("META-INF/container.xml", stored)
+        .map_err(to_error)?;
+    zip.write_all(CONTAINER_XML.as_bytes())?;
+
+    let mut write_image = |id: String, path: &Path| -> Result<EpubPage> {
+        let bytes = std::fs::read(path)?;
+        let info = match ImageInfo::from_bytes(&bytes) {
+            Some(d) => d,
+            None => {
+                return Err(Error::UnexpectedError(anyhow::Error::msg(format!(
+                    "{} is not a supported image",
+                    path.display()
+                ))))
+            }
+        };
+        let page = EpubPage {
+            image: format!("{id}.{}", info.format.extension()),
+            id,
+            mime_type: info.format.mime_type(),
+            width: info.width,
+            height: info.height,
+        };
+        zip.start_file(format!("OEBPS/images/{}", page.image), stored)
+            .map_err(to_error)?;
+        zip.write_all(&bytes)?;
+        zip.start_file(format!("OEBPS/pages/{}.xhtml", page.id), stored)
+            .map_err(to_error)?;
+        zip.write_all(page_xhtml(&metadata.title, &page).as_bytes())?;
+        Ok(page)
+    };
+
+    let cover = match cover {
+        Some(path) => Some(write_image("cover".to_string(), path)?),
+        None => None,
+    };
+    let mut pages = Vec::new();
+    let mut nav_entries = Vec::new();
+    for (chapter_index, chapter) in chapters.iter().enumerate() {
+        for (page_index, path) in chapter.pages.iter().enumerate() {
+            let page = write_image(
+                format!("c{:04}-p{:04}", chapter_index + 1, page_index + 1),
+                path,
+            )?;
+            if page_index == 0 {
+                nav_entries.push((chapter.title.clone(), format!("pages/{}.xhtml", page.id)));
+            }
+            pages.push(page);
+        }
+    }
+
+    zip.start_file("OEBPS/nav.xhtml", stored)
+        .map_err(to_error)?;
+    zip.write_all(nav_xhtml(&metadata.title, &nav_entries).as_bytes())?;
+    zip.start_file("OEBPS/content.opf", stored)
+        .map_err(to_error)?;
+    zip.write_all(content_opf(metadata, cover.as_ref(), &pages).as_bytes())?;
+    zip.finish().map_err(to_error)
+}
+
+/// Maximum number of chapters fetched per chapter list request.
+const CHAPTER_LIST_LIMIT: usize = 100;
+
+/// Export volumes or a whole manga as EPUB 3 books.
+///
+/// External and not yet readable chapters are left out of the books.
+#[derive(Clone, Builder)]
+#[builder(setter(into, strip_option), pattern = "owned")]
+#[non_exhaustive]
+pub struct EpubExport {
+    #[doc(hidden)]
+    #[builder(pattern = "immutable")]
+    http_client: HttpClientRef,
+    manga: ApiObject<MangaAttributes>,
+    /// Credited as creators.
+    #[builder(default)]
+    authors: Vec<AuthorAttributes>,
+    /// Credited as contributors.
+    #[builder(default)]
+    artists: Vec<AuthorAttributes>,
+    /// Language of the exported chapters.
+    #[builder(default = "Language::English")]
+    translated_language: Language,
+    #[builder(default)]
+    mode: Option<DownloadMode>,
+    /// Maximum number of pages downloaded at the same time.
+    #[builder(default)]
+    concurrency: Option<usize>,
+    /// Enable reporting at `api.mangadex.network`.
+    #[builder(default)]
+    report: Option<bool>,
+}
+
+impl EpubExport {
+    /// Get the manga volumes in the exported language, in reading order.
+    pub async fn volumes(&self) -> Result<Vec<VolumeAggregate>> {
+        let client = MangaDexClient::new_with_http_client_ref(self.http_client.clone());
+        let volumes = client
+            .manga()
+            .aggregate()
+            .manga_id(self.manga.id)
+            .add_language(self.translated_language)
+            .build()
+            .map_err(request_builder_error)?
+            .send()
+            .await?
+            .volumes;
+        Ok(volumes)
+    }
+    /// Get every cover of the manga.
+    pub async fn covers(&self) -> Result<Vec<ApiObject<CoverAttributes>>> {
+        let client = MangaDexClient::new_with_http_client_ref(self.http_client.clone());
+        let mut covers = Vec::new();
+        loop {
+            let res = client
+                .cover()
+                .list()
+                .add_manga_id(self.manga.id)
+                .limit(100u32)
+                .offset(covers.len() as u32)
+                .build()
+                .map_err(request_builder_error)?
+                .send()
+                .await?;
+            let total = res.total as usize;
+            let is_empty = res.data.is_empty();
+            covers.extend(res.data);
+            if is_empty || covers.len() >= total {
+                return Ok(covers);
+            }
+        }
+    }
+    /// Get the attributes of the chapters still on MangaDex among `ids`.
+    async fn chapter_attributes(&self, ids: &[Uuid]) -> Result<HashMap<Uuid, ChapterAttributes>> {
+        let client = MangaDexClient::new_with_http_client_ref(self.http_client.clone());
+        let mut attributes = HashMap::new();
+        for batch in ids.chunks(CHAPTER_LIST_LIMIT) {
+            let mut builder = client
+                .chapter()
+                .list()
+                .chapter_ids(batch.to_vec())
+                .limit(CHAPTER_LIST_LIMIT as u32);
+            for content_rating in CONTENT_RATINGS {
+                builder = builder.add_content_rating(content_rating);
+            }
+            let res = builder
+                .build()
+                .map_err(request_builder_error)?
+                .send()
+                .await?;
+            attributes.extend(
+                res.data
+                    .into_iter()
+                    .map(|chapter| (chapter.id, chapter.attributes)),
+            );
+        }
+        Ok(attributes)
+    }
+    fn metadata(&self) -> EpubMetadata {
+        EpubMetadata::new(
+            self.manga.id,
+            &self.manga.attributes,
+            &self.authors,
+            &self.artists,
+            self.translated_language,
+        )
+    }
+    /// Export a single volume, with its volume cover as the book cover.
+    pub async fn export_volume<P: AsRef<Path>>(
+        &self,
+        volume: &VolumeAggregate,
+        output: P,
+    ) -> Result<PathBuf> {
+        let covers = self.covers().await?;
+        self.export_volume_with_covers(volume, &covers, output.as_ref())
+            .await
+    }
+    /// Export one book per volume to `output_dir`, named `volume-<number>.epub`.
+    ///
+    /// The volume goes through [`sanitize_file_name`], it is free text on MangaDex.
+    pub async fn export_volumes<P: AsRef<Path>>(&self, output_dir: P) -> Result<Vec<PathBuf>> {
+        let output_dir = output_dir.as_ref();
+        tokio::fs::create_dir_all(output_dir).await?;
+        let covers = self.covers().await?;
+        let mut books = Vec::new();
+        for volume in self.volumes().await? {
+            let output = output_dir.join(format!(
+                "volume-{}.epub",
+                sanitize_file_name(&volume.volume)
+            ));
+            books.push(
+                self.export_volume_with_covers(&volume, &covers, &output)
+                    .await?,
+            );
+        }
+        Ok(books)
+    }
+    /// Export every volume in a single book, with the manga cover as the book cover.
+    pub async fn export_manga<P: AsRef<Path>>(&self, output: P) -> Result<PathBuf> {
+        let volumes = self.volumes().await?;
+        self.write_book(
+            self.metadata(),
+            CoverSource::Manga(Box::new(self.manga.clone())),
+            &volumes,
+            output.as_ref(),
+        )
+        .await
+    }
+    async fn export_volume_with_covers(
+        &self,
+        volume: &VolumeAggregate,
+        covers: &[ApiObject<CoverAttributes>],
+        output: &Path,
+    ) -> Result<PathBuf> {
+        let mut metadata = self.metadata();
+        metadata.identifier = format!("{}:volume:{}", metadata.identifier, volume.volume);
+        if volume.number().is_numbered() {
+            metadata.title = format!("{} - Volume {}", metadata.title, volume.volume);
+        }
+        // Prefer the cover in the exported language, then in the original language
+        let volume_covers: Vec<&ApiObject<CoverAttributes>> = covers
+            .iter()
+            .filter(|cover| cover.attributes.volume.as_deref() == Some(volume.volume.as_str()))
+            .collect();
+        let cover = [
+            Some(self.translated_language),
+            Some(self.manga.attributes.original_language.language()),
+            None,
+        ]
+        .into_iter()
+        .find_map(|locale| {
+            volume_covers.iter().find(|cover| {
+                locale.is_none()
+                    || cover.attributes.locale.as_ref().map(LanguageTag::language) == locale
+            })
+        });
+        let cover = match cover {
+            Some(cover) => CoverSource::Cover(Box::new((*cover).clone())),
+            None => CoverSource::Manga(Box::new(self.manga.clone())),
+        };
+        self.write_book(metadata, cover, std::slice::from_ref(volume), output)
+            .await
+    }
+    /// Download the chapters and the cover to a `<output>.pages` directory and write the book.
+    ///
+    /// External and not yet readable chapters are skipped.
+    /// The work directory is removed once the book is written.
+    async fn write_book(
+        &self,
+        metadata: EpubMetadata,
+        cover: CoverSource,
+        volumes: &[VolumeAggregate],
+        output: &Path,
+    ) -> Result<PathBuf> {
+        let client = MangaDexClient::new_with_http_client_ref(self.http_client.clone());
+        let mut work_dir = output.as_os_str().to_owned();
+        work_dir.push(".pages");
+        let work_dir = PathBuf::from(work_dir);
+        tokio::fs::create_dir_all(&work_dir).await?;
+
+        let ids: Vec<Uuid> = volumes
+            .iter()
+            .flat_map(|volume| volume.chapters.iter().map(|chapter| chapter.id))
+            .collect();
+        let mut attributes = self.chapter_attributes(&ids).await?;
+        let now = OffsetDateTime::now_utc();
+
+        let mut chapters = Vec::new();
+        for chapter in volumes.iter().flat_map(|volume| volume.chapters.iter()) {
+            let mut download = client
+                .download()
+                .chapter(chapter.id)
+                .mode(self.mode.clone().unwrap_or_default())
+                .report(self.report.unwrap_or(false));
+            // The chapters missing from the chapter list are still tried
+            if let Some(attributes) = attributes.remove(&chapter.id) {
+                if !attributes.is_readable_at(&now) {
+                    continue;
+                }
+                download = download.attributes(attributes);
+            }
+            if let Some(concurrency) = self.concurrency {
+                download = download.concurrency(concurrency);
+            }
+            let download = download.build().map_err(request_builder_error)?;
+            let chapter_dir = work_dir.join(chapter.id.to_string());
+            let manifest = download.download_to_dir_resumable(&chapter_dir).await?;
+            chapters.push(EpubChapter {
+                title: if chapter.number().is_numbered() {
+                    format!("Chapter {}", chapter.chapter)
+                } else {
+                    "Oneshot".to_string()
+                },
+                pages: manifest
+                    .pages
+                    .iter()
+                    .map(|page| chapter_dir.join(page))
+                    .collect(),
+            });
+        }
+
+        // A missing cover shouldn't prevent reading the book
+        let cover_path = client
+            .download()
+            .cover()
+            .build()
+            .map_err(request_builder_error)?
+            .download_to_dir(cover, &work_dir)
+            .await
+            .ok();
+
+        let mut file = AtomicFile::create(output).await?;
+        let std_file = file.file().try_clone().await?.into_std().await;
+        let written = match tokio::task::spawn_blocking(move || {
+            write_epub(std_file, &metadata, cover_path.as_deref(), &chapters)
+        })
+        .await
+        {
+            Ok(d) => d,
+            Err(e) => Err(Error::UnexpectedError(anyhow::Error::new(e))),
+        };
+        if let Err(e) = written {
+            file.discard().await;
+            return Err(e);
+        }
+        let path = file.persist().await?;
+        tokio::fs::remove_dir_all(&work_dir).await?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read};
+
+    use serde_json::json;
+    use url::Url;
+    use wiremock::matchers::{method, path, path_regex};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+    use crate::HttpClient;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR".to_vec();
+        png.extend_from_slice(&width.to_be_bytes());
+        png.extend_from_slice(&height.to_be_bytes());
+        png
+    }
+
+    #[test]
+    fn write_epub_with_fixed_layout_pages() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("mangadex-api-epub-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir)?;
+        let cover = dir.join("cover.png");
+        let page = dir.join("1-aaaa.png");
+        std::fs::write(&cover, png(600, 900))?;
+        std::fs::write(&page, png(800, 1200))?;
+        let metadata = EpubMetadata {
+            identifier: "urn:uuid:0".to_string(),
+            title: "Volume & Co".to_string(),
+            language: "en".to_string(),
+            creators: vec!["Author".to_string()],
+            contributors: vec![],
+            description: None,
+            subjects: vec![],
+            year: None,
+            rtl: true,
+            modified: "2023-01-01T00:00:00Z".to_string(),
+        };
+        let chapters = vec![EpubChapter {
+            title: "Chapter 1".to_string(),
+            pages: vec![page],
+        }];
+
+        let cursor = write_epub(Cursor::new(Vec::new()), &metadata, Some(&cover), &chapters)?;
+        let mut archive = zip::ZipArchive::new(cursor).unwrap();
+        assert_eq!(archive.by_index(0).unwrap().name(), "mimetype");
+        let mut opf = String::new();
+        archive
+            .by_name("OEBPS/content.opf")
+            .unwrap()
+            .read_to_string(&mut opf)?;
+        assert!(opf.contains("<dc:title>Volume &amp; Co</dc:title>"));
+        assert!(opf.contains("properties=\"cover-image\""));
+        assert!(opf.contains("page-progression-direction=\"rtl\""));
+        let mut page = String::new();
+        archive
+            .by_name("OEBPS/pages/c0001-p0001.xhtml")
+            .unwrap()
+            .read_to_string(&mut page)?;
+        assert!(page.contains("width=800, height=1200"));
+        let mut nav = String::new();
+        archive
+            .by_name("OEBPS/nav.xhtml")
+            .unwrap()
+            .read_to_string(&mut nav)?;
+        assert!(nav.contains("<a href=\"pages/c0001-p0001.xhtml\">Chapter 1</a>"));
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    fn chapter(id: Uuid, number: &str, external_url: Option<&str>) -> serde_json::Value {
+        json!({
+            "id": id,
+            "type": "chapter",
+            "attributes": {
+                "title": "",
+                "volume": "1",
+                "chapter": number,
+                "pages": if external_url.is_some() { 0 } else { 1 },
+                "translatedLanguage": "en",
+                "externalUrl": external_url,
+                "version": 1,
+                "createdAt": "2023-01-01T00:00:00+00:00",
+                "updatedAt": null,
+                "publishAt": "2023-01-01T00:00:00+00:00",
+                "readableAt": "2023-01-01T00:00:00+00:00"
+            },
+            "relationships": []
+        })
+    }
+
+    fn manga() -> serde_json::Result<ApiObject<MangaAttributes>> {
+        serde_json::from_value(json!({
+            "id": Uuid::new_v4(),
+            "type": "manga",
+            "attributes": {
+                "title": { "en": "Title" },
+                "altTitles": [],
+                "description": {},
+                "isLocked": false,
+                "links": null,
+                "originalLanguage": "ja",
+                "lastVolume": null,
+                "lastChapter": null,
+                "publicationDemographic": null,
+                "status": "ongoing",
+                "year": null,
+                "contentRating": "safe",
+                "chapterNumbersResetOnNewVolume": false,
+                "latestUploadedChapter": null,
+                "availableTranslatedLanguages": [],
+                "tags": [],
+                "state": "published",
+                "createdAt": "2023-01-01T00:00:00+00:00",
+                "updatedAt": null,
+                "version": 1
+            },
+            "relationships": []
+        }))
+    }
+
+    #[tokio::test]
+    async fn external_chapters_are_left_out_of_the_book() -> anyhow::Result<()> {
+        let mock_server = MockServer::start().await;
+        let client = MangaDexClient::new_with_http_client(
+            HttpClient::builder()
+                .base_url(Url::parse(&mock_server.uri())?)
+                .build()?,
+        );
+        let readable = Uuid::new_v4();
+        let external = Uuid::new_v4();
+        Mock::given(method("GET"))
+            .and(path("/chapter"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "result": "ok",
+                "response": "collection",
+                "data": [
+                    chapter(readable, "1", None),
+                    chapter(external, "2", Some("https://example.org/chapter/2"))
+                ],
+                "limit": 100,
+                "offset": 0,
+                "total": 2
+            })))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/cover"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "result": "ok",
+                "response": "collection",
+                "data": [],
+                "limit": 100,
+                "offset": 0,
+                "total": 0
+            })))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("/at-home/server/{readable}")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "result": "ok",
+                "baseUrl": mock_server.uri(),
+                "chapter": { "hash": "hash", "data": ["1.png"], "dataSaver": ["1.png"] }
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path_regex(r"^/at-home/server/"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/data/hash/1.png"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(png(800, 1200)))
+            .mount(&mock_server)
+            .await;
+
+        let manga = manga()?;
+        let volume: VolumeAggregate = serde_json::from_value(json!({
+            "volume": "1",
+            "count": 2,
+            "chapters": [
+                { "chapter": "1", "id": readable, "others": [], "count": 1 },
+                { "chapter": "2", "id": external, "others": [], "count": 1 }
+            ]
+        }))?;
+        let dir = std::env::temp_dir().join(format!("mangadex-api-epub-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir)?;
+
+        let book = client
+            .export()
+            .epub(manga)
+            .mode(DownloadMode::Normal)
+            .build()?
+            .export_volume(&volume, dir.join("volume-1.epub"))
+            .await?;
+
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(&book)?)?;
+        let mut nav = String::new();
+        archive
+            .by_name("OEBPS/nav.xhtml")?
+            .read_to_string(&mut nav)?;
+        assert!(nav.contains(">Chapter 1</a>"));
+        assert!(!nav.contains("Chapter 2"));
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn volume_books_stay_in_the_output_directory() -> anyhow::Result<()> {
+        let mock_server = MockServer::start().await;
+        let client = MangaDexClient::new_with_http_client(
+            HttpClient::builder()
+                .base_url(Url::parse(&mock_server.uri())?)
+                .build()?,
+        );
+        let manga = manga()?;
+        let chapter_id = Uuid::new_v4();
+        Mock::given(method("GET"))
+            .and(path(format!("/manga/{}/aggregate", manga.id)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "result": "ok",
+                "volumes": {
+                    "1/2": {
+                        "volume": "1/2",
+                        "count": 1,
+                        "chapters": {
+                            "1": { "chapter": "1", "id": chapter_id, "others": [], "count": 1 }
+                        }
+                    }
+                }
+            })))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/chapter"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "result": "ok",
+                "response": "collection",
+                "data": [chapter(chapter_id, "1", None)],
+                "limit": 100,
+                "offset": 0,
+                "total": 1
+            })))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/cover"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "result": "ok",
+                "response": "collection",
+                "data": [],
+                "limit": 100,
+                "offset": 0,
+                "total": 0
+            })))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("/at-home/server/{chapter_id}")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "result": "ok",
+                "baseUrl": mock_server.uri(),
+                "chapter": { "hash": "hash", "data": ["1.png"], "dataSaver": ["1.png"] }
+            })))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/data/hash/1.png"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(png(800, 1200)))
+            .mount(&mock_server)
+            .await;
+        let dir = std::env::temp_dir().join(format!("mangadex-api-epub-{}", Uuid::new_v4()));
+
+        let books = client
+            .export()
+            .epub(manga)
+            .mode(DownloadMode::Normal)
+            .build()?
+            .export_volumes(&dir)
+            .await;
+        let entries: Vec<PathBuf> = std::fs::read_dir(&dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<_>>()?;
+        std::fs::remove_dir_all(&dir)?;
+
+        assert_eq!(books?, vec![dir.join("volume-1_2.epub")]);
+        assert_eq!(entries, vec![dir.join("volume-1_2.epub")]);
+        Ok(())
+    }
+}
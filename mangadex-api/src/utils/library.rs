@@ -0,0 +1,574 @@
+//! Lay out downloaded manga on disk with a path template, and scan such a library back into ids.
+//!
+//! A template is a `/` separated path where each placeholder is replaced by a value:
+//!
+//! | Placeholder       | Value                                                  |
+//! |-------------------|--------------------------------------------------------|
+//! | `{manga_title}`   | The manga title, see [`localized_title`]               |
+//! | `{manga_id}`      | The manga id                                           |
+//! | `{volume}`        | The volume number                                      |
+//! | `{chapter}`       | The chapter number                                     |
+//! | `{chapter_title}` | The chapter title                                      |
+//! | `{chapter_id}`    | The chapter id                                         |
+//! | `{group}`         | The scanlation group names                             |
+//! | `{language}`      | The chapter language code                              |
+//!
+//! A section enclosed in `<` and `>` is left out when one of its placeholders has no value,
+//! like the volume of a chapter without volume.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use mangadex_api::utils::library::{Library, PathTemplate, PathValues};
+//! use uuid::Uuid;
+//!
+//! # fn run() -> anyhow::Result<()> {
+//! let template = PathTemplate::parse("{manga_title}/<Vol.{volume} >Ch.{chapter}< [{group}]>")?;
+//! let library = Library::new("/library", template);
+//!
+//! let values = PathValues {
+//!     manga_id: Some(Uuid::new_v4()),
+//!     manga_title: Some("Oshi no Ko".to_string()),
+//!     chapter: Some("1".to_string()),
+//!     group: Some("Some group".to_string()),
+//!     ..Default::default()
+//! };
+//! assert_eq!(
+//!     library.path_of(&values).to_str(),
+//!     Some("/library/Oshi no Ko/Ch.1 [Some group]")
+//! );
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use mangadex_api_schema::v5::{ChapterObject, MangaAttributes, RelatedAttributes};
+use mangadex_api_schema::ApiObject;
+use mangadex_api_types::error::{Error, Result};
+use mangadex_api_types::{Language, RelationshipType};
+use uuid::Uuid;
+
+use crate::utils::download::chapter::ChapterManifest;
+
+/// Maximum length of a file name in bytes on most Linux filesystems.
+const MAX_FILE_NAME_LEN: usize = 255;
+
+/// Placeholders of a [`PathTemplate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PathField {
+    MangaTitle,
+    MangaId,
+    Volume,
+    Chapter,
+    ChapterTitle,
+    ChapterId,
+    Group,
+    Language,
+}
+
+impl PathField {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::MangaTitle => "manga_title",
+            Self::MangaId => "manga_id",
+            Self::Volume => "volume",
+            Self::Chapter => "chapter",
+            Self::ChapterTitle => "chapter_title",
+            Self::ChapterId => "chapter_id",
+            Self::Group => "group",
+            Self::Language => "language",
+        }
+    }
+}
+
+impl FromStr for PathField {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "manga_title" => Self::MangaTitle,
+            "manga_id" => Self::MangaId,
+            "volume" => Self::Volume,
+            "chapter" => Self::Chapter,
+            "chapter_title" => Self::ChapterTitle,
+            "chapter_id" => Self::ChapterId,
+            "group" => Self::Group,
+            "language" => Self::Language,
+            _ => {
+                return Err(Error::ParseError(format!(
+                    "unknown path placeholder {{{s}}}"
+                )))
+            }
+        })
+    }
+}
+
+impl Display for PathField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{{{}}}", self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Literal(String),
+    Field(PathField),
+    Optional(Vec<Token>),
+}
+
+/// A parsed path template, see the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathTemplate {
+    template: String,
+    segments: Vec<Vec<Token>>,
+}
+
+impl PathTemplate {
+    pub fn parse(template: &str) -> Result<Self> {
+        let mut segments: Vec<Vec<Token>> = vec![Vec::new()];
+        let mut optional: Option<Vec<Token>> = None;
+        let mut literal = String::new();
+        let mut chars = template.chars();
+        while let Some(c) = chars.next() {
+            if !matches!(c, '{' | '<' | '>' | '/' | '}') {
+                literal.push(c);
+                continue;
+            }
+            if !literal.is_empty() {
+                let token = Token::Literal(std::mem::take(&mut literal));
+                push_token(&mut segments, &mut optional, token);
+            }
+            match c {
+                '{' => {
+                    let name: String = chars.by_ref().take_while(|c| *c != '}').collect();
+                    push_token(&mut segments, &mut optional, Token::Field(name.parse()?));
+                }
+                '<' if optional.is_none() => optional = Some(Vec::new()),
+                '>' if optional.is_some() => {
+                    let section = optional.take().expect("checked by the match guard");
+                    push_token(&mut segments, &mut optional, Token::Optional(section));
+                }
+                '/' if optional.is_none() => segments.push(Vec::new()),
+                _ => {
+                    return Err(Error::ParseError(format!(
+                        "unexpected `{c}` in the path template {template}"
+                    )))
+                }
+            }
+        }
+        if optional.is_some() {
+            return Err(Error::ParseError(format!(
+                "unclosed `<` in the path template {template}"
+            )));
+        }
+        if !literal.is_empty() {
+            push_token(&mut segments, &mut optional, Token::Literal(literal));
+        }
+        if segments.iter().any(|segment| segment.is_empty()) {
+            return Err(Error::ParseError(format!(
+                "empty path segment in the path template {template}"
+            )));
+        }
+        Ok(Self {
+            template: template.to_string(),
+            segments,
+        })
+    }
+    pub fn as_str(&self) -> &str {
+        &self.template
+    }
+    /// Render the template into a relative path.
+    pub fn render(&self, values: &PathValues) -> PathBuf {
+        self.segments
+            .iter()
+            .map(|segment| {
+                let mut rendered = String::new();
+                render_tokens(segment, values, &mut rendered);
+                sanitize_file_name(&rendered)
+            })
+            .collect()
+    }
+    /// Match a relative path against the template, returns the placeholder values.
+    ///
+    /// Ids must be valid UUIDs to match.
+    pub fn match_path<P: AsRef<Path>>(&self, path: P) -> Option<BTreeMap<PathField, String>> {
+        let components: Vec<String> = path
+            .as_ref()
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        if components.len() != self.segments.len() {
+            return None;
+        }
+        let mut captures: Vec<(PathField, String)> = Vec::new();
+        for (segment, component) in self.segments.iter().zip(&components) {
+            if !match_tokens(segment, component, &mut captures) {
+                return None;
+            }
+        }
+        Some(captures.into_iter().collect())
+    }
+}
+
+impl FromStr for PathTemplate {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+fn push_token(segments: &mut [Vec<Token>], optional: &mut Option<Vec<Token>>, token: Token) {
+    match optional {
+        Some(section) => section.push(token),
+        None => segments
+            .last_mut()
+            .expect("there is always a segment")
+            .push(token),
+    }
+}
+
+/// Returns `false` if a placeholder has no value.
+fn render_tokens(tokens: &[Token], values: &PathValues, rendered: &mut String) -> bool {
+    let mut complete = true;
+    for token in tokens {
+        match token {
+            Token::Literal(literal) => rendered.push_str(literal),
+            Token::Field(field) => match values.get(*field) {
+                Some(value) => rendered.push_str(&sanitize_file_name(&value)),
+                None => complete = false,
+            },
+            Token::Optional(section) => {
+                let mut section_rendered = String::new();
+                if render_tokens(section, values, &mut section_rendered) {
+                    rendered.push_str(&section_rendered);
+                }
+            }
+        }
+    }
+    complete
+}
+
+fn match_tokens(tokens: &[Token], input: &str, captures: &mut Vec<(PathField, String)>) -> bool {
+    let (first, rest) = match tokens.split_first() {
+        Some(d) => d,
+        None => return input.is_empty(),
+    };
+    match first {
+        Token::Literal(literal) => match input.strip_prefix(literal.as_str()) {
+            Some(remaining) => match_tokens(rest, remaining, captures),
+            None => false,
+        },
+        Token::Field(field) => {
+            // Shortest values first, a placeholder never captures an empty value
+            for end in (1..=input.len()).filter(|end| input.is_char_boundary(*end)) {
+                let value = &input[..end];
+                if matches!(field, PathField::MangaId | PathField::ChapterId)
+                    && Uuid::parse_str(value).is_err()
+                {
+                    continue;
+                }
+                captures.push((*field, value.to_string()));
+                if match_tokens(rest, &input[end..], captures) {
+                    return true;
+                }
+                captures.pop();
+            }
+            false
+        }
+        Token::Optional(section) => {
+            let with_section: Vec<Token> = section.iter().chain(rest).cloned().collect();
+            let len = captures.len();
+            if match_tokens(&with_section, input, captures) {
+                return true;
+            }
+            captures.truncate(len);
+            match_tokens(rest, input, captures)
+        }
+    }
+}
+
+/// Make `name` a valid Linux file name.
+///
+/// `/`, NUL and control characters are replaced by `_`, surrounding whitespace is trimmed,
+/// `.` and `..` are escaped and the name is truncated to 255 bytes.
+pub fn sanitize_file_name(name: &str) -> String {
+    let sanitized: String = name
+        .chars()
+        .map(|c| if c == '/' || c.is_control() { '_' } else { c })
+        .collect();
+    let mut sanitized = sanitized.trim().to_string();
+    if sanitized.is_empty() || sanitized == "." || sanitized == ".." {
+        sanitized = sanitized.replace('.', "_");
+        if sanitized.is_empty() {
+            sanitized.push('_');
+        }
+    }
+    if sanitized.len() > MAX_FILE_NAME_LEN {
+        let mut end = MAX_FILE_NAME_LEN;
+        while !sanitized.is_char_boundary(end) {
+            end -= 1;
+        }
+        sanitized.truncate(end);
+    }
+    sanitized
+}
+
+/// Pick the manga title in the first of `languages` it is available in,
+/// see [`MangaAttributes::best_title`].
+pub fn localized_title(manga: &MangaAttributes, languages: &[Language]) -> Option<String> {
+    manga.best_title(languages).map(String::from)
+}
+
+/// Values of the [`PathTemplate`] placeholders.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PathValues {
+    pub manga_id: Option<Uuid>,
+    pub manga_title: Option<String>,
+    pub volume: Option<String>,
+    pub chapter: Option<String>,
+    pub chapter_title: Option<String>,
+    pub chapter_id: Option<Uuid>,
+    pub group: Option<String>,
+    pub language: Option<Language>,
+}
+
+impl PathValues {
+    /// Fill the values from a manga and one of its chapters.
+    ///
+    /// The group names are read from the expanded scanlation group relationships of `chapter`.
+    /// The manga title is picked with [`localized_title`].
+    pub fn from_chapter(
+        manga: &ApiObject<MangaAttributes>,
+        chapter: &ChapterObject,
+        languages: &[Language],
+    ) -> Self {
+        let groups: Vec<&str> = chapter
+            .relationships
+            .iter()
+            .filter(|relationship| relationship.type_ == RelationshipType::ScanlationGroup)
+            .filter_map(|relationship| match &relationship.attributes {
+                Some(RelatedAttributes::ScanlationGroup(group)) => Some(group.name.as_str()),
+                _ => None,
+            })
+            .collect();
+        let attributes = &chapter.attributes;
+        Self {
+            manga_id: Some(manga.id),
+            manga_title: localized_title(&manga.attributes, languages),
+            volume: attributes.volume.clone(),
+            chapter: attributes.chapter.clone(),
+            chapter_title: Some(attributes.title.clone()).filter(|title| !title.is_empty()),
+            chapter_id: Some(chapter.id),
+            group: Some(groups.join(" & ")).filter(|groups| !groups.is_empty()),
+            language: Some(attributes.translated_language.language()),
+        }
+    }
+    pub fn get(&self, field: PathField) -> Option<String> {
+        match field {
+            PathField::MangaTitle => self.manga_title.clone(),
+            PathField::MangaId => self.manga_id.map(|id| id.to_string()),
+            PathField::Volume => self.volume.clone(),
+            PathField::Chapter => self.chapter.clone(),
+            PathField::ChapterTitle => self.chapter_title.clone(),
+            PathField::ChapterId => self.chapter_id.map(|id| id.to_string()),
+            PathField::Group => self.group.clone(),
+            PathField::Language => self.language.map(|language| language.code2().to_string()),
+        }
+    }
+}
+
+/// A chapter found by [`Library::scan`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LibraryEntry {
+    pub path: PathBuf,
+    pub manga_id: Option<Uuid>,
+    /// Read from the path, or from the [`ChapterManifest`] of a resumable download.
+    pub chapter_id: Option<Uuid>,
+    /// Placeholder values read from the path.
+    pub values: BTreeMap<PathField, String>,
+}
+
+/// A directory laid out with a [`PathTemplate`].
+#[derive(Debug, Clone)]
+pub struct Library {
+    root: PathBuf,
+    template: PathTemplate,
+}
+
+impl Library {
+    pub fn new<P: Into<PathBuf>>(root: P, template: PathTemplate) -> Self {
+        Self {
+            root: root.into(),
+            template,
+        }
+    }
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+    pub fn template(&self) -> &PathTemplate {
+        &self.template
+    }
+    /// Get the path of a chapter in the library.
+    pub fn path_of(&self, values: &PathValues) -> PathBuf {
+        self.root.join(self.template.render(values))
+    }
+    /// Find the chapters of the library, the paths matching the template.
+    ///
+    /// Ids can only be found if the template has the `{manga_id}` or `{chapter_id}` placeholders,
+    /// or, for chapters, if they were downloaded with
+    /// [`ChapterDownload::download_to_dir_resumable`](crate::utils::download::chapter::ChapterDownload::download_to_dir_resumable).
+    pub async fn scan(&self) -> Result<Vec<LibraryEntry>> {
+        let depth = self.template.segments.len();
+        let mut entries: Vec<LibraryEntry> = Vec::new();
+        let mut dirs: Vec<(PathBuf, usize)> = vec![(self.root.clone(), 0)];
+        while let Some((dir, level)) = dirs.pop() {
+            let mut read_dir = match tokio::fs::read_dir(&dir).await {
+                Ok(d) => d,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound && level == 0 => {
+                    return Ok(entries)
+                }
+                Err(e) => return Err(Error::Io(e)),
+            };
+            while let Some(entry) = read_dir.next_entry().await? {
+                let path = entry.path();
+                let is_dir = entry.file_type().await?.is_dir();
+                if level + 1 < depth {
+                    if is_dir {
+                        dirs.push((path, level + 1));
+                    }
+                    continue;
+                }
+                let relative = match path.strip_prefix(&self.root) {
+                    Ok(d) => d,
+                    Err(_) => continue,
+                };
+                let values = match self.template.match_path(relative) {
+                    Some(d) => d,
+                    None => continue,
+                };
+                let id = |field| {
+                    values
+                        .get(&field)
+                        .and_then(|id: &String| Uuid::parse_str(id).ok())
+                };
+                let mut chapter_id = id(PathField::ChapterId);
+                if chapter_id.is_none() && is_dir {
+                    chapter_id = ChapterManifest::load(&path)
+                        .await
+                        .ok()
+                        .flatten()
+                        .map(|manifest| manifest.chapter_id);
+                }
+                entries.push(LibraryEntry {
+                    manga_id: id(PathField::MangaId),
+                    chapter_id,
+                    path,
+                    values,
+                });
+            }
+        }
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_skips_the_optional_sections_without_value() -> Result<()> {
+        let template =
+            PathTemplate::parse("{manga_title}/<Vol.{volume} >Ch.{chapter}< [{group}]>")?;
+        let mut values = PathValues {
+            manga_title: Some("AC/DC: the manga".to_string()),
+            volume: Some("2".to_string()),
+            chapter: Some("10.5".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            template.render(&values),
+            PathBuf::from("AC_DC: the manga/Vol.2 Ch.10.5")
+        );
+        values.volume = None;
+        values.group = Some("Group".to_string());
+        assert_eq!(
+            template.render(&values),
+            PathBuf::from("AC_DC: the manga/Ch.10.5 [Group]")
+        );
+        assert!(PathTemplate::parse("{title}").is_err());
+        assert!(PathTemplate::parse("<{volume}/{chapter}>").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn sanitize_linux_file_names() {
+        assert_eq!(sanitize_file_name(" a/b\0c "), "a_b_c");
+        assert_eq!(sanitize_file_name(".."), "__");
+        assert_eq!(sanitize_file_name(""), "_");
+        assert_eq!(sanitize_file_name(&"é".repeat(200)).len(), 254);
+    }
+
+    #[test]
+    fn match_path_reads_the_ids_back() -> Result<()> {
+        let template =
+            PathTemplate::parse("{manga_title} [{manga_id}]/<Vol.{volume} >Ch.{chapter}")?;
+        let manga_id = Uuid::new_v4();
+        let values = PathValues {
+            manga_id: Some(manga_id),
+            manga_title: Some("Title [with brackets]".to_string()),
+            chapter: Some("3".to_string()),
+            ..Default::default()
+        };
+        let captures = template
+            .match_path(template.render(&values))
+            .expect("the rendered path matches");
+        assert_eq!(
+            captures.get(&PathField::MangaId),
+            Some(&manga_id.to_string())
+        );
+        assert_eq!(
+            captures.get(&PathField::MangaTitle).map(String::as_str),
+            Some("Title [with brackets]")
+        );
+        assert_eq!(captures.get(&PathField::Volume), None);
+        assert_eq!(
+            captures.get(&PathField::Chapter).map(String::as_str),
+            Some("3")
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn scan_finds_the_rendered_chapters() -> Result<()> {
+        let root = std::env::temp_dir().join(format!("mangadex-api-library-{}", Uuid::new_v4()));
+        let library = Library::new(
+            &root,
+            PathTemplate::parse("{manga_title} ({manga_id})/Ch.{chapter} {chapter_id}")?,
+        );
+        let values = PathValues {
+            manga_id: Some(Uuid::new_v4()),
+            manga_title: Some("Manga".to_string()),
+            chapter: Some("1".to_string()),
+            chapter_id: Some(Uuid::new_v4()),
+            ..Default::default()
+        };
+        let path = library.path_of(&values);
+        tokio::fs::create_dir_all(&path).await?;
+        tokio::fs::create_dir_all(root.join("not a manga")).await?;
+
+        let entries = library.scan().await?;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path, path);
+        assert_eq!(entries[0].manga_id, values.manga_id);
+        assert_eq!(entries[0].chapter_id, values.chapter_id);
+
+        tokio::fs::remove_dir_all(&root).await?;
+        Ok(())
+    }
+}
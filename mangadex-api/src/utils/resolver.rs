@@ -0,0 +1,317 @@
+//! Hydrate unexpanded relationships with batched list requests.
+//!
+//! When [Reference Expansion](https://api.mangadex.org/docs/reference-expansion/) isn't used
+//! (or can't be, like the tags of a chapter's manga), each [`Relationship`] only carries an id.
+//! Looking them up one by one ends up in N+1 requests,
+//! the [`RelationshipResolver`] collects the ids by type and fetches them with a handful of list requests instead.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use mangadex_api::MangaDexClient;
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let client = MangaDexClient::default();
+//!
+//! let mut chapters = client.chapter().list().limit(10u32).build()?.send().await?;
+//!
+//! client.resolver().resolve(chapters.data.iter_mut()).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{HashMap, HashSet};
+
+use mangadex_api_schema::v5::{
+    AuthorAttributes, MangaAttributes, RelatedAttributes, Relationship, ScanlationGroupAttributes,
+    UserAttributes,
+};
+use mangadex_api_schema::ApiObject;
+use mangadex_api_types::error::Result;
+use mangadex_api_types::RelationshipType;
+use uuid::Uuid;
+
+use crate::utils::{request_builder_error, CONTENT_RATINGS};
+use crate::{HttpClientRef, MangaDexClient};
+
+/// Maximum number of ids the list endpoints accept in a single request.
+pub const MAX_IDS_PER_REQUEST: usize = 100;
+
+/// The list endpoint used to resolve a relationship.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum ResolvableKind {
+    /// Resolved with `GET /manga`.
+    Manga,
+    /// Resolved with `GET /group`.
+    ScanlationGroup,
+    /// Resolved with `GET /author`, this covers both authors and artists.
+    Author,
+    /// Resolved with `GET /user`, this covers users, group leaders, members and creators.
+    User,
+}
+
+impl ResolvableKind {
+    /// Get the list endpoint able to resolve a relationship type, if any.
+    pub fn from_relationship_type(type_: RelationshipType) -> Option<Self> {
+        match type_ {
+            RelationshipType::Manga => Some(Self::Manga),
+            RelationshipType::ScanlationGroup => Some(Self::ScanlationGroup),
+            RelationshipType::Author | RelationshipType::Artist => Some(Self::Author),
+            RelationshipType::User
+            | RelationshipType::Leader
+            | RelationshipType::Member
+            | RelationshipType::Creator => Some(Self::User),
+            _ => None,
+        }
+    }
+}
+
+/// Attributes fetched by the resolver, indexed by id.
+#[derive(Debug, Clone, Default)]
+pub struct ResolvedAttributes {
+    pub manga: HashMap<Uuid, MangaAttributes>,
+    pub scanlation_groups: HashMap<Uuid, ScanlationGroupAttributes>,
+    pub authors: HashMap<Uuid, AuthorAttributes>,
+    pub users: HashMap<Uuid, UserAttributes>,
+}
+
+impl ResolvedAttributes {
+    /// Build the attributes of a relationship from the fetched data.
+    pub fn get(&self, relationship: &Relationship) -> Option<RelatedAttributes> {
+        let id = &relationship.id;
+        match relationship.type_ {
+            RelationshipType::Manga => self.manga.get(id).cloned().map(RelatedAttributes::Manga),
+            RelationshipType::ScanlationGroup => self
+                .scanlation_groups
+                .get(id)
+                .cloned()
+                .map(RelatedAttributes::ScanlationGroup),
+            RelationshipType::Author => {
+                self.authors.get(id).cloned().map(RelatedAttributes::Author)
+            }
+            RelationshipType::Artist => {
+                self.authors.get(id).cloned().map(RelatedAttributes::Artist)
+            }
+            RelationshipType::User
+            | RelationshipType::Leader
+            | RelationshipType::Member
+            | RelationshipType::Creator => self.users.get(id).cloned().map(RelatedAttributes::User),
+            _ => None,
+        }
+    }
+}
+
+/// Collect the ids of the relationships without attributes, grouped by the list endpoint able to resolve them.
+pub fn collect_unexpanded_ids<'a, I>(relationships: I) -> HashMap<ResolvableKind, HashSet<Uuid>>
+where
+    I: IntoIterator<Item = &'a Relationship>,
+{
+    let mut ids: HashMap<ResolvableKind, HashSet<Uuid>> = HashMap::new();
+    for relationship in relationships {
+        if relationship.attributes.is_some() {
+            continue;
+        }
+        if let Some(kind) = ResolvableKind::from_relationship_type(relationship.type_) {
+            ids.entry(kind).or_default().insert(relationship.id);
+        }
+    }
+    ids
+}
+
+/// Fill the unexpanded relationships with the resolved attributes.
+///
+/// Relationships that already have attributes are left untouched.
+pub fn fill_relationships<'a, I>(relationships: I, resolved: &ResolvedAttributes)
+where
+    I: IntoIterator<Item = &'a mut Relationship>,
+{
+    for relationship in relationships {
+        if relationship.attributes.is_none() {
+            relationship.attributes = resolved.get(relationship);
+        }
+    }
+}
+
+/// Resolve unexpanded relationships of schema objects.
+#[derive(Debug, Clone)]
+pub struct RelationshipResolver {
+    http_client: HttpClientRef,
+}
+
+impl RelationshipResolver {
+    #[doc(hidden)]
+    pub(crate) fn new(http_client: HttpClientRef) -> Self {
+        Self { http_client }
+    }
+
+    /// Fill `Relationship::attributes` in place for every given object.
+    ///
+    /// The ids are fetched with at most one request per [`MAX_IDS_PER_REQUEST`] ids and per relationship kind.
+    pub async fn resolve<'a, A, T, I>(&self, objects: I) -> Result<()>
+    where
+        A: 'a,
+        T: 'a,
+        I: IntoIterator<Item = &'a mut ApiObject<A, T>>,
+    {
+        let mut objects: Vec<&mut ApiObject<A, T>> = objects.into_iter().collect();
+        let ids = collect_unexpanded_ids(
+            objects
+                .iter()
+                .flat_map(|object| object.relationships.iter()),
+        );
+        let resolved = self.fetch(&ids).await?;
+        fill_relationships(
+            objects
+                .iter_mut()
+                .flat_map(|object| object.relationships.iter_mut()),
+            &resolved,
+        );
+        Ok(())
+    }
+
+    /// Fetch the attributes of the given ids.
+    pub async fn fetch(
+        &self,
+        ids: &HashMap<ResolvableKind, HashSet<Uuid>>,
+    ) -> Result<ResolvedAttributes> {
+        let client = MangaDexClient::new_with_http_client_ref(self.http_client.clone());
+        let mut resolved = ResolvedAttributes::default();
+        for (kind, ids) in ids {
+            let ids: Vec<Uuid> = ids.iter().copied().collect();
+            for chunk in ids.chunks(MAX_IDS_PER_REQUEST) {
+                let limit = chunk.len() as u32;
+                match kind {
+                    ResolvableKind::Manga => {
+                        let mut builder = client.manga().list().limit(limit);
+                        for id in chunk {
+                            builder = builder.add_manga_id(id);
+                        }
+                        // Manga outside of the default content ratings would be missing otherwise.
+                        for content_rating in CONTENT_RATINGS {
+                            builder = builder.add_content_rating(content_rating);
+                        }
+                        let res = builder
+                            .build()
+                            .map_err(request_builder_error)?
+                            .send()
+                            .await?;
+                        resolved
+                            .manga
+                            .extend(res.data.into_iter().map(|o| (o.id, o.attributes)));
+                    }
+                    ResolvableKind::ScanlationGroup => {
+                        let mut builder = client.scanlation_group().list().limit(limit);
+                        for id in chunk {
+                            builder = builder.add_group_id(id);
+                        }
+                        let res = builder
+                            .build()
+                            .map_err(request_builder_error)?
+                            .send()
+                            .await?;
+                        resolved
+                            .scanlation_groups
+                            .extend(res.data.into_iter().map(|o| (o.id, o.attributes)));
+                    }
+                    ResolvableKind::Author => {
+                        let mut builder = client.author().list().limit(limit);
+                        for id in chunk {
+                            builder = builder.add_author(id);
+                        }
+                        let res = builder
+                            .build()
+                            .map_err(request_builder_error)?
+                            .send()
+                            .await?;
+                        resolved
+                            .authors
+                            .extend(res.data.into_iter().map(|o| (o.id, o.attributes)));
+                    }
+                    ResolvableKind::User => {
+                        let mut builder = client.user().list().limit(limit);
+                        for id in chunk {
+                            builder = builder.add_user_id(id);
+                        }
+                        let res = builder
+                            .build()
+                            .map_err(request_builder_error)?
+                            .send()
+                            .await?;
+                        resolved
+                            .users
+                            .extend(res.data.into_iter().map(|o| (o.id, o.attributes)));
+                    }
+                }
+            }
+        }
+        Ok(resolved)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mangadex_api_schema::v5::{RelatedAttributes, Relationship, UserAttributes};
+    use mangadex_api_types::RelationshipType;
+    use serde_json::json;
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn relationship(id: Uuid, type_: &str, attributes: Option<serde_json::Value>) -> Relationship {
+        let mut value = json!({ "id": id, "type": type_ });
+        if let Some(attributes) = attributes {
+            value["attributes"] = attributes;
+        }
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn collect_unexpanded_ids_groups_by_list_endpoint() {
+        let author_id = Uuid::new_v4();
+        let leader_id = Uuid::new_v4();
+        let expanded_user_id = Uuid::new_v4();
+        let relationships = vec![
+            relationship(author_id, "author", None),
+            relationship(author_id, "artist", None),
+            relationship(leader_id, "leader", None),
+            relationship(
+                expanded_user_id,
+                "user",
+                Some(json!({ "username": "user", "roles": [], "version": 1 })),
+            ),
+            relationship(Uuid::new_v4(), "cover_art", None),
+        ];
+
+        let ids = collect_unexpanded_ids(&relationships);
+
+        assert_eq!(ids.len(), 2);
+        assert_eq!(ids[&ResolvableKind::Author], HashSet::from([author_id]));
+        assert_eq!(ids[&ResolvableKind::User], HashSet::from([leader_id]));
+    }
+
+    #[test]
+    fn fill_relationships_uses_the_relationship_type() {
+        let user_id = Uuid::new_v4();
+        let mut relationships = vec![
+            relationship(user_id, "creator", None),
+            relationship(Uuid::new_v4(), "user", None),
+        ];
+        let mut resolved = ResolvedAttributes::default();
+        resolved.users.insert(
+            user_id,
+            serde_json::from_value::<UserAttributes>(
+                json!({ "username": "creator", "roles": [], "version": 1 }),
+            )
+            .unwrap(),
+        );
+
+        fill_relationships(&mut relationships, &resolved);
+
+        assert_eq!(relationships[0].type_, RelationshipType::Creator);
+        match relationships[0].attributes.as_ref().unwrap() {
+            RelatedAttributes::User(user) => assert_eq!(user.username, "creator"),
+            other => panic!("Expected user RelatedAttributes, got {other:?}"),
+        }
+        assert!(relationships[1].attributes.is_none());
+    }
+}
@@ -0,0 +1,506 @@
+//! Normalized entity store keyed by UUID.
+//!
+//! The [`EntityStore`] is an identity map holding one copy of each manga, chapter, cover,
+//! author, scanlation group, tag, user and custom list, whatever endpoint it came from.
+//! Every attributes struct carries a `version` field, the store keeps the highest one per id
+//! and notifies subscribers when an entity is added or updated.
+//!
+//! Objects can be ingested manually through [`EntityStore::ingest()`],
+//! or automatically for every response passing through a client built with a store:
+//!
+//! ```rust
+//! use mangadex_api::utils::store::EntityStore;
+//! use mangadex_api::{HttpClient, MangaDexClient};
+//! use mangadex_api_schema::v5::MangaAttributes;
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let store = EntityStore::new();
+//! let http_client = HttpClient::builder().store(store.clone()).build()?;
+//! let client = MangaDexClient::new_with_http_client(http_client);
+//!
+//! let mut events = store.subscribe();
+//! let manga = client.manga().random().build()?.send().await?;
+//!
+//! let stored = store.get::<MangaAttributes>(&manga.data.id);
+//! println!("{:?} {:?}", stored, events.try_recv());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::{Arc, PoisonError, RwLock};
+
+use mangadex_api_schema::v5::{
+    AuthorAttributes, ChapterAttributes, CoverAttributes, CustomListAttributes, MangaAttributes,
+    RelatedAttributes, Relationship, Results, ScanlationGroupAttributes, TagAttributes,
+    UserAttributes,
+};
+use mangadex_api_schema::{ApiData, ApiObject};
+use mangadex_api_types::RelationshipType;
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// Number of change notifications kept for slow subscribers.
+pub const DEFAULT_EVENT_CAPACITY: usize = 1024;
+
+/// Attributes of an entity that can be held in the [`EntityStore`].
+pub trait StoreEntity: Clone + Send + Sync + 'static {
+    /// Relationship type of the entity.
+    const TYPE: RelationshipType;
+
+    /// Version of the entity, incremented by MangaDex on each update.
+    fn version(&self) -> u32;
+
+    /// Ingest the entities nested in the attributes, such as the tags of a manga.
+    fn ingest_nested(&self, _store: &EntityStore) {}
+}
+
+macro_rules! store_entity {
+    ($($attributes:ty => $type_:ident,)*) => {
+        $(
+            impl StoreEntity for $attributes {
+                const TYPE: RelationshipType = RelationshipType::$type_;
+
+                fn version(&self) -> u32 {
+                    self.version
+                }
+            }
+        )*
+    };
+}
+
+store_entity! {
+    ChapterAttributes => Chapter,
+    CoverAttributes => CoverArt,
+    AuthorAttributes => Author,
+    ScanlationGroupAttributes => ScanlationGroup,
+    TagAttributes => Tag,
+    UserAttributes => User,
+    CustomListAttributes => CustomList,
+}
+
+impl StoreEntity for MangaAttributes {
+    const TYPE: RelationshipType = RelationshipType::Manga;
+
+    fn version(&self) -> u32 {
+        self.version
+    }
+
+    fn ingest_nested(&self, store: &EntityStore) {
+        self.tags.ingest_into(store);
+    }
+}
+
+/// Notification sent when an entity is added to the store or replaced by a newer version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StoreEvent {
+    pub type_: RelationshipType,
+    pub id: Uuid,
+    pub version: u32,
+    /// `None` if the entity wasn't in the store before.
+    pub previous_version: Option<u32>,
+}
+
+type Collection<A> = HashMap<Uuid, Arc<ApiObject<A>>>;
+
+/// Identity map of MangaDex entities, keeping the highest `version` per id.
+///
+/// Cloning the store is cheap, all the clones share the same entities.
+#[derive(Clone)]
+pub struct EntityStore {
+    collections: Arc<RwLock<HashMap<TypeId, Box<dyn Any + Send + Sync>>>>,
+    events: broadcast::Sender<StoreEvent>,
+}
+
+impl Default for EntityStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for EntityStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EntityStore")
+            .field("subscribers", &self.events.receiver_count())
+            .finish_non_exhaustive()
+    }
+}
+
+impl EntityStore {
+    /// Create an empty store.
+    pub fn new() -> Self {
+        Self::with_event_capacity(DEFAULT_EVENT_CAPACITY)
+    }
+
+    /// Create an empty store keeping up to `capacity` notifications for slow subscribers.
+    pub fn with_event_capacity(capacity: usize) -> Self {
+        let (events, _) = broadcast::channel(capacity);
+        Self {
+            collections: Default::default(),
+            events,
+        }
+    }
+
+    /// Subscribe to the entity changes.
+    pub fn subscribe(&self) -> broadcast::Receiver<StoreEvent> {
+        self.events.subscribe()
+    }
+
+    /// Insert an entity, keeping the one with the highest version.
+    ///
+    /// Returns `true` if the entity was added or replaced by a newer version.
+    ///
+    /// When both versions are the same, the stored relationships are only replaced if the new object has some,
+    /// so that a bare object coming from a relationship doesn't erase them.
+    pub fn insert<A: StoreEntity>(&self, object: ApiObject<A>) -> bool {
+        object.attributes.ingest_nested(self);
+        let id = object.id;
+        let version = object.attributes.version();
+        let previous_version = {
+            let mut collections = self
+                .collections
+                .write()
+                .unwrap_or_else(PoisonError::into_inner);
+            let collection = collections
+                .entry(TypeId::of::<A>())
+                .or_insert_with(|| Box::<Collection<A>>::default())
+                .downcast_mut::<Collection<A>>()
+                .expect("the collection is keyed by its type");
+            let previous_version = collection.get(&id).map(|o| o.attributes.version());
+            match previous_version {
+                Some(previous) if previous > version => return false,
+                Some(previous) if previous == version => {
+                    if !object.relationships.is_empty() {
+                        collection.insert(id, Arc::new(object));
+                    }
+                    return false;
+                }
+                _ => {
+                    collection.insert(id, Arc::new(object));
+                }
+            }
+            previous_version
+        };
+        // Sending only fails when there is no subscriber.
+        let _ = self.events.send(StoreEvent {
+            type_: A::TYPE,
+            id,
+            version,
+            previous_version,
+        });
+        true
+    }
+
+    /// Get an entity by its id.
+    pub fn get<A: StoreEntity>(&self, id: &Uuid) -> Option<Arc<ApiObject<A>>> {
+        let collections = self
+            .collections
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        collections
+            .get(&TypeId::of::<A>())
+            .and_then(|collection| collection.downcast_ref::<Collection<A>>())
+            .and_then(|collection| collection.get(id).cloned())
+    }
+
+    /// Get all the entities of a type.
+    pub fn get_all<A: StoreEntity>(&self) -> Vec<Arc<ApiObject<A>>> {
+        let collections = self
+            .collections
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        collections
+            .get(&TypeId::of::<A>())
+            .and_then(|collection| collection.downcast_ref::<Collection<A>>())
+            .map(|collection| collection.values().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Remove an entity from the store.
+    pub fn remove<A: StoreEntity>(&self, id: &Uuid) -> Option<Arc<ApiObject<A>>> {
+        let mut collections = self
+            .collections
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        collections
+            .get_mut(&TypeId::of::<A>())
+            .and_then(|collection| collection.downcast_mut::<Collection<A>>())
+            .and_then(|collection| collection.remove(id))
+    }
+
+    /// Ingest every entity contained in a response.
+    pub fn ingest<I: Ingest + ?Sized>(&self, data: &I) {
+        data.ingest_into(self);
+    }
+
+    /// Ingest every entity found in a raw JSON response.
+    ///
+    /// Any object with an `id`, a `type` and `attributes` is considered an entity,
+    /// this is what the client uses for the responses passing through it.
+    pub fn ingest_value(&self, value: &Value) {
+        match value {
+            Value::Array(values) => {
+                for value in values {
+                    self.ingest_value(value);
+                }
+            }
+            Value::Object(map) => {
+                if let (Some(id), Some(type_), Some(attributes)) =
+                    (map.get("id"), map.get("type"), map.get("attributes"))
+                {
+                    if let (Ok(id), Ok(type_)) =
+                        (Uuid::deserialize(id), RelationshipType::deserialize(type_))
+                    {
+                        let relationships = map
+                            .get("relationships")
+                            .and_then(|r| Vec::<Relationship>::deserialize(r).ok())
+                            .unwrap_or_default();
+                        if let Ok(attributes) =
+                            RelatedAttributes::from_value(type_, attributes.clone())
+                        {
+                            self.insert_related(id, attributes, relationships);
+                        }
+                    }
+                }
+                for value in map.values() {
+                    self.ingest_value(value);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn insert_related(
+        &self,
+        id: Uuid,
+        attributes: RelatedAttributes,
+        relationships: Vec<Relationship>,
+    ) {
+        fn object<A: StoreEntity>(
+            id: Uuid,
+            attributes: A,
+            relationships: Vec<Relationship>,
+        ) -> ApiObject<A> {
+            ApiObject {
+                id,
+                type_: A::TYPE,
+                attributes,
+                relationships,
+            }
+        }
+
+        match attributes {
+            RelatedAttributes::Manga(a) => self.insert(object(id, a, relationships)),
+            RelatedAttributes::Chapter(a) => self.insert(object(id, a, relationships)),
+            RelatedAttributes::CoverArt(a) => self.insert(object(id, a, relationships)),
+            RelatedAttributes::Author(a) | RelatedAttributes::Artist(a) => {
+                self.insert(object(id, a, relationships))
+            }
+            RelatedAttributes::ScanlationGroup(a) => self.insert(object(id, a, relationships)),
+            RelatedAttributes::Tag(a) => self.insert(object(id, a, relationships)),
+            RelatedAttributes::User(a) => self.insert(object(id, a, relationships)),
+            RelatedAttributes::CustomList(a) => self.insert(object(id, a, relationships)),
+            RelatedAttributes::UploadSessionFile(_) | RelatedAttributes::Unknown(_) => false,
+        };
+    }
+}
+
+/// Data from which entities can be ingested into an [`EntityStore`].
+pub trait Ingest {
+    fn ingest_into(&self, store: &EntityStore);
+}
+
+impl<A: StoreEntity> Ingest for ApiObject<A> {
+    fn ingest_into(&self, store: &EntityStore) {
+        store.insert(self.clone());
+        self.relationships.ingest_into(store);
+    }
+}
+
+impl Ingest for Relationship {
+    fn ingest_into(&self, store: &EntityStore) {
+        if let Some(attributes) = &self.attributes {
+            store.insert_related(self.id, attributes.clone(), Vec::new());
+        }
+    }
+}
+
+impl<T: Ingest> Ingest for Results<T> {
+    fn ingest_into(&self, store: &EntityStore) {
+        self.data.ingest_into(store);
+    }
+}
+
+impl<T: Ingest> Ingest for ApiData<T> {
+    fn ingest_into(&self, store: &EntityStore) {
+        self.data.ingest_into(store);
+    }
+}
+
+impl<T: Ingest> Ingest for [T] {
+    fn ingest_into(&self, store: &EntityStore) {
+        for data in self {
+            data.ingest_into(store);
+        }
+    }
+}
+
+impl<T: Ingest> Ingest for Vec<T> {
+    fn ingest_into(&self, store: &EntityStore) {
+        self.as_slice().ingest_into(store);
+    }
+}
+
+impl<T: Ingest> Ingest for Option<T> {
+    fn ingest_into(&self, store: &EntityStore) {
+        if let Some(data) = self {
+            data.ingest_into(store);
+        }
+    }
+}
+
+impl<T: Ingest, E> Ingest for Result<T, E> {
+    fn ingest_into(&self, store: &EntityStore) {
+        if let Ok(data) = self {
+            data.ingest_into(store);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mangadex_api_schema::v5::{CustomListAttributes, UserAttributes};
+    use serde_json::json;
+
+    use super::*;
+
+    fn user(id: Uuid, username: &str, version: u32) -> ApiObject<UserAttributes> {
+        serde_json::from_value(json!({
+            "id": id,
+            "type": "user",
+            "attributes": { "username": username, "roles": [], "version": version },
+            "relationships": []
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn store_keeps_the_highest_version() {
+        let store = EntityStore::new();
+        let mut events = store.subscribe();
+        let id = Uuid::new_v4();
+
+        assert!(store.insert(user(id, "v2", 2)));
+        assert!(!store.insert(user(id, "v1", 1)));
+        assert_eq!(
+            store
+                .get::<UserAttributes>(&id)
+                .unwrap()
+                .attributes
+                .username,
+            "v2"
+        );
+        assert!(store.insert(user(id, "v3", 3)));
+        assert_eq!(
+            store
+                .get::<UserAttributes>(&id)
+                .unwrap()
+                .attributes
+                .username,
+            "v3"
+        );
+
+        assert_eq!(events.try_recv().unwrap().previous_version, None);
+        let event = events.try_recv().unwrap();
+        assert_eq!(event.type_, RelationshipType::User);
+        assert_eq!((event.version, event.previous_version), (3, Some(2)));
+        assert!(events.try_recv().is_err());
+    }
+
+    #[test]
+    fn store_lookups_are_typed() {
+        let store = EntityStore::new();
+        let id = Uuid::new_v4();
+
+        store.insert(user(id, "user", 1));
+
+        assert!(store.get::<UserAttributes>(&id).is_some());
+        assert!(store.get::<CustomListAttributes>(&id).is_none());
+        assert_eq!(store.get_all::<UserAttributes>().len(), 1);
+    }
+
+    #[test]
+    fn store_ingests_relationships_from_raw_json() {
+        let store = EntityStore::new();
+        let list_id = Uuid::new_v4();
+        let owner_id = Uuid::new_v4();
+
+        store.ingest_value(&json!({
+            "result": "ok",
+            "response": "entity",
+            "data": {
+                "id": list_id,
+                "type": "custom_list",
+                "attributes": { "name": "Favorites", "visibility": "public", "version": 1 },
+                "relationships": [{
+                    "id": owner_id,
+                    "type": "user",
+                    "attributes": { "username": "owner", "roles": [], "version": 4 }
+                }]
+            }
+        }));
+
+        let list = store.get::<CustomListAttributes>(&list_id).unwrap();
+        assert_eq!(list.relationships.len(), 1);
+        assert_eq!(
+            store
+                .get::<UserAttributes>(&owner_id)
+                .unwrap()
+                .attributes
+                .version,
+            4
+        );
+    }
+
+    #[tokio::test]
+    async fn client_with_store_keeps_decode_errors() -> anyhow::Result<()> {
+        use mangadex_api_types::error::Error;
+        use url::Url;
+        use wiremock::matchers::method;
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        use crate::{HttpClient, MangaDexClient};
+
+        let mock_server = MockServer::start().await;
+        let store = EntityStore::new();
+        let http_client = HttpClient::builder()
+            .base_url(Url::parse(&mock_server.uri())?)
+            .store(store.clone())
+            .build()?;
+        let mangadex_client = MangaDexClient::new_with_http_client(http_client);
+
+        let user_id = Uuid::new_v4();
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "result": "ok",
+                "response": "entity",
+                "data": { "id": user_id, "type": "user" }
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let res = mangadex_client.user().me().build()?.send().await;
+
+        match res {
+            Err(Error::RequestError(e)) => assert!(e.is_decode()),
+            other => panic!("expected a decode error, got {other:?}"),
+        }
+        assert!(store.get::<UserAttributes>(&user_id).is_none());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1238 @@
+//! Keep a [`Library`] in step with the followed manga of the logged-in user.
+//!
+//! The synced chapters are recorded in a [`SyncState`] file at the library root.
+//! Each sync downloads the new chapters of the followed manga, downloads again the chapters
+//! re-uploaded with a new `version`, and handles the deleted chapters and the unfollowed manga
+//! according to the [`RemovedPolicy`] and the [`UnfollowedPolicy`].
+//!
+//! Newly followed manga are read from their whole manga feed,
+//! the manga already synced only from the followed manga feed.
+//! Chapters not readable yet are kept in the state and checked again on each sync.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use mangadex_api::utils::library::{Library, PathTemplate};
+//! use mangadex_api::utils::sync::UnfollowedPolicy;
+//! use mangadex_api::MangaDexClient;
+//! use mangadex_api_types::Language;
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let client = MangaDexClient::default();
+//! // Log in first, the followed manga belong to the logged-in user
+//!
+//! let library = Library::new(
+//!     "./library",
+//!     PathTemplate::parse("{manga_title}/<Vol.{volume} >Ch.{chapter}< [{group}]>")?,
+//! );
+//! let report = client
+//!     .download()
+//!     .library_sync(library)
+//!     .add_language(Language::English)
+//!     .unfollowed(UnfollowedPolicy::Delete)
+//!     .build()?
+//!     .sync()
+//!     .await?;
+//! println!(
+//!     "{} new, {} updated, {} failed",
+//!     report.downloaded.len(),
+//!     report.updated.len(),
+//!     report.failed.len()
+//! );
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+
+use derive_builder::Builder;
+use mangadex_api_schema::v5::{ChapterObject, MangaAttributes};
+use mangadex_api_schema::ApiObject;
+use mangadex_api_types::error::{Error, Result};
+use mangadex_api_types::{
+    Language, MangaDexDateTime, MangaFeedSortOrder, OrderDirection, ReferenceExpansionResource,
+    RelationshipType,
+};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+use crate::utils::download::chapter::{AtHomeReporter, ChapterManifest, DownloadMode};
+use crate::utils::download::progress::ProgressHandler;
+use crate::utils::download::sink::AtomicFile;
+use crate::utils::download::throttle::BandwidthLimiter;
+use crate::utils::library::{localized_title, Library, PathValues};
+use crate::utils::{request_builder_error, whole_manga_feed, CONTENT_RATINGS, FEED_LIMIT};
+use crate::{HttpClientRef, MangaDexClient};
+
+/// File name of the sync state written at the library root.
+pub const SYNC_STATE_FILE_NAME: &str = "mangadex-sync.json";
+
+/// Maximum number of manga the followed manga list returns per request.
+const FOLLOWS_LIMIT: u32 = 100;
+/// Maximum number of chapters checked per chapter list request.
+const CHECK_LIMIT: usize = 100;
+
+/// What to do with the downloaded chapters of a manga that is no longer followed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnfollowedPolicy {
+    /// Keep the chapters, the manga just stops being synced.
+    #[default]
+    Keep,
+    /// Delete the chapters and forget the manga.
+    Delete,
+}
+
+/// What to do with a downloaded chapter deleted from MangaDex.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RemovedPolicy {
+    /// Keep the chapter, it is marked as [`removed`](SyncedChapter::removed) in the state.
+    #[default]
+    Keep,
+    /// Delete the chapter.
+    Delete,
+}
+
+/// A chapter downloaded by a sync.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncedChapter {
+    /// Version of the downloaded upload.
+    pub version: u32,
+    /// Chapter directory, relative to the library root.
+    pub path: PathBuf,
+    /// The chapter was deleted from MangaDex, but kept locally.
+    #[serde(default)]
+    pub removed: bool,
+}
+
+/// A manga synced at least once.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SyncedManga {
+    pub title: Option<String>,
+    pub followed: bool,
+    /// Start of the last sync that got every chapter of the manga.
+    ///
+    /// The whole manga feed is read again while it is unset.
+    pub last_sync: Option<MangaDexDateTime>,
+    pub chapters: BTreeMap<Uuid, SyncedChapter>,
+    /// Chapters seen before they were readable, checked again on each sync.
+    #[serde(default)]
+    pub scheduled: BTreeSet<Uuid>,
+}
+
+/// How a chapter differs from the [`SyncState`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChapterChange {
+    /// The chapter was never downloaded.
+    New,
+    /// The chapter was re-uploaded, or deleted and restored, since it was downloaded.
+    Updated,
+    Unchanged,
+}
+
+/// The synced manga and chapters, stored as JSON at the library root.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SyncState {
+    pub manga: BTreeMap<Uuid, SyncedManga>,
+}
+
+impl SyncState {
+    /// Read the state at `path`, an empty state if there is none.
+    pub async fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let content = match tokio::fs::read(path).await {
+            Ok(d) => d,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(Error::Io(e)),
+        };
+        match serde_json::from_slice(&content) {
+            Ok(d) => Ok(d),
+            Err(e) => Err(Error::ParseError(e.to_string())),
+        }
+    }
+    /// Write the state to `path`, replacing the previous one atomically.
+    pub async fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let content = match serde_json::to_vec_pretty(self) {
+            Ok(d) => d,
+            Err(e) => return Err(Error::ParseError(e.to_string())),
+        };
+        let mut file = AtomicFile::create(path).await?;
+        if let Err(e) = file.file().write_all(&content).await {
+            file.discard().await;
+            return Err(Error::Io(e));
+        }
+        file.persist().await?;
+        Ok(())
+    }
+    pub fn chapter(&self, manga_id: &Uuid, chapter_id: &Uuid) -> Option<&SyncedChapter> {
+        self.manga.get(manga_id)?.chapters.get(chapter_id)
+    }
+    /// Compare `chapter` of `manga_id` with its downloaded version.
+    pub fn change(&self, manga_id: &Uuid, chapter: &ChapterObject) -> ChapterChange {
+        match self.chapter(manga_id, &chapter.id) {
+            None => ChapterChange::New,
+            Some(synced) if synced.removed || synced.version != chapter.attributes.version => {
+                ChapterChange::Updated
+            }
+            Some(_) => ChapterChange::Unchanged,
+        }
+    }
+    /// The synced manga that are missing from `followed`.
+    pub fn unfollowed(&self, followed: &HashSet<Uuid>) -> Vec<Uuid> {
+        self.manga
+            .iter()
+            .filter(|(id, manga)| manga.followed && !followed.contains(*id))
+            .map(|(id, _)| *id)
+            .collect()
+    }
+}
+
+/// Changes found by [`LibrarySync::plan`].
+#[derive(Debug, Clone, Default)]
+pub struct SyncPlan {
+    /// The followed manga.
+    pub manga: Vec<ApiObject<MangaAttributes>>,
+    /// Chapters never downloaded.
+    pub new: Vec<ChapterObject>,
+    /// Chapters re-uploaded since they were downloaded.
+    pub updated: Vec<ChapterObject>,
+    /// Downloaded chapters deleted from MangaDex, as `(manga_id, chapter_id)`.
+    pub removed: Vec<(Uuid, Uuid)>,
+    /// Chapters not readable yet, as `(manga_id, chapter_id)`.
+    pub scheduled: Vec<(Uuid, Uuid)>,
+    /// Synced manga no longer followed.
+    pub unfollowed: Vec<Uuid>,
+    /// Directory of each new and updated chapter, relative to the library root.
+    ///
+    /// The chapter id is appended to the paths already taken by another chapter.
+    pub paths: HashMap<Uuid, PathBuf>,
+}
+
+/// Outcome of [`LibrarySync::sync`].
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    /// Chapters downloaded for the first time.
+    pub downloaded: Vec<Uuid>,
+    /// Chapters downloaded again after a re-upload.
+    pub updated: Vec<Uuid>,
+    /// Chapters deleted from MangaDex, whatever the [`RemovedPolicy`].
+    pub removed: Vec<Uuid>,
+    /// Manga no longer followed, whatever the [`UnfollowedPolicy`].
+    pub unfollowed: Vec<Uuid>,
+    /// Chapters that couldn't be downloaded or deleted, they are retried on the next sync.
+    pub failed: Vec<(Uuid, Error)>,
+}
+
+fn manga_of(chapter: &ChapterObject) -> Option<Uuid> {
+    chapter
+        .relationships
+        .iter()
+        .find(|relationship| relationship.type_ == RelationshipType::Manga)
+        .map(|relationship| relationship.id)
+}
+
+/// Append the chapter `id` to the last component of `path`.
+fn path_with_id(path: &Path, id: Uuid) -> PathBuf {
+    match path.file_name() {
+        Some(name) => {
+            let mut name = name.to_os_string();
+            name.push(format!(" [{id}]"));
+            path.with_file_name(name)
+        }
+        None => path.join(id.to_string()),
+    }
+}
+
+/// When the chapter was last changed on MangaDex.
+fn changed_at(chapter: &ChapterObject) -> &OffsetDateTime {
+    chapter
+        .attributes
+        .updated_at
+        .as_ref()
+        .unwrap_or(&chapter.attributes.created_at)
+        .as_ref()
+}
+
+#[derive(Clone, Builder)]
+#[builder(setter(into, strip_option), pattern = "owned")]
+#[non_exhaustive]
+pub struct LibrarySync {
+    #[doc(hidden)]
+    #[builder(pattern = "immutable")]
+    http_client: HttpClientRef,
+    library: Library,
+    /// Path of the [`SyncState`] file.
+    ///
+    /// Defaults to [`SYNC_STATE_FILE_NAME`] at the library root.
+    #[builder(default)]
+    state_path: Option<PathBuf>,
+    /// Chapter languages, also used to pick the manga titles.
+    ///
+    /// Every language is synced if empty.
+    #[builder(setter(each = "add_language"), default)]
+    languages: Vec<Language>,
+    /// Chapters of these scanlation groups are never downloaded.
+    #[builder(setter(each = "exclude_group"), default)]
+    excluded_groups: Vec<Uuid>,
+    #[builder(default)]
+    unfollowed: UnfollowedPolicy,
+    #[builder(default)]
+    removed: RemovedPolicy,
+    #[builder(default)]
+    mode: Option<DownloadMode>,
+    /// Maximum number of pages of a chapter downloaded at the same time.
+    #[builder(default)]
+    concurrency: Option<usize>,
+    /// Enable reporting at `api.mangadex.network`.
+    #[builder(default)]
+    report: Option<bool>,
+    /// Background reporter shared by every chapter.
+    #[builder(default)]
+    reporter: Option<AtHomeReporter>,
+    /// Receives the byte-level download events of every page.
+    #[builder(default)]
+    progress: Option<ProgressHandler>,
+    /// Bandwidth limiters shared by every chapter.
+    #[builder(setter(each = "bandwidth_limit"), default)]
+    bandwidth_limits: Vec<BandwidthLimiter>,
+}
+
+impl LibrarySync {
+    pub fn state_path(&self) -> PathBuf {
+        match &self.state_path {
+            Some(d) => d.clone(),
+            None => self.library.root().join(SYNC_STATE_FILE_NAME),
+        }
+    }
+    fn client(&self) -> MangaDexClient {
+        MangaDexClient::new_with_http_client_ref(self.http_client.clone())
+    }
+    /// Whether the chapter is synced, once readable.
+    fn is_wanted(&self, chapter: &ChapterObject) -> bool {
+        let attributes = &chapter.attributes;
+        // External chapters can't be downloaded
+        !attributes.is_external()
+            && (self.languages.is_empty()
+                || self
+                    .languages
+                    .contains(&attributes.translated_language.language()))
+            && !chapter.relationships.iter().any(|relationship| {
+                relationship.type_ == RelationshipType::ScanlationGroup
+                    && self.excluded_groups.contains(&relationship.id)
+            })
+    }
+    /// Fetch every manga followed by the logged-in user.
+    pub async fn followed_manga(&self) -> Result<Vec<ApiObject<MangaAttributes>>> {
+        let client = self.client();
+        let mut manga: Vec<ApiObject<MangaAttributes>> = Vec::new();
+        loop {
+            let res = client
+                .user()
+                .followed_manga()
+                .limit(FOLLOWS_LIMIT)
+                .offset(manga.len() as u32)
+                .build()
+                .map_err(request_builder_error)?
+                .send()
+                .await?;
+            let total = res.total as usize;
+            let is_empty = res.data.is_empty();
+            manga.extend(res.data);
+            if is_empty || manga.len() >= total {
+                return Ok(manga);
+            }
+        }
+    }
+    /// Fetch the whole feed of a manga, with the scanlation groups.
+    async fn manga_feed(&self, manga_id: Uuid) -> Result<Vec<ChapterObject>> {
+        let mut builder = self
+            .client()
+            .manga()
+            .feed()
+            .manga_id(manga_id)
+            .include(ReferenceExpansionResource::ScanlationGroup);
+        for language in &self.languages {
+            builder = builder.add_translated_language(*language);
+        }
+        for group in &self.excluded_groups {
+            builder = builder.excluded_group(*group);
+        }
+        whole_manga_feed(builder).await
+    }
+    /// Fetch the followed manga feed, down to the chapters changed before `since`.
+    async fn followed_feed(&self, since: &OffsetDateTime) -> Result<Vec<ChapterObject>> {
+        let client = self.client();
+        let mut chapters: Vec<ChapterObject> = Vec::new();
+        let mut offset = 0;
+        loop {
+            let mut builder = client
+                .feed()
+                .followed_manga()
+                .limit(FEED_LIMIT)
+                .offset(offset)
+                // Most recently changed first, so the walk can stop at the last sync
+                .order(MangaFeedSortOrder::UpdatedAt(OrderDirection::Descending))
+                .include(ReferenceExpansionResource::ScanlationGroup);
+            for language in &self.languages {
+                builder = builder.add_translated_language(*language);
+            }
+            for group in &self.excluded_groups {
+                builder = builder.excluded_group(*group);
+            }
+            for content_rating in CONTENT_RATINGS {
+                builder = builder.add_content_rating(content_rating);
+            }
+            let res = builder
+                .build()
+                .map_err(request_builder_error)?
+                .send()
+                .await?;
+            offset += res.data.len() as u32;
+            let is_last = res.data.is_empty() || offset >= res.total;
+            for chapter in res.data {
+                if changed_at(&chapter) < since {
+                    return Ok(chapters);
+                }
+                chapters.push(chapter);
+            }
+            if is_last {
+                return Ok(chapters);
+            }
+        }
+    }
+    /// Fetch the chapters still on MangaDex among `ids`.
+    async fn existing_chapters(&self, ids: &[Uuid]) -> Result<Vec<ChapterObject>> {
+        let client = self.client();
+        let mut chapters: Vec<ChapterObject> = Vec::new();
+        for batch in ids.chunks(CHECK_LIMIT) {
+            let mut builder = client
+                .chapter()
+                .list()
+                .chapter_ids(batch.to_vec())
+                .limit(CHECK_LIMIT as u32)
+                .include(ReferenceExpansionResource::ScanlationGroup);
+            for content_rating in CONTENT_RATINGS {
+                builder = builder.add_content_rating(content_rating);
+            }
+            let res = builder
+                .build()
+                .map_err(request_builder_error)?
+                .send()
+                .await?;
+            chapters.extend(res.data);
+        }
+        Ok(chapters)
+    }
+    /// Compare the followed manga and their chapters with `state`, without changing anything.
+    pub async fn plan(&self, state: &SyncState) -> Result<SyncPlan> {
+        let now = OffsetDateTime::now_utc();
+        let manga = self.followed_manga().await?;
+        let followed: HashSet<Uuid> = manga.iter().map(|manga| manga.id).collect();
+        let mut plan = SyncPlan {
+            unfollowed: state.unfollowed(&followed),
+            ..Default::default()
+        };
+        let mut chapters: Vec<ChapterObject> = Vec::new();
+        let mut since: Option<&OffsetDateTime> = None;
+        for manga in &manga {
+            match state
+                .manga
+                .get(&manga.id)
+                .filter(|synced| synced.followed)
+                .and_then(|synced| synced.last_sync.as_ref())
+            {
+                Some(last_sync) => {
+                    since = Some(
+                        since.map_or(last_sync.as_ref(), |since| since.min(last_sync.as_ref())),
+                    )
+                }
+                None => chapters.extend(self.manga_feed(manga.id).await?),
+            }
+        }
+        if let Some(since) = since {
+            chapters.extend(self.followed_feed(since).await?);
+        }
+        // The downloaded chapters missing from the feeds may have been deleted or re-uploaded,
+        // and the scheduled ones may have become readable without changing
+        let seen: HashSet<Uuid> = chapters.iter().map(|chapter| chapter.id).collect();
+        let unseen: Vec<(Uuid, Uuid)> = state
+            .manga
+            .iter()
+            .filter(|(id, _)| followed.contains(*id))
+            .flat_map(|(manga_id, synced)| {
+                synced
+                    .chapters
+                    .iter()
+                    .filter(|(_, chapter)| !chapter.removed)
+                    .map(|(id, _)| id)
+                    .chain(
+                        synced
+                            .scheduled
+                            .iter()
+                            .filter(|id| !synced.chapters.contains_key(*id)),
+                    )
+                    .filter(|id| !seen.contains(*id))
+                    .map(|id| (*manga_id, *id))
+            })
+            .collect();
+        let ids: Vec<Uuid> = unseen.iter().map(|(_, id)| *id).collect();
+        let existing = self.existing_chapters(&ids).await?;
+        let existing_ids: HashSet<Uuid> = existing.iter().map(|chapter| chapter.id).collect();
+        plan.removed = unseen
+            .into_iter()
+            .filter(|(manga_id, id)| {
+                !existing_ids.contains(id) && state.chapter(manga_id, id).is_some()
+            })
+            .collect();
+        chapters.extend(existing);
+
+        let mut planned: HashSet<Uuid> = HashSet::new();
+        for chapter in chapters {
+            let manga_id = match manga_of(&chapter) {
+                Some(d) if followed.contains(&d) => d,
+                _ => continue,
+            };
+            if !self.is_wanted(&chapter) || !planned.insert(chapter.id) {
+                continue;
+            }
+            if !chapter.attributes.is_readable_at(&now) {
+                plan.scheduled.push((manga_id, chapter.id));
+                continue;
+            }
+            if chapter.attributes.pages == 0 {
+                continue;
+            }
+            match state.change(&manga_id, &chapter) {
+                ChapterChange::New => plan.new.push(chapter),
+                ChapterChange::Updated => plan.updated.push(chapter),
+                ChapterChange::Unchanged => {}
+            }
+        }
+        plan.paths = self.chapter_paths(state, &manga, &plan);
+        plan.manga = manga;
+        Ok(plan)
+    }
+    /// Render the directory of the new and updated chapters of `plan`.
+    ///
+    /// The updated chapters are placed first, so that they keep their directory.
+    fn chapter_paths(
+        &self,
+        state: &SyncState,
+        manga: &[ApiObject<MangaAttributes>],
+        plan: &SyncPlan,
+    ) -> HashMap<Uuid, PathBuf> {
+        let manga: HashMap<Uuid, &ApiObject<MangaAttributes>> =
+            manga.iter().map(|manga| (manga.id, manga)).collect();
+        let mut taken: HashMap<PathBuf, Uuid> = state
+            .manga
+            .values()
+            .flat_map(|synced| synced.chapters.iter())
+            .map(|(id, chapter)| (chapter.path.clone(), *id))
+            .collect();
+        let mut paths: HashMap<Uuid, PathBuf> = HashMap::new();
+        for chapter in plan.updated.iter().chain(plan.new.iter()) {
+            let manga = match manga_of(chapter).and_then(|id| manga.get(&id)) {
+                Some(d) => *d,
+                None => continue,
+            };
+            let mut path = self.library.template().render(&PathValues::from_chapter(
+                manga,
+                chapter,
+                &self.languages,
+            ));
+            if matches!(taken.get(&path), Some(id) if *id != chapter.id) {
+                path = path_with_id(&path, chapter.id);
+            }
+            taken.insert(path.clone(), chapter.id);
+            paths.insert(chapter.id, path);
+        }
+        paths
+    }
+    /// Delete a chapter directory, with the parent directories it leaves empty.
+    async fn remove_chapter_dir(&self, relative: &Path) -> Result<()> {
+        // The state file could have been edited, never delete outside the library
+        if relative
+            .components()
+            .any(|component| !matches!(component, Component::Normal(_)))
+        {
+            return Err(Error::UnexpectedError(anyhow::Error::msg(format!(
+                "{} is not a path in the library",
+                relative.display()
+            ))));
+        }
+        let root = self.library.root();
+        let path = root.join(relative);
+        match tokio::fs::remove_dir_all(&path).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(Error::Io(e)),
+        }
+        let mut parent = path.parent();
+        while let Some(dir) = parent {
+            if dir == root || tokio::fs::remove_dir(dir).await.is_err() {
+                break;
+            }
+            parent = dir.parent();
+        }
+        Ok(())
+    }
+    async fn download_chapter(
+        &self,
+        chapter: &ChapterObject,
+        dir: &Path,
+    ) -> Result<ChapterManifest> {
+        let mut download = self
+            .client()
+            .download()
+            .chapter_object(chapter)?
+            .mode(self.mode.clone().unwrap_or_default())
+            .report(self.report.unwrap_or(false))
+            .bandwidth_limits(self.bandwidth_limits.clone());
+        if let Some(concurrency) = self.concurrency {
+            download = download.concurrency(concurrency);
+        }
+        if let Some(reporter) = self.reporter.clone() {
+            download = download.reporter(reporter);
+        }
+        if let Some(progress) = self.progress.clone() {
+            download = download.progress(progress);
+        }
+        download
+            .build()
+            .map_err(request_builder_error)?
+            .download_to_dir_resumable(dir)
+            .await
+    }
+    /// Download a new upload of a chapter downloaded to `previous`, then move it to `path`.
+    ///
+    /// The upload is downloaded next to `path` first, `previous` is only removed once it succeeded.
+    /// An interrupted update resumes from the pages already downloaded.
+    async fn replace_chapter(
+        &self,
+        chapter: &ChapterObject,
+        previous: &Path,
+        path: &Path,
+    ) -> Result<()> {
+        let root = self.library.root();
+        let mut staging = path.as_os_str().to_owned();
+        staging.push(".update");
+        let staging = root.join(staging);
+        self.download_chapter(chapter, &staging).await?;
+        // The previous upload is replaced, not merged
+        self.remove_chapter_dir(previous).await?;
+        let target = root.join(path);
+        if let Some(parent) = target.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::rename(&staging, &target).await?;
+        Ok(())
+    }
+    /// Bring the library in step with the followed manga.
+    ///
+    /// Chapters are downloaded one after another with
+    /// [`ChapterDownload::download_to_dir_resumable`](crate::utils::download::chapter::ChapterDownload::download_to_dir_resumable),
+    /// and the state is saved after each of them, so an interrupted sync loses nothing.
+    /// A chapter that fails is reported in [`SyncReport::failed`] and retried on the next sync.
+    pub async fn sync(&self) -> Result<SyncReport> {
+        let state_path = self.state_path();
+        let mut state = SyncState::load(&state_path).await?;
+        let started = OffsetDateTime::now_utc();
+        let plan = self.plan(&state).await?;
+        let mut report = SyncReport::default();
+        let mut incomplete: HashSet<Uuid> = HashSet::new();
+
+        for manga_id in plan.unfollowed {
+            let synced = match state.manga.get_mut(&manga_id) {
+                Some(d) => d,
+                None => continue,
+            };
+            match self.unfollowed {
+                UnfollowedPolicy::Keep => {
+                    synced.followed = false;
+                    // Read the whole feed again if the manga is followed again
+                    synced.last_sync = None;
+                }
+                UnfollowedPolicy::Delete => {
+                    let mut kept: BTreeMap<Uuid, SyncedChapter> = BTreeMap::new();
+                    for (chapter_id, chapter) in std::mem::take(&mut synced.chapters) {
+                        if let Err(e) = self.remove_chapter_dir(&chapter.path).await {
+                            report.failed.push((chapter_id, e));
+                            kept.insert(chapter_id, chapter);
+                        }
+                    }
+                    if kept.is_empty() {
+                        state.manga.remove(&manga_id);
+                    } else {
+                        // Forgotten once every chapter is deleted
+                        synced.chapters = kept;
+                        synced.followed = false;
+                        synced.last_sync = None;
+                    }
+                }
+            }
+            report.unfollowed.push(manga_id);
+        }
+
+        for (manga_id, chapter_id) in plan.removed {
+            let chapters = match state.manga.get_mut(&manga_id) {
+                Some(d) => &mut d.chapters,
+                None => continue,
+            };
+            match self.removed {
+                RemovedPolicy::Keep => {
+                    if let Some(chapter) = chapters.get_mut(&chapter_id) {
+                        chapter.removed = true;
+                    }
+                }
+                RemovedPolicy::Delete => {
+                    if let Some(chapter) = chapters.remove(&chapter_id) {
+                        if let Err(e) = self.remove_chapter_dir(&chapter.path).await {
+                            report.failed.push((chapter_id, e));
+                            chapters.insert(chapter_id, chapter);
+                            continue;
+                        }
+                    }
+                }
+            }
+            report.removed.push(chapter_id);
+        }
+
+        let manga: HashMap<Uuid, &ApiObject<MangaAttributes>> =
+            plan.manga.iter().map(|manga| (manga.id, manga)).collect();
+        for (id, manga) in &manga {
+            let synced = state.manga.entry(*id).or_default();
+            synced.title = localized_title(&manga.attributes, &self.languages);
+            synced.followed = true;
+            synced.scheduled.clear();
+        }
+        for (manga_id, chapter_id) in &plan.scheduled {
+            if let Some(synced) = state.manga.get_mut(manga_id) {
+                synced.scheduled.insert(*chapter_id);
+            }
+        }
+        state.save(&state_path).await?;
+
+        let updated: HashSet<Uuid> = plan.updated.iter().map(|chapter| chapter.id).collect();
+        for chapter in plan.new.iter().chain(plan.updated.iter()) {
+            let (manga_id, path) = match manga_of(chapter)
+                .and_then(|id| Some((id, plan.paths.get(&chapter.id)?.clone())))
+            {
+                Some(d) => d,
+                None => continue,
+            };
+            let result = match state.chapter(&manga_id, &chapter.id) {
+                Some(previous) => {
+                    let previous = previous.path.clone();
+                    self.replace_chapter(chapter, &previous, &path).await
+                }
+                None => self
+                    .download_chapter(chapter, &self.library.root().join(&path))
+                    .await
+                    .map(|_| ()),
+            };
+            let synced = state.manga.entry(manga_id).or_default();
+            match result {
+                Ok(_) => {
+                    synced.chapters.insert(
+                        chapter.id,
+                        SyncedChapter {
+                            version: chapter.attributes.version,
+                            path,
+                            removed: false,
+                        },
+                    );
+                    state.save(&state_path).await?;
+                    if updated.contains(&chapter.id) {
+                        report.updated.push(chapter.id);
+                    } else {
+                        report.downloaded.push(chapter.id);
+                    }
+                }
+                Err(e) => {
+                    // A failed update keeps the previous upload, and its state
+                    incomplete.insert(manga_id);
+                    report.failed.push((chapter.id, e));
+                }
+            }
+        }
+
+        for id in manga.keys().filter(|id| !incomplete.contains(*id)) {
+            if let Some(synced) = state.manga.get_mut(id) {
+                synced.last_sync = Some(started.into());
+            }
+        }
+        state.save(&state_path).await?;
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use url::Url;
+    use wiremock::matchers::{method, path, path_regex};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+    use crate::utils::library::PathTemplate;
+    use crate::v5::AuthTokens;
+    use crate::HttpClient;
+
+    fn chapter(version: u32) -> ChapterObject {
+        serde_json::from_value(json!({
+            "id": Uuid::new_v4(),
+            "type": "chapter",
+            "attributes": {
+                "title": "",
+                "volume": "1",
+                "chapter": "1",
+                "pages": 20,
+                "translatedLanguage": "en",
+                "externalUrl": null,
+                "version": version,
+                "createdAt": "2021-01-01T00:00:00+00:00",
+                "updatedAt": null,
+                "publishAt": "2021-01-01T00:00:00+00:00",
+                "readableAt": "2021-01-01T00:00:00+00:00"
+            },
+            "relationships": []
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn chapters_are_compared_by_version() {
+        let manga_id = Uuid::new_v4();
+        let downloaded = chapter(1);
+        let reuploaded = chapter(1);
+        let removed = chapter(1);
+        let mut synced = SyncedManga {
+            followed: true,
+            ..Default::default()
+        };
+        for (chapter, removed) in [(&downloaded, false), (&reuploaded, false), (&removed, true)] {
+            synced.chapters.insert(
+                chapter.id,
+                SyncedChapter {
+                    version: 1,
+                    path: PathBuf::from(chapter.id.to_string()),
+                    removed,
+                },
+            );
+        }
+        let mut state = SyncState::default();
+        state.manga.insert(manga_id, synced);
+
+        let mut reuploaded = reuploaded;
+        reuploaded.attributes.version = 2;
+        assert_eq!(
+            state.change(&manga_id, &downloaded),
+            ChapterChange::Unchanged
+        );
+        assert_eq!(state.change(&manga_id, &reuploaded), ChapterChange::Updated);
+        assert_eq!(state.change(&manga_id, &removed), ChapterChange::Updated);
+        assert_eq!(state.change(&manga_id, &chapter(1)), ChapterChange::New);
+        assert_eq!(
+            state.change(&Uuid::new_v4(), &downloaded),
+            ChapterChange::New
+        );
+    }
+
+    #[tokio::test]
+    async fn state_round_trip_and_unfollowed_manga() -> anyhow::Result<()> {
+        let followed = Uuid::new_v4();
+        let unfollowed = Uuid::new_v4();
+        let already_unfollowed = Uuid::new_v4();
+        let mut state = SyncState::default();
+        for (id, is_followed) in [
+            (followed, true),
+            (unfollowed, true),
+            (already_unfollowed, false),
+        ] {
+            state.manga.insert(
+                id,
+                SyncedManga {
+                    title: Some("Title".to_string()),
+                    followed: is_followed,
+                    last_sync: Some(OffsetDateTime::from_unix_timestamp(1_600_000_000)?.into()),
+                    chapters: BTreeMap::new(),
+                    scheduled: BTreeSet::new(),
+                },
+            );
+        }
+        assert_eq!(
+            state.unfollowed(&HashSet::from([followed])),
+            vec![unfollowed]
+        );
+
+        let dir = std::env::temp_dir().join(format!("mangadex-sync-{}", Uuid::new_v4()));
+        tokio::fs::create_dir_all(&dir).await?;
+        let path = dir.join(SYNC_STATE_FILE_NAME);
+        assert_eq!(SyncState::load(&path).await?, SyncState::default());
+        state.save(&path).await?;
+        assert_eq!(SyncState::load(&path).await?, state);
+        tokio::fs::remove_dir_all(&dir).await?;
+        Ok(())
+    }
+
+    /// A chapter of `manga_id` in the feeds, readable at `readable_at`.
+    fn feed_chapter(
+        id: Uuid,
+        manga_id: Uuid,
+        number: &str,
+        version: u32,
+        readable_at: &str,
+    ) -> serde_json::Value {
+        json!({
+            "id": id,
+            "type": "chapter",
+            "attributes": {
+                "title": "",
+                "volume": null,
+                "chapter": number,
+                "pages": 1,
+                "translatedLanguage": "en",
+                "externalUrl": null,
+                "version": version,
+                "createdAt": "2023-01-01T00:00:00+00:00",
+                "updatedAt": "2023-06-01T00:00:00+00:00",
+                "publishAt": "2023-01-01T00:00:00+00:00",
+                "readableAt": readable_at
+            },
+            "relationships": [{ "id": manga_id, "type": "manga" }]
+        })
+    }
+
+    fn collection(data: Vec<serde_json::Value>) -> ResponseTemplate {
+        let total = data.len();
+        ResponseTemplate::new(200).set_body_json(json!({
+            "result": "ok",
+            "response": "collection",
+            "data": data,
+            "limit": 100,
+            "offset": 0,
+            "total": total
+        }))
+    }
+
+    /// Serve `manga_id` as the only followed manga, and `feed` as the followed manga feed.
+    async fn mock_follows(mock_server: &MockServer, manga_id: Uuid, feed: Vec<serde_json::Value>) {
+        Mock::given(method("GET"))
+            .and(path("/user/follows/manga"))
+            .respond_with(collection(vec![json!({
+                "id": manga_id,
+                "type": "manga",
+                "attributes": {
+                    "title": { "en": "Title" },
+                    "altTitles": [],
+                    "description": {},
+                    "isLocked": false,
+                    "links": null,
+                    "originalLanguage": "ja",
+                    "lastVolume": null,
+                    "lastChapter": null,
+                    "publicationDemographic": null,
+                    "status": "ongoing",
+                    "year": null,
+                    "contentRating": "safe",
+                    "chapterNumbersResetOnNewVolume": false,
+                    "latestUploadedChapter": null,
+                    "availableTranslatedLanguages": [],
+                    "tags": [],
+                    "state": "published",
+                    "createdAt": "2023-01-01T00:00:00+00:00",
+                    "updatedAt": null,
+                    "version": 1
+                },
+                "relationships": []
+            })]))
+            .mount(mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/user/follows/manga/feed"))
+            .respond_with(collection(feed))
+            .mount(mock_server)
+            .await;
+    }
+
+    /// Serve a single page for the chapter `id`, or a server error if `page` is `None`.
+    async fn mock_download(mock_server: &MockServer, id: Uuid, page: Option<&[u8]>) {
+        Mock::given(method("GET"))
+            .and(path(format!("/at-home/server/{id}")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "result": "ok",
+                "baseUrl": mock_server.uri(),
+                "chapter": { "hash": id, "data": ["1.png"], "dataSaver": ["1.png"] }
+            })))
+            .mount(mock_server)
+            .await;
+        let response = match page {
+            Some(page) => ResponseTemplate::new(200).set_body_bytes(page),
+            None => ResponseTemplate::new(500),
+        };
+        Mock::given(method("GET"))
+            .and(path(format!("/data/{id}/1.png")))
+            .respond_with(response)
+            .mount(mock_server)
+            .await;
+    }
+
+    fn library_sync(mock_server: &MockServer, root: &Path) -> anyhow::Result<LibrarySync> {
+        let http_client = HttpClient::builder()
+            .base_url(Url::parse(&mock_server.uri())?)
+            .auth_tokens(AuthTokens {
+                session: "sessiontoken".to_string(),
+                refresh: "refreshtoken".to_string(),
+            })
+            .build()?;
+        Ok(MangaDexClient::new_with_http_client(http_client)
+            .download()
+            .library_sync(Library::new(root, PathTemplate::parse("Ch. {chapter}")?))
+            .removed(RemovedPolicy::Delete)
+            .mode(DownloadMode::Normal)
+            .build()?)
+    }
+
+    /// A state with the chapters `(id, path)` of `manga_id` downloaded at version 1.
+    fn synced_state(manga_id: Uuid, chapters: &[(Uuid, &str)], scheduled: &[Uuid]) -> SyncState {
+        let mut synced = SyncedManga {
+            title: Some("Title".to_string()),
+            followed: true,
+            last_sync: Some(
+                OffsetDateTime::from_unix_timestamp(1_672_531_200)
+                    .unwrap()
+                    .into(),
+            ),
+            scheduled: scheduled.iter().copied().collect(),
+            ..Default::default()
+        };
+        for (id, path) in chapters {
+            synced.chapters.insert(
+                *id,
+                SyncedChapter {
+                    version: 1,
+                    path: PathBuf::from(path),
+                    removed: false,
+                },
+            );
+        }
+        let mut state = SyncState::default();
+        state.manga.insert(manga_id, synced);
+        state
+    }
+
+    #[tokio::test]
+    async fn sync_downloads_new_updated_and_scheduled_chapters() -> anyhow::Result<()> {
+        let mock_server = MockServer::start().await;
+        let root = std::env::temp_dir().join(format!("mangadex-sync-{}", Uuid::new_v4()));
+        let manga_id = Uuid::new_v4();
+        let [updated, deleted, scheduled, published, new, failed] = [(); 6].map(|_| Uuid::new_v4());
+        let readable = "2023-01-01T00:00:00+00:00";
+        mock_follows(
+            &mock_server,
+            manga_id,
+            vec![
+                feed_chapter(updated, manga_id, "1", 2, readable),
+                feed_chapter(new, manga_id, "5", 1, readable),
+                feed_chapter(failed, manga_id, "6", 1, readable),
+                feed_chapter(scheduled, manga_id, "7", 1, "2999-01-01T00:00:00+00:00"),
+            ],
+        )
+        .await;
+        // The deleted chapter is missing, the scheduled one became readable without changing
+        Mock::given(method("GET"))
+            .and(path("/chapter"))
+            .respond_with(collection(vec![feed_chapter(
+                published, manga_id, "3", 1, readable,
+            )]))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+        for id in [updated, published, new] {
+            mock_download(&mock_server, id, Some(b"page")).await;
+        }
+        mock_download(&mock_server, failed, None).await;
+        Mock::given(method("GET"))
+            .and(path_regex(r"^/at-home/server/"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+
+        for dir in ["Ch. 1", "Ch. 2"] {
+            tokio::fs::create_dir_all(root.join(dir)).await?;
+            tokio::fs::write(root.join(dir).join("1.png"), b"old page").await?;
+        }
+        synced_state(
+            manga_id,
+            &[(updated, "Ch. 1"), (deleted, "Ch. 2")],
+            &[published],
+        )
+        .save(root.join(SYNC_STATE_FILE_NAME))
+        .await?;
+        let sync = library_sync(&mock_server, &root)?;
+
+        let plan = sync
+            .plan(&SyncState::load(sync.state_path()).await?)
+            .await?;
+        let ids = |chapters: &[ChapterObject]| -> Vec<Uuid> {
+            chapters.iter().map(|chapter| chapter.id).collect()
+        };
+        assert_eq!(ids(&plan.new), [new, failed, published]);
+        assert_eq!(ids(&plan.updated), [updated]);
+        assert_eq!(plan.removed, [(manga_id, deleted)]);
+        assert_eq!(plan.scheduled, [(manga_id, scheduled)]);
+
+        let report = sync.sync().await?;
+        assert_eq!(report.downloaded, [new, published]);
+        assert_eq!(report.updated, [updated]);
+        assert_eq!(report.removed, [deleted]);
+        let failures: Vec<Uuid> = report.failed.iter().map(|(id, _)| *id).collect();
+        assert_eq!(failures, [failed]);
+
+        let state = SyncState::load(sync.state_path()).await?;
+        let synced = &state.manga[&manga_id];
+        let chapters: Vec<(Uuid, u32, &Path)> = [updated, published, new]
+            .into_iter()
+            .filter_map(|id| {
+                let chapter = synced.chapters.get(&id)?;
+                Some((id, chapter.version, chapter.path.as_path()))
+            })
+            .collect();
+        assert_eq!(
+            chapters,
+            [
+                (updated, 2, Path::new("Ch. 1")),
+                (published, 1, Path::new("Ch. 3")),
+                (new, 1, Path::new("Ch. 5"))
+            ]
+        );
+        assert_eq!(synced.chapters.len(), 3);
+        assert_eq!(synced.scheduled, BTreeSet::from([scheduled]));
+        // The failed chapter is read from the feed again on the next sync
+        assert_eq!(
+            synced.last_sync,
+            synced_state(manga_id, &[], &[]).manga[&manga_id].last_sync
+        );
+        assert_eq!(tokio::fs::read(root.join("Ch. 1/1.png")).await?, b"page");
+        assert!(!root.join("Ch. 1.update").exists());
+        assert!(!root.join("Ch. 2").exists());
+
+        tokio::fs::remove_dir_all(&root).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn chapters_rendered_to_the_same_path_get_their_id_appended() -> anyhow::Result<()> {
+        let mock_server = MockServer::start().await;
+        let root = std::env::temp_dir().join(format!("mangadex-sync-{}", Uuid::new_v4()));
+        let manga_id = Uuid::new_v4();
+        let [synced, retranslated, first, second] = [(); 4].map(|_| Uuid::new_v4());
+        let readable = "2023-01-01T00:00:00+00:00";
+        let by_group = |id: Uuid, number: &str| {
+            let mut chapter = feed_chapter(id, manga_id, number, 1, readable);
+            chapter["relationships"]
+                .as_array_mut()
+                .unwrap()
+                .push(json!({ "id": Uuid::new_v4(), "type": "scanlation_group" }));
+            chapter
+        };
+        mock_follows(
+            &mock_server,
+            manga_id,
+            vec![
+                by_group(retranslated, "1"),
+                by_group(first, "4"),
+                by_group(second, "4"),
+            ],
+        )
+        .await;
+        Mock::given(method("GET"))
+            .and(path("/chapter"))
+            .respond_with(collection(vec![by_group(synced, "1")]))
+            .mount(&mock_server)
+            .await;
+        for id in [retranslated, first, second] {
+            mock_download(&mock_server, id, Some(b"page")).await;
+        }
+
+        tokio::fs::create_dir_all(root.join("Ch. 1")).await?;
+        tokio::fs::write(root.join("Ch. 1/1.png"), b"synced page").await?;
+        synced_state(manga_id, &[(synced, "Ch. 1")], &[])
+            .save(root.join(SYNC_STATE_FILE_NAME))
+            .await?;
+        let sync = library_sync(&mock_server, &root)?;
+
+        let plan = sync
+            .plan(&SyncState::load(sync.state_path()).await?)
+            .await?;
+        assert_eq!(
+            plan.paths,
+            HashMap::from([
+                (
+                    retranslated,
+                    PathBuf::from(format!("Ch. 1 [{retranslated}]"))
+                ),
+                (first, PathBuf::from("Ch. 4")),
+                (second, PathBuf::from(format!("Ch. 4 [{second}]"))),
+            ])
+        );
+
+        let report = sync.sync().await?;
+        assert_eq!(report.downloaded, [retranslated, first, second]);
+        let state = SyncState::load(sync.state_path()).await?;
+        for (id, path) in &plan.paths {
+            assert_eq!(&state.manga[&manga_id].chapters[id].path, path);
+            assert_eq!(
+                tokio::fs::read(root.join(path).join("1.png")).await?,
+                b"page"
+            );
+        }
+        assert_eq!(
+            tokio::fs::read(root.join("Ch. 1/1.png")).await?,
+            b"synced page"
+        );
+
+        tokio::fs::remove_dir_all(&root).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn failed_updates_keep_the_previous_upload() -> anyhow::Result<()> {
+        let mock_server = MockServer::start().await;
+        let root = std::env::temp_dir().join(format!("mangadex-sync-{}", Uuid::new_v4()));
+        let manga_id = Uuid::new_v4();
+        let chapter_id = Uuid::new_v4();
+        mock_follows(
+            &mock_server,
+            manga_id,
+            vec![feed_chapter(
+                chapter_id,
+                manga_id,
+                "1",
+                2,
+                "2023-01-01T00:00:00+00:00",
+            )],
+        )
+        .await;
+        mock_download(&mock_server, chapter_id, None).await;
+        tokio::fs::create_dir_all(root.join("Ch. 1")).await?;
+        tokio::fs::write(root.join("Ch. 1/1.png"), b"old page").await?;
+        let state = synced_state(manga_id, &[(chapter_id, "Ch. 1")], &[]);
+        let sync = library_sync(&mock_server, &root)?;
+        state.save(sync.state_path()).await?;
+
+        let report = sync.sync().await?;
+        assert!(report.updated.is_empty());
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(SyncState::load(sync.state_path()).await?, state);
+        assert_eq!(
+            tokio::fs::read(root.join("Ch. 1/1.png")).await?,
+            b"old page"
+        );
+
+        tokio::fs::remove_dir_all(&root).await?;
+        Ok(())
+    }
+}
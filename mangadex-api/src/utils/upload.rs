@@ -0,0 +1,1238 @@
+//! Upload a chapter in one go.
+//!
+//! [`ChapterUpload`] starts an upload session, uploads the pages in batches of
+//! [`MAX_FILES_PER_REQUEST`] files, commits the session with the pages in order
+//! and returns the new chapter.
+//! The session is abandoned if anything fails, so no half-uploaded session is left behind.
+//! If the process stops mid-upload, [`ChapterUpload::resume`] picks the session up where it was left,
+//! and the [`progress`] of each page can be followed.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use mangadex_api::utils::upload::PageSource;
+//! use mangadex_api::MangaDexClient;
+//! use mangadex_api_types::Language;
+//! use uuid::Uuid;
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let client = MangaDexClient::default();
+//! // Log in first, uploading requires authentication
+//!
+//! let chapter = client
+//!     .upload()
+//!     .chapter()
+//!     .manga_id(Uuid::new_v4())
+//!     .add_group(Uuid::new_v4())
+//!     .volume("1")
+//!     .chapter("1")
+//!     .title("The beginning")
+//!     .translated_language(Language::English)
+//!     .add_page(PageSource::file("./chapter/01.png"))
+//!     .add_page(PageSource::file("./chapter/02.png"))
+//!     .build()?
+//!     .upload()
+//!     .await?;
+//! println!("uploaded {}", chapter.id);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The pages and metadata can also be read from a directory of images or a CBZ archive,
+//! the volume, chapter and title come from its `ComicInfo.xml` or its name:
+//!
+//! ```rust,no_run
+//! use mangadex_api::utils::upload::ChapterSource;
+//! use mangadex_api::MangaDexClient;
+//! use mangadex_api_types::Language;
+//! use uuid::Uuid;
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let client = MangaDexClient::default();
+//!
+//! let source = ChapterSource::open("./Vol. 1 Ch. 2 - The Return.cbz").await?;
+//! for warning in &source.warnings {
+//!     eprintln!("{warning}");
+//! }
+//! let chapter = client
+//!     .upload()
+//!     .chapter()
+//!     .manga_id(Uuid::new_v4())
+//!     .translated_language(Language::English)
+//!     .source(source)
+//!     // Overrides the title of the archive
+//!     .title("Homecoming")
+//!     .build()?
+//!     .upload()
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use bytes::Bytes;
+use derive_builder::Builder;
+use mangadex_api_schema::v5::error::MangaDexError;
+use mangadex_api_schema::v5::{ChapterObject, UploadSessionFileAttributes, UploadSessionResponse};
+use mangadex_api_types::error::{schema, Error, Result};
+use mangadex_api_types::{Language, MangaDexDateTime, RelationshipType};
+use md5::Md5;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::utils::request_builder_error;
+use crate::{HttpClientRef, MangaDexClient};
+
+use progress::{UploadEventKind, UploadProgressHandler, UploadTarget};
+
+mod source;
+pub use source::{natural_cmp, ChapterMetadata, ChapterSource, COMIC_INFO_FILE_NAME};
+mod edit;
+pub mod progress;
+pub use edit::{ChapterEdit, ChapterEditBuilder, EditPage, EditSession};
+mod validate;
+pub use validate::{ImageProblem, ImageReport, SessionProblem, UploadLimits, ValidationReport};
+
+/// Maximum number of files the upload endpoint accepts per request.
+pub const MAX_FILES_PER_REQUEST: usize = 10;
+
+/// Delay before the first retry of a file, doubled on each retry.
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// A page to upload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PageSource {
+    /// An image file, read when the pages are validated.
+    File(PathBuf),
+    /// An image in memory.
+    Bytes { file_name: String, bytes: Bytes },
+}
+
+impl PageSource {
+    pub fn file<P: Into<PathBuf>>(path: P) -> Self {
+        Self::File(path.into())
+    }
+    pub fn bytes<N: Into<String>, B: Into<Bytes>>(file_name: N, bytes: B) -> Self {
+        Self::Bytes {
+            file_name: file_name.into(),
+            bytes: bytes.into(),
+        }
+    }
+    pub fn file_name(&self) -> String {
+        match self {
+            Self::File(path) => path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            Self::Bytes { file_name, .. } => file_name.clone(),
+        }
+    }
+    /// Read the image.
+    pub async fn read(&self) -> Result<Bytes> {
+        match self {
+            Self::File(path) => Ok(Bytes::from(tokio::fs::read(path).await?)),
+            Self::Bytes { bytes, .. } => Ok(bytes.clone()),
+        }
+    }
+}
+
+impl From<PathBuf> for PageSource {
+    fn from(value: PathBuf) -> Self {
+        Self::File(value)
+    }
+}
+
+impl From<&Path> for PageSource {
+    fn from(value: &Path) -> Self {
+        Self::File(value.to_path_buf())
+    }
+}
+
+/// Turn the errors of the rejected files into an [`Error::Api`].
+fn files_error(errors: Vec<MangaDexError>) -> Error {
+    Error::Api(schema::MangaDexErrorResponse {
+        errors: errors
+            .into_iter()
+            .map(|error| schema::MangaDexError {
+                id: error.id,
+                status: error.status,
+                title: error.title,
+                detail: error.detail,
+                context: error.context,
+            })
+            .collect(),
+    })
+}
+
+/// Check if a failed file upload is worth retrying.
+///
+/// This is the case for network errors and `5xx` responses, other errors come from the file itself.
+fn is_retryable(error: &Error) -> bool {
+    match error {
+        Error::RequestError(e) => !e.is_builder() && !e.is_decode(),
+        Error::ServerError(status, _) => *status >= 500,
+        Error::Api(response) => response.errors.iter().all(|error| error.status >= 500),
+        _ => false,
+    }
+}
+
+/// Get the open upload session, if any.
+///
+/// Without an open session, the server answers with a `404` or an empty body.
+/// Committed and deleted sessions don't count as open.
+async fn get_open_session(http_client: &HttpClientRef) -> Result<Option<UploadSessionResponse>> {
+    let endpoint = MangaDexClient::new_with_http_client_ref(http_client.clone())
+        .upload()
+        .get_session()
+        .build()
+        .map_err(request_builder_error)?;
+    #[cfg(not(feature = "multi-thread"))]
+    let res = http_client
+        .try_borrow()?
+        .send_request_without_deserializing(&endpoint)
+        .await?;
+    #[cfg(feature = "multi-thread")]
+    let res = http_client
+        .lock()
+        .await
+        .send_request_without_deserializing(&endpoint)
+        .await?;
+
+    let status = res.status();
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    let body = res.text().await?;
+    if !status.is_success() {
+        return Err(Error::ServerError(status.as_u16(), body));
+    }
+    if body.trim().is_empty() {
+        return Ok(None);
+    }
+    let session: UploadSessionResponse =
+        serde_json::from_str(&body).map_err(|e| Error::ParseError(e.to_string()))?;
+    if session.attributes.is_committed || session.attributes.is_deleted {
+        return Ok(None);
+    }
+    Ok(Some(session))
+}
+
+/// Abandon the open upload session, if any.
+async fn abandon_open_session(http_client: &HttpClientRef) -> Result<()> {
+    let session = match get_open_session(http_client).await? {
+        Some(session) => session,
+        None => return Ok(()),
+    };
+    MangaDexClient::new_with_http_client_ref(http_client.clone())
+        .upload()
+        .abandon_session()
+        .session_id(session.id)
+        .build()
+        .map_err(request_builder_error)?
+        .send()
+        .await
+}
+
+/// Read the pages one at a time and check them against `limits`.
+async fn validate_pages<'a, I>(pages: I, limits: &UploadLimits) -> Result<ValidationReport>
+where
+    I: IntoIterator<Item = &'a PageSource>,
+{
+    let mut images = Vec::new();
+    for (index, page) in pages.into_iter().enumerate() {
+        let bytes = page.read().await?;
+        images.push(ImageReport::new(index, page.file_name(), &bytes, limits));
+    }
+    Ok(ValidationReport::from_images(images, limits))
+}
+
+/// Read the pages, each with its page index, and check them against `limits`.
+///
+/// Each page is read once and kept for the upload, an invalid [`ValidationReport`]
+/// is returned as an [`Error::InvalidUpload`].
+async fn read_pages<'a, I>(pages: I, limits: &UploadLimits) -> Result<Vec<PageFile>>
+where
+    I: IntoIterator<Item = (usize, &'a PageSource)>,
+{
+    let mut files = Vec::new();
+    let mut images = Vec::new();
+    for (index, page) in pages {
+        let file = PageFile {
+            index,
+            name: page.file_name(),
+            bytes: page.read().await?,
+        };
+        images.push(ImageReport::new(
+            images.len(),
+            file.name.clone(),
+            &file.bytes,
+            limits,
+        ));
+        files.push(file);
+    }
+    let report = ValidationReport::from_images(images, limits);
+    if !report.is_valid() {
+        return Err(Error::InvalidUpload(Box::new(report)));
+    }
+    Ok(files)
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Digests of a page, to find it among the files of an upload session.
+struct PageDigests {
+    size: u64,
+    md5: String,
+    sha256: String,
+}
+
+impl PageDigests {
+    fn new(bytes: &[u8]) -> Self {
+        Self {
+            size: bytes.len() as u64,
+            md5: hex(&Md5::digest(bytes)),
+            sha256: hex(&Sha256::digest(bytes)),
+        }
+    }
+    /// Check the page against an upload session file, whose `fileHash` is an MD5 or SHA-256 digest.
+    fn matches(&self, file: &UploadSessionFileAttributes) -> bool {
+        file.file_size == self.size
+            && (file.file_hash.eq_ignore_ascii_case(&self.md5)
+                || file.file_hash.eq_ignore_ascii_case(&self.sha256))
+    }
+}
+
+/// A page read for upload.
+#[derive(Clone)]
+struct PageFile {
+    /// Index of the page, for the progress events.
+    index: usize,
+    name: String,
+    bytes: Bytes,
+}
+
+/// The files of an open upload session.
+#[derive(Clone)]
+struct SessionFiles {
+    http_client: HttpClientRef,
+    session_id: Uuid,
+    /// Number of times a file is retried, see [`is_retryable`].
+    retries: usize,
+    progress: Option<UploadProgressHandler>,
+}
+
+impl SessionFiles {
+    fn client(&self) -> MangaDexClient {
+        MangaDexClient::new_with_http_client_ref(self.http_client.clone())
+    }
+    fn target(&self, file: &PageFile) -> UploadTarget {
+        UploadTarget {
+            session_id: self.session_id,
+            index: file.index,
+            file_name: file.name.clone(),
+        }
+    }
+    async fn abandon(&self) -> Result<()> {
+        self.client()
+            .upload()
+            .abandon_session()
+            .session_id(self.session_id)
+            .build()
+            .map_err(request_builder_error)?
+            .send()
+            .await
+    }
+    /// Upload `files` in a single request, returns the upload session file ids in order.
+    ///
+    /// Fails if any file was rejected.
+    async fn upload_files(&self, files: &[PageFile]) -> Result<Vec<Uuid>> {
+        let mut builder = self
+            .client()
+            .upload()
+            .upload_images()
+            .session_id(self.session_id)
+            .files(
+                files
+                    .iter()
+                    .map(|file| Cow::Owned(file.bytes.to_vec()))
+                    .collect::<Vec<Cow<'static, [u8]>>>(),
+            )
+            .file_names(
+                files
+                    .iter()
+                    .map(|file| file.name.clone())
+                    .collect::<Vec<_>>(),
+            );
+        if let Some(progress) = &self.progress {
+            // The events are about the pages, not the position in the request
+            let progress = progress.clone();
+            let indices: Vec<usize> = files.iter().map(|file| file.index).collect();
+            builder = builder.progress(UploadProgressHandler::new(move |mut event| {
+                event.target.index = indices[event.target.index];
+                progress.emit(event)
+            }));
+        }
+        let res = builder
+            .build()
+            .map_err(request_builder_error)?
+            .send()
+            .await?;
+        if !res.errors.is_empty() || res.data.len() != files.len() {
+            // Some files were accepted, but they can't be told apart
+            let uploaded: Vec<Uuid> = res.data.iter().map(|file| file.id).collect();
+            if !uploaded.is_empty() {
+                self.delete_files(uploaded).await?;
+            }
+            if res.errors.is_empty() {
+                return Err(Error::UnexpectedError(anyhow::Error::msg(format!(
+                    "{} of {} files were uploaded",
+                    res.data.len(),
+                    files.len()
+                ))));
+            }
+            return Err(files_error(res.errors));
+        }
+        let ids: Vec<Uuid> = res.data.into_iter().map(|file| file.id).collect();
+        if let Some(progress) = &self.progress {
+            for (file, id) in files.iter().zip(&ids) {
+                progress.emit_kind(
+                    self.target(file),
+                    UploadEventKind::Finished {
+                        session_file_id: *id,
+                    },
+                );
+            }
+        }
+        Ok(ids)
+    }
+    async fn delete_files(&self, files: Vec<Uuid>) -> Result<()> {
+        self.client()
+            .upload()
+            .delete_images()
+            .session_id(self.session_id)
+            .session_file_ids(files)
+            .build()
+            .map_err(request_builder_error)?
+            .send()
+            .await?;
+        Ok(())
+    }
+    /// Upload a single file, retrying it as configured.
+    async fn upload_file(&self, file: PageFile) -> Result<Uuid> {
+        let file = [file];
+        let mut delay = RETRY_DELAY;
+        let mut attempt = 0;
+        loop {
+            match self.upload_files(&file).await {
+                Ok(ids) => return Ok(ids[0]),
+                Err(e) if attempt < self.retries && is_retryable(&e) => {
+                    attempt += 1;
+                    if let Some(progress) = &self.progress {
+                        progress.retried(self.target(&file[0]), attempt, &e);
+                    }
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+                Err(e) => {
+                    if let Some(progress) = &self.progress {
+                        progress.failed(self.target(&file[0]), &e);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+    }
+    /// Upload the pages, returns the upload session file ids in order.
+    ///
+    /// Each batch is sent in a single request first.
+    /// If the request fails, the files of the batch are uploaded again one by one, with retries.
+    async fn upload_pages(&self, pages: &[PageFile]) -> Result<Vec<Uuid>> {
+        let mut page_order: Vec<Uuid> = Vec::with_capacity(pages.len());
+        for batch in pages.chunks(MAX_FILES_PER_REQUEST) {
+            match self.upload_files(batch).await {
+                Ok(ids) => page_order.extend(ids),
+                Err(e) if batch.len() > 1 || is_retryable(&e) => {
+                    for file in batch {
+                        page_order.push(self.upload_file(file.clone()).await?);
+                    }
+                }
+                Err(e) => {
+                    if let Some(progress) = &self.progress {
+                        progress.failed(self.target(&batch[0]), &e);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Ok(page_order)
+    }
+}
+
+#[derive(Clone, Builder)]
+#[builder(setter(into, strip_option), pattern = "owned")]
+#[non_exhaustive]
+pub struct ChapterUpload {
+    #[doc(hidden)]
+    #[builder(pattern = "immutable")]
+    http_client: HttpClientRef,
+    manga_id: Uuid,
+    /// Scanlation groups credited for the chapter, none for no group.
+    #[builder(setter(each = "add_group"), default)]
+    groups: Vec<Uuid>,
+    #[builder(default)]
+    volume: Option<String>,
+    #[builder(default)]
+    chapter: Option<String>,
+    #[builder(default)]
+    title: Option<String>,
+    translated_language: Language,
+    /// Publish the chapter later, at this date.
+    #[builder(default)]
+    publish_at: Option<MangaDexDateTime>,
+    /// Pages in reading order.
+    #[builder(setter(each = "add_page"), default)]
+    pages: Vec<PageSource>,
+    /// Number of times a file is retried after a network error or a `5xx` response.
+    ///
+    /// Defaults to `3`.
+    #[builder(default = "3")]
+    retries: usize,
+    /// Abandon the upload session left over by a previous upload, only one session can be open at a time.
+    ///
+    /// Defaults to `true`.
+    #[builder(default = "true")]
+    abandon_stale_session: bool,
+    /// Limits the pages are checked against before the upload session is started.
+    ///
+    /// Defaults to the MangaDex limits.
+    #[builder(default)]
+    limits: UploadLimits,
+    /// Receives the progress of each page.
+    #[builder(default)]
+    progress: Option<UploadProgressHandler>,
+}
+
+impl ChapterUpload {
+    fn session_files(&self, session_id: Uuid) -> SessionFiles {
+        SessionFiles {
+            http_client: self.http_client.clone(),
+            session_id,
+            retries: self.retries,
+            progress: self.progress.clone(),
+        }
+    }
+    async fn start_session(&self) -> Result<SessionFiles> {
+        if self.abandon_stale_session {
+            abandon_open_session(&self.http_client).await?;
+        }
+        let session = MangaDexClient::new_with_http_client_ref(self.http_client.clone())
+            .upload()
+            .start_session()
+            .manga_id(self.manga_id)
+            .groups(self.groups.clone())
+            .build()
+            .map_err(request_builder_error)?
+            .send()
+            .await?;
+        Ok(self.session_files(session.id))
+    }
+    /// Get the open upload session for the manga and groups, if any.
+    async fn open_session(&self) -> Result<Option<UploadSessionResponse>> {
+        let session = match get_open_session(&self.http_client).await? {
+            Some(session) => session,
+            None => return Ok(None),
+        };
+        let other_manga = session.relationships.iter().any(|relationship| {
+            relationship.type_ == RelationshipType::Manga && relationship.id != self.manga_id
+        });
+        let groups: BTreeSet<Uuid> = session
+            .relationships
+            .iter()
+            .filter(|relationship| relationship.type_ == RelationshipType::ScanlationGroup)
+            .map(|relationship| relationship.id)
+            .collect();
+        if other_manga || groups != self.groups.iter().copied().collect() {
+            return Ok(None);
+        }
+        Ok(Some(session))
+    }
+    /// Match the files of `session` with the pages, returns the upload session file id of each page
+    /// already uploaded.
+    ///
+    /// The files matching no page are deleted.
+    async fn resumed_pages(
+        &self,
+        session: &UploadSessionResponse,
+        files: &SessionFiles,
+        pages: &[PageFile],
+    ) -> Result<Vec<Option<Uuid>>> {
+        let mut uploaded: Vec<(Uuid, UploadSessionFileAttributes)> =
+            EditPage::from_session(session)
+                .into_iter()
+                .filter_map(|file| match file {
+                    EditPage::Existing { id, attributes } => Some((id, attributes)),
+                    EditPage::New(_) => None,
+                })
+                .collect();
+        let mut resumed = Vec::with_capacity(pages.len());
+        for page in pages {
+            let digests = PageDigests::new(&page.bytes);
+            let position = uploaded
+                .iter()
+                .position(|(_, attributes)| digests.matches(attributes));
+            let id = position.map(|position| uploaded.remove(position).0);
+            if let (Some(id), Some(progress)) = (id, &self.progress) {
+                progress.emit_kind(
+                    files.target(page),
+                    UploadEventKind::Resumed {
+                        session_file_id: id,
+                    },
+                );
+            }
+            resumed.push(id);
+        }
+        if !uploaded.is_empty() {
+            files
+                .delete_files(uploaded.into_iter().map(|(id, _)| id).collect())
+                .await?;
+        }
+        Ok(resumed)
+    }
+    async fn commit(&self, session: &SessionFiles, page_order: Vec<Uuid>) -> Result<ChapterObject> {
+        let mut builder = session
+            .client()
+            .upload()
+            .commit_session()
+            .session_id(session.session_id)
+            .page_order(page_order)
+            .volume(self.volume.clone())
+            .chapter(self.chapter.clone())
+            .title(self.title.clone())
+            .translated_language(self.translated_language);
+        if let Some(publish_at) = &self.publish_at {
+            builder = builder.publish_at(publish_at.clone());
+        }
+        builder.build()?.send().await
+    }
+    /// Check the pages against the [`limits`](ChapterUploadBuilder::limits), without uploading them.
+    ///
+    /// The pages are read one at a time.
+    pub async fn validate(&self) -> Result<ValidationReport> {
+        validate_pages(&self.pages, &self.limits).await
+    }
+    /// Read and validate the pages, see [`read_pages`].
+    async fn read(&self) -> Result<Vec<PageFile>> {
+        if self.pages.is_empty() {
+            return Err(Error::RequestBuilderError(
+                "a chapter needs at least one page".to_string(),
+            ));
+        }
+        read_pages(self.pages.iter().enumerate(), &self.limits).await
+    }
+    /// Upload the pages not `uploaded` yet and commit the session.
+    async fn finish(
+        &self,
+        session: &SessionFiles,
+        pages: Vec<PageFile>,
+        uploaded: Vec<Option<Uuid>>,
+    ) -> Result<ChapterObject> {
+        let missing: Vec<PageFile> = pages
+            .into_iter()
+            .filter(|page| uploaded[page.index].is_none())
+            .collect();
+        let res = match session.upload_pages(&missing).await {
+            Ok(ids) => {
+                let mut ids = ids.into_iter();
+                let page_order = uploaded
+                    .into_iter()
+                    .filter_map(|id| id.or_else(|| ids.next()))
+                    .collect();
+                self.commit(session, page_order).await
+            }
+            Err(e) => Err(e),
+        };
+        if res.is_err() {
+            // The original error matters more than a failed rollback
+            let _ = session.abandon().await;
+        }
+        res
+    }
+    /// Upload the chapter, returns the committed chapter.
+    ///
+    /// The pages are [validated](Self::validate) first, an invalid [`ValidationReport`]
+    /// is returned as an [`Error::InvalidUpload`] without starting an upload session.
+    /// The upload session is abandoned if any page can't be uploaded, or if the commit fails.
+    /// Each page is read once and kept in memory until the upload is over.
+    pub async fn upload(&self) -> Result<ChapterObject> {
+        let pages = self.read().await?;
+        let session = self.start_session().await?;
+        self.finish(&session, pages, vec![None; self.pages.len()])
+            .await
+    }
+    /// Upload the chapter to the upload session left open for the manga by an interrupted upload,
+    /// returns the committed chapter.
+    ///
+    /// The files already in the session are matched with the pages by their hash and size,
+    /// only the missing pages are uploaded and the other files are deleted.
+    /// Without an open session for the manga and groups, this is the same as [`upload`](Self::upload).
+    pub async fn resume(&self) -> Result<ChapterObject> {
+        let pages = self.read().await?;
+        let (session, uploaded) = match self.open_session().await? {
+            Some(session) => {
+                let files = self.session_files(session.id);
+                match self.resumed_pages(&session, &files, &pages).await {
+                    Ok(uploaded) => (files, uploaded),
+                    Err(e) => {
+                        let _ = files.abandon().await;
+                        return Err(e);
+                    }
+                }
+            }
+            None => (self.start_session().await?, vec![None; self.pages.len()]),
+        };
+        self.finish(&session, pages, uploaded).await
+    }
+}
+
+impl ChapterUploadBuilder {
+    /// Upload the pages of `source`, see [`ChapterSource::open`].
+    ///
+    /// The inferred volume, chapter, title and language only fill the values not set explicitly,
+    /// before or after this call.
+    pub fn source(mut self, source: ChapterSource) -> Self {
+        let metadata = source.metadata;
+        if self.volume.is_none() {
+            self.volume = Some(metadata.volume);
+        }
+        if self.chapter.is_none() {
+            self.chapter = Some(metadata.chapter);
+        }
+        if self.title.is_none() {
+            self.title = Some(metadata.title);
+        }
+        if let (None, Some(language)) = (self.translated_language, metadata.language) {
+            self.translated_language = Some(language);
+        }
+        self.pages = Some(source.pages);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use url::Url;
+    use wiremock::matchers::{method, path, path_regex};
+    use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+    use super::*;
+    use crate::v5::AuthTokens;
+    use crate::HttpClient;
+
+    fn http_client(server: &MockServer) -> HttpClientRef {
+        let http_client = HttpClient::builder()
+            .base_url(Url::parse(&server.uri()).unwrap())
+            .auth_tokens(AuthTokens {
+                session: "sessiontoken".to_string(),
+                refresh: "refreshtoken".to_string(),
+            })
+            .build()
+            .unwrap();
+        MangaDexClient::new_with_http_client(http_client).get_http_client()
+    }
+
+    fn png_bytes(height: u32) -> Vec<u8> {
+        let mut png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR".to_vec();
+        png.extend_from_slice(&800u32.to_be_bytes());
+        png.extend_from_slice(&height.to_be_bytes());
+        png
+    }
+
+    fn png(name: &str, height: u32) -> PageSource {
+        PageSource::bytes(name, png_bytes(height))
+    }
+
+    /// An upload session file holding `bytes`.
+    fn session_file(id: u128, bytes: &[u8]) -> serde_json::Value {
+        json!({
+            "id": Uuid::from_u128(id),
+            "type": "upload_session_file",
+            "attributes": {
+                "originalFileName": format!("{id}.png"),
+                "fileHash": hex(&Md5::digest(bytes)),
+                "fileSize": bytes.len(),
+                "mimeType": "image/png",
+                "source": "local",
+                "version": 1
+            }
+        })
+    }
+
+    fn session(id: Uuid, is_committed: bool) -> serde_json::Value {
+        json!({
+            "id": id,
+            "type": "upload_session",
+            "attributes": {
+                "isCommitted": is_committed,
+                "isProcessed": false,
+                "isDeleted": false,
+                "version": 1,
+                "createdAt": "2021-06-16T00:40:22+00:00",
+                "updatedAt": "2021-06-16T00:40:22+00:00"
+            },
+            "relationships": []
+        })
+    }
+
+    fn uploaded(ids: &[u128]) -> serde_json::Value {
+        json!({
+            "result": "ok",
+            "errors": [],
+            "data": ids.iter().map(|id| json!({
+                "id": Uuid::from_u128(*id),
+                "type": "upload_session_file",
+                "attributes": {
+                    "originalFileName": format!("{id}.png"),
+                    "fileHash": "e199c7d73af7a58e8a4d0263f03db660",
+                    "fileSize": 20,
+                    "mimeType": "image/png",
+                    "source": "local",
+                    "version": 1
+                },
+                "relationships": []
+            })).collect::<Vec<_>>()
+        })
+    }
+
+    fn chapter(id: Uuid) -> serde_json::Value {
+        json!({
+            "id": id,
+            "type": "chapter",
+            "attributes": {
+                "title": "The beginning",
+                "volume": "1",
+                "chapter": "1",
+                "pages": 12,
+                "translatedLanguage": "en",
+                "version": 1,
+                "createdAt": "2021-06-16T00:40:22+00:00",
+                "updatedAt": "2021-06-16T00:40:22+00:00",
+                "publishAt": "2021-06-16T00:40:22+00:00",
+                "readableAt": "2021-06-16T00:40:22+00:00"
+            },
+            "relationships": []
+        })
+    }
+
+    /// Number of files sent in each upload request, in order.
+    async fn files_per_request(server: &MockServer) -> Vec<usize> {
+        server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .filter(|request| {
+                request.method == wiremock::http::Method::Post
+                    && request.url.path() != "/upload/begin"
+            })
+            .map(|request| {
+                String::from_utf8_lossy(&request.body)
+                    .matches("filename=")
+                    .count()
+            })
+            .collect()
+    }
+
+    fn committed_page_order(requests: &[Request]) -> Vec<Uuid> {
+        let commit = requests
+            .iter()
+            .find(|request| request.url.path().ends_with("/commit"))
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&commit.body).unwrap();
+        serde_json::from_value(body["pageOrder"].clone()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn no_open_session_is_not_an_error() -> anyhow::Result<()> {
+        let server = MockServer::start().await;
+        let http_client = http_client(&server);
+        #[cfg(not(feature = "multi-thread"))]
+        http_client
+            .borrow_mut()
+            .set_store(crate::utils::store::EntityStore::new());
+        #[cfg(feature = "multi-thread")]
+        http_client
+            .lock()
+            .await
+            .set_store(crate::utils::store::EntityStore::new());
+
+        Mock::given(method("GET"))
+            .and(path("/upload"))
+            .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+                "result": "error",
+                "errors": [{
+                    "id": Uuid::new_v4(),
+                    "status": 404,
+                    "title": "upload_session_not_found",
+                    "detail": "No upload session found"
+                }]
+            })))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/upload"))
+            .respond_with(ResponseTemplate::new(200))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/upload"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(session(Uuid::new_v4(), true)))
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"result": "ok"})))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        assert!(get_open_session(&http_client).await?.is_none());
+        assert!(get_open_session(&http_client).await?.is_none());
+        abandon_open_session(&http_client).await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn pages_are_uploaded_in_batches_and_committed_in_order() -> anyhow::Result<()> {
+        let server = MockServer::start().await;
+        let session_id = Uuid::new_v4();
+        let chapter_id = Uuid::new_v4();
+
+        Mock::given(method("GET"))
+            .and(path("/upload"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/upload/begin"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(session(session_id, false)))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(format!("/upload/{session_id}")))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(uploaded(&(1..=10).collect::<Vec<_>>())),
+            )
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(format!("/upload/{session_id}")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(uploaded(&[11, 12])))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path(format!("/upload/{session_id}/commit")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(chapter(chapter_id)))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let res = ChapterUploadBuilder::default()
+            .http_client(http_client(&server))
+            .manga_id(Uuid::new_v4())
+            .translated_language(Language::English)
+            .pages(
+                (1..=12)
+                    .map(|page| png(&format!("{page:02}.png"), 1200))
+                    .collect::<Vec<_>>(),
+            )
+            .build()?
+            .upload()
+            .await?;
+
+        assert_eq!(res.id, chapter_id);
+        assert_eq!(files_per_request(&server).await, [10, 2]);
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(
+            committed_page_order(&requests),
+            (1..=12).map(Uuid::from_u128).collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn invalid_pages_are_rejected_before_starting_a_session() -> anyhow::Result<()> {
+        let server = MockServer::start().await;
+
+        let res = ChapterUploadBuilder::default()
+            .http_client(http_client(&server))
+            .manga_id(Uuid::new_v4())
+            .translated_language(Language::English)
+            .add_page(png("01.png", 1200))
+            .add_page(png("02.png", 12_000))
+            .build()?
+            .upload()
+            .await;
+
+        let report = match res {
+            Err(Error::InvalidUpload(report)) => report.downcast::<ValidationReport>().unwrap(),
+            _ => panic!("the upload should be invalid"),
+        };
+        assert_eq!(report.rejected().count(), 1);
+        assert!(server.received_requests().await.unwrap().is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn the_session_is_abandoned_when_a_page_is_rejected() -> anyhow::Result<()> {
+        let server = MockServer::start().await;
+        let session_id = Uuid::new_v4();
+
+        Mock::given(method("GET"))
+            .and(path("/upload"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/upload/begin"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(session(session_id, false)))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(format!("/upload/{session_id}")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "result": "ok",
+                "errors": [{
+                    "id": Uuid::new_v4(),
+                    "status": 400,
+                    "title": "file_type_not_allowed",
+                    "detail": "File type not allowed"
+                }],
+                "data": []
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path_regex(r"^/upload/[0-9a-f-]+$"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"result": "ok"})))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(chapter(Uuid::new_v4())))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let res = ChapterUploadBuilder::default()
+            .http_client(http_client(&server))
+            .manga_id(Uuid::new_v4())
+            .translated_language(Language::English)
+            .add_page(png("01.png", 1200))
+            .build()?
+            .upload()
+            .await;
+
+        assert!(matches!(res, Err(Error::Api(_))));
+        let abandoned = server
+            .received_requests()
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|request| request.method == wiremock::http::Method::Delete)
+            .unwrap();
+        assert_eq!(abandoned.url.path(), format!("/upload/{session_id}"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn page_sources_are_named_after_their_file() {
+        assert_eq!(PageSource::file("./chapter/01.png").file_name(), "01.png");
+        assert_eq!(
+            PageSource::bytes("cover.jpg", vec![0xff, 0xd8]).file_name(),
+            "cover.jpg"
+        );
+    }
+
+    #[test]
+    fn pages_match_the_md5_or_sha256_file_hash() {
+        let file = |file_hash: &str, file_size| -> UploadSessionFileAttributes {
+            serde_json::from_value(serde_json::json!({
+                "originalFileName": "01.png",
+                "fileHash": file_hash,
+                "fileSize": file_size,
+                "mimeType": "image/png",
+                "source": "local",
+                "version": 1
+            }))
+            .unwrap()
+        };
+        let digests = PageDigests::new(b"abc");
+        assert!(digests.matches(&file("900150983CD24FB0D6963F7D28E17F72", 3)));
+        assert!(digests.matches(&file(
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            3
+        )));
+        assert!(!digests.matches(&file("900150983cd24fb0d6963f7d28e17f72", 4)));
+        assert!(!digests.matches(&file("e199c7d73af7a58e8a4d0263f03db660", 3)));
+    }
+
+    #[test]
+    fn only_server_errors_are_retried() {
+        assert!(is_retryable(&Error::ServerError(502, String::new())));
+        assert!(!is_retryable(&Error::ServerError(400, String::new())));
+        assert!(!is_retryable(&Error::MissingTokens));
+    }
+
+    #[tokio::test]
+    async fn resume_uploads_only_the_missing_pages() -> anyhow::Result<()> {
+        let server = MockServer::start().await;
+        let session_id = Uuid::new_v4();
+        let manga_id = Uuid::new_v4();
+        let group_id = Uuid::new_v4();
+
+        let mut open_session = session(session_id, false);
+        open_session["relationships"] = json!([
+            { "id": manga_id, "type": "manga" },
+            { "id": group_id, "type": "scanlation_group" },
+            session_file(1, &png_bytes(1200)),
+            session_file(2, &png_bytes(1000))
+        ]);
+        Mock::given(method("GET"))
+            .and(path("/upload"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(open_session))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/upload/begin"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(session(session_id, false)))
+            .expect(0)
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path(format!("/upload/{session_id}/batch")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"result": "ok"})))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(format!("/upload/{session_id}")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(uploaded(&[12, 13])))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path(format!("/upload/{session_id}/commit")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(chapter(Uuid::new_v4())))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        ChapterUploadBuilder::default()
+            .http_client(http_client(&server))
+            .manga_id(manga_id)
+            .add_group(group_id)
+            .translated_language(Language::English)
+            .add_page(png("01.png", 1200))
+            .add_page(png("02.png", 1400))
+            .add_page(png("03.png", 1600))
+            .build()?
+            .resume()
+            .await?;
+
+        assert_eq!(files_per_request(&server).await, [2]);
+        let requests = server.received_requests().await.unwrap();
+        let deleted = requests
+            .iter()
+            .find(|request| request.method == wiremock::http::Method::Delete)
+            .unwrap();
+        assert_eq!(
+            serde_json::from_slice::<Vec<Uuid>>(&deleted.body)?,
+            [Uuid::from_u128(2)]
+        );
+        assert_eq!(
+            committed_page_order(&requests),
+            [1, 12, 13].map(Uuid::from_u128)
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn resume_starts_over_when_the_groups_differ() -> anyhow::Result<()> {
+        let server = MockServer::start().await;
+        let stale_session_id = Uuid::new_v4();
+        let session_id = Uuid::new_v4();
+        let manga_id = Uuid::new_v4();
+
+        let mut open_session = session(stale_session_id, false);
+        open_session["relationships"] = json!([
+            { "id": manga_id, "type": "manga" },
+            { "id": Uuid::new_v4(), "type": "scanlation_group" },
+            session_file(1, &png_bytes(1200))
+        ]);
+        Mock::given(method("GET"))
+            .and(path("/upload"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(open_session))
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path(format!("/upload/{stale_session_id}")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"result": "ok"})))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/upload/begin"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(session(session_id, false)))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(format!("/upload/{session_id}")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(uploaded(&[11])))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path(format!("/upload/{session_id}/commit")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(chapter(Uuid::new_v4())))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        ChapterUploadBuilder::default()
+            .http_client(http_client(&server))
+            .manga_id(manga_id)
+            .add_group(Uuid::new_v4())
+            .translated_language(Language::English)
+            .add_page(png("01.png", 1200))
+            .build()?
+            .resume()
+            .await?;
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(committed_page_order(&requests), [Uuid::from_u128(11)]);
+
+        Ok(())
+    }
+}
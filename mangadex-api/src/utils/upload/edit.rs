@@ -0,0 +1,560 @@
+//! Edit the pages of a published chapter.
+//!
+//! [`ChapterEdit::open`] starts an edit session, whose [`EditSession::pages`] are the pages of the chapter.
+//! The pages can be replaced, inserted, deleted or reordered locally,
+//! nothing is sent until [`EditSession::commit`].
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use mangadex_api::utils::upload::PageSource;
+//! use mangadex_api::MangaDexClient;
+//! use uuid::Uuid;
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let client = MangaDexClient::default();
+//! // Log in first, editing requires authentication
+//!
+//! let mut edit = client
+//!     .upload()
+//!     .edit_chapter()
+//!     .chapter_id(Uuid::new_v4())
+//!     .build()?
+//!     .open()
+//!     .await?;
+//! // Fix a typo on the third page and drop the credits page
+//! edit.replace(2, PageSource::file("./fixed/03.png"))?;
+//! edit.delete(edit.pages().len() - 1)?;
+//! let chapter = edit.commit().await?;
+//! println!("chapter {} is now at version {}", chapter.id, chapter.attributes.version);
+//! # Ok(())
+//! # }
+//! ```
+
+use derive_builder::Builder;
+use mangadex_api_schema::v5::{
+    ChapterAttributes, ChapterObject, RelatedAttributes, Relationship, UploadSessionFileAttributes,
+    UploadSessionResponse,
+};
+use mangadex_api_types::error::{Error, Result};
+use mangadex_api_types::RelationshipType;
+use uuid::Uuid;
+
+use crate::utils::request_builder_error;
+use crate::{HttpClientRef, MangaDexClient};
+
+use super::progress::UploadProgressHandler;
+use super::{
+    abandon_open_session, read_pages, validate_pages, PageSource, SessionFiles, UploadLimits,
+    ValidationReport,
+};
+
+/// A page of an [`EditSession`].
+#[derive(Debug, Clone)]
+pub enum EditPage {
+    /// A file of the upload session, such as a page of the chapter.
+    Existing {
+        id: Uuid,
+        attributes: UploadSessionFileAttributes,
+    },
+    /// A page uploaded on commit.
+    New(PageSource),
+}
+
+impl EditPage {
+    /// The file of an upload session `relationship`, if it is one.
+    pub fn from_relationship(relationship: &Relationship) -> Option<Self> {
+        match (&relationship.type_, &relationship.attributes) {
+            (
+                RelationshipType::UploadSessionFile,
+                Some(RelatedAttributes::UploadSessionFile(attributes)),
+            ) => Some(Self::Existing {
+                id: relationship.id,
+                attributes: attributes.clone(),
+            }),
+            _ => None,
+        }
+    }
+    /// Collect the files of an upload session, in order.
+    pub fn from_session(session: &UploadSessionResponse) -> Vec<Self> {
+        session
+            .relationships
+            .iter()
+            .filter_map(Self::from_relationship)
+            .collect()
+    }
+}
+
+impl From<PageSource> for EditPage {
+    fn from(value: PageSource) -> Self {
+        Self::New(value)
+    }
+}
+
+#[derive(Clone, Builder)]
+#[builder(setter(into, strip_option), pattern = "owned")]
+#[non_exhaustive]
+pub struct ChapterEdit {
+    #[doc(hidden)]
+    #[builder(pattern = "immutable")]
+    http_client: HttpClientRef,
+    chapter_id: Uuid,
+    /// Number of times a new page is retried after a network error or a `5xx` response.
+    ///
+    /// Defaults to `3`.
+    #[builder(default = "3")]
+    retries: usize,
+    /// Abandon the upload session left over by a previous upload, only one session can be open at a time.
+    ///
+    /// Defaults to `true`.
+    #[builder(default = "true")]
+    abandon_stale_session: bool,
+    /// Limits the new pages are checked against before the commit.
+    ///
+    /// Defaults to the MangaDex limits.
+    #[builder(default)]
+    limits: UploadLimits,
+    /// Receives the progress of each new page.
+    #[builder(default)]
+    progress: Option<UploadProgressHandler>,
+}
+
+impl ChapterEdit {
+    /// Start an edit session for the latest version of the chapter.
+    pub async fn open(&self) -> Result<EditSession> {
+        let client = MangaDexClient::new_with_http_client_ref(self.http_client.clone());
+        let chapter = client
+            .chapter()
+            .get()
+            .chapter_id(self.chapter_id)
+            .build()
+            .map_err(request_builder_error)?
+            .send()
+            .await?
+            .data;
+        if self.abandon_stale_session {
+            abandon_open_session(&self.http_client).await?;
+        }
+        let session = client
+            .upload()
+            .start_edit_chapter_session()
+            .chapter_id(self.chapter_id)
+            .version(chapter.attributes.version)
+            .build()
+            .map_err(request_builder_error)?
+            .send()
+            .await?;
+        Ok(EditSession {
+            files: SessionFiles {
+                http_client: self.http_client.clone(),
+                session_id: session.id,
+                retries: self.retries,
+                progress: self.progress.clone(),
+            },
+            limits: self.limits,
+            pages: EditPage::from_session(&session),
+            removed: Vec::new(),
+            chapter: chapter.attributes,
+        })
+    }
+}
+
+fn no_page(index: usize, len: usize) -> Error {
+    Error::RequestBuilderError(format!(
+        "no page at index {index}, the chapter has {len} pages"
+    ))
+}
+
+/// An open edit session, see [`ChapterEdit::open`].
+///
+/// Pages are indexed from `0`.
+pub struct EditSession {
+    files: SessionFiles,
+    limits: UploadLimits,
+    pages: Vec<EditPage>,
+    /// Files of the session no longer in the pages, deleted on commit.
+    removed: Vec<Uuid>,
+    chapter: ChapterAttributes,
+}
+
+impl EditSession {
+    pub fn session_id(&self) -> Uuid {
+        self.files.session_id
+    }
+    /// The chapter being edited.
+    pub fn chapter(&self) -> &ChapterAttributes {
+        &self.chapter
+    }
+    /// The pages, in the order they'll be committed.
+    pub fn pages(&self) -> &[EditPage] {
+        &self.pages
+    }
+    fn remove(&mut self, page: &EditPage) {
+        if let EditPage::Existing { id, .. } = page {
+            self.removed.push(*id);
+        }
+    }
+    /// Replace the page at `index`, returns the replaced page.
+    pub fn replace<P: Into<PageSource>>(&mut self, index: usize, page: P) -> Result<EditPage> {
+        let len = self.pages.len();
+        let slot = self
+            .pages
+            .get_mut(index)
+            .ok_or_else(|| no_page(index, len))?;
+        let replaced = std::mem::replace(slot, EditPage::New(page.into()));
+        self.remove(&replaced);
+        Ok(replaced)
+    }
+    /// Insert a page at `index`, shifting the following pages.
+    pub fn insert<P: Into<PageSource>>(&mut self, index: usize, page: P) -> Result<()> {
+        if index > self.pages.len() {
+            return Err(no_page(index, self.pages.len()));
+        }
+        self.pages.insert(index, EditPage::New(page.into()));
+        Ok(())
+    }
+    /// Insert a page after the page at `index`.
+    pub fn insert_after<P: Into<PageSource>>(&mut self, index: usize, page: P) -> Result<()> {
+        if index >= self.pages.len() {
+            return Err(no_page(index, self.pages.len()));
+        }
+        self.insert(index + 1, page)
+    }
+    /// Add a page at the end.
+    pub fn push<P: Into<PageSource>>(&mut self, page: P) {
+        self.pages.push(EditPage::New(page.into()));
+    }
+    /// Delete the page at `index`, returns the deleted page.
+    pub fn delete(&mut self, index: usize) -> Result<EditPage> {
+        if index >= self.pages.len() {
+            return Err(no_page(index, self.pages.len()));
+        }
+        let deleted = self.pages.remove(index);
+        self.remove(&deleted);
+        Ok(deleted)
+    }
+    /// Reorder the pages, `order` lists the current index of each page in its new position.
+    ///
+    /// Fails unless each current index appears exactly once.
+    pub fn reorder(&mut self, order: &[usize]) -> Result<()> {
+        let mut seen = vec![false; self.pages.len()];
+        for &index in order {
+            match seen.get_mut(index) {
+                Some(seen) if !*seen => *seen = true,
+                Some(_) => {
+                    return Err(Error::RequestBuilderError(format!(
+                        "page {index} appears more than once in the new order"
+                    )))
+                }
+                None => return Err(no_page(index, self.pages.len())),
+            }
+        }
+        if order.len() != self.pages.len() {
+            return Err(Error::RequestBuilderError(format!(
+                "the new order has {} pages, the chapter has {}",
+                order.len(),
+                self.pages.len()
+            )));
+        }
+        let mut pages: Vec<Option<EditPage>> = self.pages.drain(..).map(Some).collect();
+        self.pages = order
+            .iter()
+            .filter_map(|&index| pages[index].take())
+            .collect();
+        Ok(())
+    }
+    fn new_pages(&self) -> impl Iterator<Item = &PageSource> {
+        self.pages.iter().filter_map(|page| match page {
+            EditPage::New(page) => Some(page),
+            EditPage::Existing { .. } => None,
+        })
+    }
+    /// The limits left for the new pages, once the kept pages are accounted for.
+    fn new_pages_limits(&self) -> UploadLimits {
+        let (kept, kept_size) = self
+            .pages
+            .iter()
+            .filter_map(|page| match page {
+                EditPage::Existing { attributes, .. } => Some(attributes.file_size),
+                EditPage::New(_) => None,
+            })
+            .fold((0, 0), |(count, size), file_size| {
+                (count + 1, size + file_size)
+            });
+        self.limits.remaining(kept, kept_size)
+    }
+    /// Check the new pages against the limits, once the kept pages are accounted for.
+    pub async fn validate(&self) -> Result<ValidationReport> {
+        validate_pages(self.new_pages(), &self.new_pages_limits()).await
+    }
+    async fn apply(&self) -> Result<ChapterObject> {
+        let new_pages = self
+            .pages
+            .iter()
+            .enumerate()
+            .filter_map(|(index, page)| match page {
+                EditPage::New(page) => Some((index, page)),
+                EditPage::Existing { .. } => None,
+            });
+        let new_pages = read_pages(new_pages, &self.new_pages_limits()).await?;
+        if !self.removed.is_empty() {
+            self.files.delete_files(self.removed.clone()).await?;
+        }
+        let mut uploaded = self.files.upload_pages(&new_pages).await?.into_iter();
+        let page_order: Vec<Uuid> = self
+            .pages
+            .iter()
+            .filter_map(|page| match page {
+                EditPage::Existing { id, .. } => Some(*id),
+                EditPage::New(_) => uploaded.next(),
+            })
+            .collect();
+        self.files
+            .client()
+            .upload()
+            .commit_session()
+            .session_id(self.files.session_id)
+            .page_order(page_order)
+            .volume(self.chapter.volume.clone())
+            .chapter(self.chapter.chapter.clone())
+            .title(Some(self.chapter.title.clone()).filter(|title| !title.is_empty()))
+            .translated_language(self.chapter.translated_language.language())
+            .build()?
+            .send()
+            .await
+    }
+    /// Delete the removed files in one request, upload the new pages and commit the session,
+    /// returns the new version of the chapter.
+    ///
+    /// The new pages are [validated](Self::validate) first, an invalid [`ValidationReport`]
+    /// is returned as an [`Error::InvalidUpload`].
+    /// The session is abandoned if anything fails.
+    pub async fn commit(self) -> Result<ChapterObject> {
+        if self.pages.is_empty() {
+            return Err(Error::RequestBuilderError(
+                "a chapter needs at least one page".to_string(),
+            ));
+        }
+        let res = self.apply().await;
+        if res.is_err() {
+            // The original error matters more than a failed rollback
+            let _ = self.files.abandon().await;
+        }
+        res
+    }
+    /// Abandon the session, leaving the chapter as it was.
+    pub async fn abandon(self) -> Result<()> {
+        self.files.abandon().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use url::Url;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+    use crate::v5::AuthTokens;
+    use crate::HttpClient;
+
+    fn file(id: u128) -> serde_json::Value {
+        serde_json::json!({
+            "id": Uuid::from_u128(id),
+            "type": "upload_session_file",
+            "attributes": {
+                "originalFileName": format!("{id}.png"),
+                "fileHash": "hash",
+                "fileSize": 1024,
+                "mimeType": "image/png",
+                "source": "remote",
+                "version": 1
+            }
+        })
+    }
+
+    fn ids(pages: &[EditPage]) -> Vec<String> {
+        pages
+            .iter()
+            .map(|page| match page {
+                EditPage::Existing { id, .. } => id.as_u128().to_string(),
+                EditPage::New(page) => page.file_name(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn edit_session_pages_are_its_files() {
+        let session: UploadSessionResponse = serde_json::from_value(serde_json::json!({
+            "id": Uuid::new_v4(),
+            "type": "upload_session",
+            "attributes": {
+                "isCommitted": false,
+                "isProcessed": false,
+                "isDeleted": false,
+                "version": 1,
+                "createdAt": "2021-06-16T00:40:22+00:00",
+                "updatedAt": "2021-06-16T00:40:22+00:00"
+            },
+            "relationships": [
+                { "id": Uuid::new_v4(), "type": "manga" },
+                file(1),
+                file(2)
+            ]
+        }))
+        .unwrap();
+
+        assert_eq!(ids(&EditPage::from_session(&session)), ["1", "2"]);
+    }
+
+    /// An edit session of a three page chapter, whose files are `1`, `2` and `3`.
+    fn edit_session(http_client: HttpClientRef, session_id: Uuid) -> EditSession {
+        let chapter: ChapterAttributes = serde_json::from_value(serde_json::json!({
+            "title": "",
+            "volume": null,
+            "chapter": "1",
+            "pages": 3,
+            "translatedLanguage": "en",
+            "externalUrl": null,
+            "version": 1,
+            "createdAt": "2021-06-16T00:40:22+00:00",
+            "updatedAt": null,
+            "publishAt": "2021-06-16T00:40:22+00:00",
+            "readableAt": "2021-06-16T00:40:22+00:00"
+        }))
+        .unwrap();
+        EditSession {
+            files: SessionFiles {
+                http_client,
+                session_id,
+                retries: 0,
+                progress: None,
+            },
+            limits: UploadLimits::default(),
+            pages: (1..=3)
+                .map(|id| serde_json::from_value(file(id)).unwrap())
+                .map(|relationship| EditPage::from_relationship(&relationship).unwrap())
+                .collect(),
+            removed: Vec::new(),
+            chapter,
+        }
+    }
+
+    #[test]
+    fn operations_keep_track_of_the_removed_files() -> Result<()> {
+        let mut session = edit_session(HttpClientRef::default(), Uuid::new_v4());
+
+        session.replace(0, PageSource::bytes("fixed.png", vec![0]))?;
+        session.insert_after(2, PageSource::bytes("extra.png", vec![0]))?;
+        session.delete(1)?;
+        assert_eq!(ids(session.pages()), ["fixed.png", "3", "extra.png"]);
+        session.reorder(&[2, 0, 1])?;
+        assert_eq!(ids(session.pages()), ["extra.png", "fixed.png", "3"]);
+        assert_eq!(session.removed, [Uuid::from_u128(1), Uuid::from_u128(2)]);
+
+        assert!(session.delete(3).is_err());
+        assert!(session
+            .insert_after(3, PageSource::bytes("a.png", vec![0]))
+            .is_err());
+        assert!(session.reorder(&[0, 0, 1]).is_err());
+        assert!(session.reorder(&[0, 1]).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn commit_deletes_the_removed_files_and_uploads_the_new_pages() -> anyhow::Result<()> {
+        let server = MockServer::start().await;
+        let http_client = HttpClient::builder()
+            .base_url(Url::parse(&server.uri())?)
+            .auth_tokens(AuthTokens {
+                session: "sessiontoken".to_string(),
+                refresh: "refreshtoken".to_string(),
+            })
+            .build()?;
+        let http_client = MangaDexClient::new_with_http_client(http_client).get_http_client();
+        let session_id = Uuid::new_v4();
+        let chapter_id = Uuid::new_v4();
+
+        Mock::given(method("DELETE"))
+            .and(path(format!("/upload/{session_id}/batch")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"result": "ok"})))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(format!("/upload/{session_id}")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "result": "ok",
+                "errors": [],
+                "data": [{
+                    "id": Uuid::from_u128(10),
+                    "type": "upload_session_file",
+                    "attributes": {
+                        "originalFileName": "fixed.png",
+                        "fileHash": "e199c7d73af7a58e8a4d0263f03db660",
+                        "fileSize": 24,
+                        "mimeType": "image/png",
+                        "source": "local",
+                        "version": 1
+                    },
+                    "relationships": []
+                }]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path(format!("/upload/{session_id}/commit")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": chapter_id,
+                "type": "chapter",
+                "attributes": {
+                    "title": "",
+                    "volume": null,
+                    "chapter": "1",
+                    "pages": 2,
+                    "translatedLanguage": "en",
+                    "version": 2,
+                    "createdAt": "2021-06-16T00:40:22+00:00",
+                    "updatedAt": "2021-06-16T00:40:22+00:00",
+                    "publishAt": "2021-06-16T00:40:22+00:00",
+                    "readableAt": "2021-06-16T00:40:22+00:00"
+                },
+                "relationships": []
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR".to_vec();
+        png.extend_from_slice(&800u32.to_be_bytes());
+        png.extend_from_slice(&1200u32.to_be_bytes());
+        let mut session = edit_session(http_client, session_id);
+        session.replace(0, PageSource::bytes("fixed.png", png))?;
+        session.delete(1)?;
+        let chapter = session.commit().await?;
+
+        assert_eq!(chapter.id, chapter_id);
+        let requests = server.received_requests().await.unwrap();
+        let deleted = requests
+            .iter()
+            .find(|request| request.method == wiremock::http::Method::Delete)
+            .unwrap();
+        assert_eq!(
+            serde_json::from_slice::<Vec<Uuid>>(&deleted.body)?,
+            [1, 2].map(Uuid::from_u128)
+        );
+        let commit = requests
+            .iter()
+            .find(|request| request.method == wiremock::http::Method::Put)
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&commit.body)?;
+        assert_eq!(
+            serde_json::from_value::<Vec<Uuid>>(body["pageOrder"].clone())?,
+            [10, 3].map(Uuid::from_u128)
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,196 @@
+//! Byte-level progress of the uploaded files.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use mangadex_api::utils::upload::progress::{UploadEventKind, UploadProgressHandler};
+//! use mangadex_api::utils::upload::PageSource;
+//! use mangadex_api::MangaDexClient;
+//! use mangadex_api_types::Language;
+//! use uuid::Uuid;
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let client = MangaDexClient::default();
+//! let (progress, mut events) = UploadProgressHandler::channel();
+//! tokio::spawn(async move {
+//!     while let Some(event) = events.recv().await {
+//!         if let UploadEventKind::Bytes { sent, size } = event.kind {
+//!             println!("{} : {sent} / {size}", event.target.file_name);
+//!         }
+//!     }
+//! });
+//! client
+//!     .upload()
+//!     .chapter()
+//!     .manga_id(Uuid::new_v4())
+//!     .translated_language(Language::English)
+//!     .add_page(PageSource::file("./chapter/01.png"))
+//!     .progress(progress)
+//!     .build()?
+//!     .upload()
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use mangadex_api_types::error::Error;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use uuid::Uuid;
+
+/// Size of the chunks the files are sent in, a [`UploadEventKind::Bytes`] event is sent for each.
+pub const PROGRESS_CHUNK_SIZE: usize = 64 * 1024;
+
+/// The file an [`UploadEvent`] is about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadTarget {
+    pub session_id: Uuid,
+    /// 0-based index of the file in the request, or of the page in a chapter upload.
+    pub index: usize,
+    pub file_name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum UploadEventKind {
+    /// `sent` bytes of the file were handed to the connection so far.
+    Bytes { sent: u64, size: u64 },
+    /// The server accepted the file.
+    Finished { session_file_id: Uuid },
+    /// The file was already uploaded to the resumed session and isn't sent again.
+    Resumed { session_file_id: Uuid },
+    /// The upload failed with `error` and is retried, `attempt` being the 1-based retry number.
+    Retried { attempt: usize, error: String },
+    /// The upload failed for good.
+    Failed { error: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadEvent {
+    pub target: UploadTarget,
+    pub kind: UploadEventKind,
+}
+
+/// Receives the [`UploadEvent`]s of an upload.
+///
+/// The callback is called inline by the upload, so it should return quickly.
+#[derive(Clone)]
+pub struct UploadProgressHandler(Arc<dyn Fn(UploadEvent) + Send + Sync>);
+
+impl UploadProgressHandler {
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: Fn(UploadEvent) + Send + Sync + 'static,
+    {
+        Self(Arc::new(callback))
+    }
+    /// Send the events to a channel.
+    ///
+    /// Events are dropped once the receiver is closed.
+    pub fn channel() -> (Self, UnboundedReceiver<UploadEvent>) {
+        let (sender, receiver) = unbounded_channel();
+        (
+            Self::new(move |event| {
+                let _ = sender.send(event);
+            }),
+            receiver,
+        )
+    }
+    pub fn emit(&self, event: UploadEvent) {
+        (self.0)(event)
+    }
+    pub(crate) fn emit_kind(&self, target: UploadTarget, kind: UploadEventKind) {
+        self.emit(UploadEvent { target, kind })
+    }
+    pub(crate) fn retried(&self, target: UploadTarget, attempt: usize, error: &Error) {
+        self.emit_kind(
+            target,
+            UploadEventKind::Retried {
+                attempt,
+                error: error.to_string(),
+            },
+        )
+    }
+    pub(crate) fn failed(&self, target: UploadTarget, error: &Error) {
+        self.emit_kind(
+            target,
+            UploadEventKind::Failed {
+                error: error.to_string(),
+            },
+        )
+    }
+    /// Stream `file` in chunks of [`PROGRESS_CHUNK_SIZE`] bytes, sending an event as each one is read.
+    pub(crate) fn stream(
+        &self,
+        target: UploadTarget,
+        file: Bytes,
+    ) -> impl Stream<Item = std::io::Result<Bytes>> + Send + 'static {
+        let handler = self.clone();
+        let size = file.len();
+        let chunks: Vec<(usize, usize)> = (0..size)
+            .step_by(PROGRESS_CHUNK_SIZE)
+            .map(|start| (start, (start + PROGRESS_CHUNK_SIZE).min(size)))
+            .collect();
+        futures::stream::iter(chunks).map(move |(start, end)| {
+            handler.emit_kind(
+                target.clone(),
+                UploadEventKind::Bytes {
+                    sent: end as u64,
+                    size: size as u64,
+                },
+            );
+            Ok(file.slice(start..end))
+        })
+    }
+}
+
+impl Debug for UploadProgressHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UploadProgressHandler")
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn streamed_files_report_the_bytes_sent() {
+        let (handler, mut events) = UploadProgressHandler::channel();
+        let target = UploadTarget {
+            session_id: Uuid::new_v4(),
+            index: 0,
+            file_name: "01.png".to_string(),
+        };
+        let file = Bytes::from(vec![0u8; PROGRESS_CHUNK_SIZE + 10]);
+        let chunks: Vec<Bytes> = handler
+            .stream(target.clone(), file.clone())
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+        drop(handler);
+
+        assert_eq!(chunks.concat(), file);
+        let mut kinds = Vec::new();
+        while let Some(event) = events.recv().await {
+            assert_eq!(event.target, target);
+            kinds.push(event.kind);
+        }
+        let size = file.len() as u64;
+        assert_eq!(
+            kinds,
+            vec![
+                UploadEventKind::Bytes {
+                    sent: PROGRESS_CHUNK_SIZE as u64,
+                    size
+                },
+                UploadEventKind::Bytes { sent: size, size },
+            ]
+        );
+    }
+}
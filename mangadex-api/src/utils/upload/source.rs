@@ -0,0 +1,513 @@
+//! Read the pages and metadata of a chapter from a directory of images or a comic archive.
+//!
+//! The metadata comes from the `ComicInfo.xml` next to the pages,
+//! completed from the name of the directory or archive.
+
+use std::cmp::Ordering;
+use std::io::{Cursor, Read, Seek};
+use std::path::Path;
+
+use mangadex_api_types::error::{Error, Result};
+use mangadex_api_types::Language;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use tokio::io::AsyncReadExt;
+
+use crate::image::ImageFormat;
+
+use super::PageSource;
+
+/// File name of the metadata file of comic archives.
+pub const COMIC_INFO_FILE_NAME: &str = "ComicInfo.xml";
+
+/// Compare file names in natural order, numbers being compared by value, so `p2` comes before `p10`.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    // Names differing only by case or leading zeros still get a stable order
+    natural_cmp_folded(a, b).then_with(|| a.cmp(b))
+}
+
+/// Compare ignoring case and leading zeros.
+fn natural_cmp_folded(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let take_number = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut digits = String::new();
+                    while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+                        digits.push(c);
+                    }
+                    digits
+                };
+                let x = take_number(&mut a);
+                let y = take_number(&mut b);
+                let x_value = x.trim_start_matches('0');
+                let y_value = y.trim_start_matches('0');
+                let ordering = x_value
+                    .len()
+                    .cmp(&y_value.len())
+                    .then_with(|| x_value.cmp(y_value));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+/// Chapter metadata found next to the pages.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChapterMetadata {
+    pub volume: Option<String>,
+    pub chapter: Option<String>,
+    pub title: Option<String>,
+    pub language: Option<Language>,
+}
+
+impl ChapterMetadata {
+    /// Fill the missing values from `other`.
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            volume: self.volume.or(other.volume),
+            chapter: self.chapter.or(other.chapter),
+            title: self.title.or(other.title),
+            language: self.language.or(other.language),
+        }
+    }
+    /// Read the metadata of a `ComicInfo.xml`.
+    ///
+    /// A malformed file only loses the values after the error.
+    pub fn from_comic_info(xml: &str) -> Self {
+        let mut reader = Reader::from_str(xml);
+        reader.trim_text(true);
+        let mut metadata = Self::default();
+        let mut language: Option<String> = None;
+        // The values are the children of the root element
+        let mut path: Vec<Vec<u8>> = Vec::new();
+        loop {
+            let text = match reader.read_event() {
+                Ok(Event::Start(element)) => {
+                    path.push(element.name().as_ref().to_vec());
+                    continue;
+                }
+                Ok(Event::End(_)) => {
+                    path.pop();
+                    continue;
+                }
+                Ok(Event::Text(text)) => match text.unescape() {
+                    Ok(text) => text.into_owned(),
+                    Err(_) => break,
+                },
+                Ok(Event::CData(text)) => String::from_utf8_lossy(&text).into_owned(),
+                Ok(Event::Eof) | Err(_) => break,
+                Ok(_) => continue,
+            };
+            let value = match path.as_slice() {
+                [_, name] => match name.as_slice() {
+                    b"Volume" => &mut metadata.volume,
+                    b"Number" => &mut metadata.chapter,
+                    b"Title" => &mut metadata.title,
+                    b"LanguageISO" => &mut language,
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            value.get_or_insert_with(String::new).push_str(&text);
+        }
+        for value in [
+            &mut metadata.volume,
+            &mut metadata.chapter,
+            &mut metadata.title,
+        ] {
+            *value = value
+                .take()
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty());
+        }
+        metadata.language = language
+            .map(|code| Language::from(code.as_str()))
+            .filter(|language| *language != Language::Unknown);
+        metadata
+    }
+    /// Guess the volume, chapter and title from a file or directory name,
+    /// like `Vol. 1 Ch. 10.5 - Title`, `v01 c010` or `[Group] Title 012`.
+    pub fn from_file_name(name: &str) -> Self {
+        let stem = match name.rsplit_once('.') {
+            Some((stem, extension))
+                if matches!(extension.to_lowercase().as_str(), "cbz" | "zip") =>
+            {
+                stem
+            }
+            _ => name,
+        };
+        // Bracketed tags, like the group name, aren't part of the numbers
+        let mut cleaned = String::new();
+        let mut depth = 0usize;
+        for c in stem.chars() {
+            match c {
+                '[' | '(' | '{' => depth += 1,
+                ']' | ')' | '}' => depth = depth.saturating_sub(1),
+                _ if depth == 0 => cleaned.push(if c == '_' { ' ' } else { c }),
+                _ => {}
+            }
+        }
+        let (head, title) = match cleaned.split_once(" - ") {
+            Some((head, title)) => (head.to_string(), Some(title.trim().to_string())),
+            None => (cleaned.clone(), None),
+        };
+        let volume = find_marked_number(&head, &["volume", "vol", "v"]);
+        let chapter = find_marked_number(&head, &["chapter", "ch", "c", "#"]).or_else(|| {
+            // A bare number ending the name, after the volume if any
+            let trailing: String = head
+                .trim_end()
+                .chars()
+                .rev()
+                .take_while(|c| c.is_ascii_digit() || *c == '.')
+                .collect::<Vec<char>>()
+                .into_iter()
+                .rev()
+                .collect();
+            let before = head.trim_end().len() - trailing.len();
+            let is_word = before == 0 || !head[..before].ends_with(char::is_alphanumeric);
+            if volume.is_none() && is_word {
+                normalize_number(&trailing)
+            } else {
+                None
+            }
+        });
+        let title =
+            title.filter(|title| !title.is_empty() && (volume.is_some() || chapter.is_some()));
+        Self {
+            volume,
+            chapter,
+            title,
+            language: None,
+        }
+    }
+}
+
+/// Strip the leading zeros of a number, like `010` or `10.50`, `None` if it isn't one.
+fn normalize_number(number: &str) -> Option<String> {
+    let number = number.trim_end_matches('.');
+    if number.is_empty()
+        || !number.starts_with(|c: char| c.is_ascii_digit())
+        || number.parse::<f64>().is_err()
+    {
+        return None;
+    }
+    let number = number.trim_start_matches('0');
+    if number.is_empty() || number.starts_with('.') {
+        Some(format!("0{number}"))
+    } else {
+        Some(number.to_string())
+    }
+}
+
+/// Find the number following one of `markers` at the start of a word, like `Vol. 2` or `c010`.
+fn find_marked_number(text: &str, markers: &[&str]) -> Option<String> {
+    let lower = text.to_lowercase();
+    for (start, _) in lower.char_indices() {
+        if lower[..start].ends_with(char::is_alphanumeric) {
+            continue;
+        }
+        for marker in markers {
+            if !lower[start..].starts_with(marker) {
+                continue;
+            }
+            let rest = lower[start + marker.len()..].trim_start_matches(['.', ' ']);
+            let number: String = rest
+                .chars()
+                .take_while(|c| c.is_ascii_digit() || *c == '.')
+                .collect();
+            if let Some(number) = normalize_number(&number) {
+                return Some(number);
+            }
+        }
+    }
+    None
+}
+
+/// Pages and metadata read from a directory or a comic archive.
+#[derive(Debug, Clone, Default)]
+pub struct ChapterSource {
+    /// Pages in natural order of their file names.
+    pub pages: Vec<PageSource>,
+    /// Metadata from the `ComicInfo.xml`, completed from the directory or archive name.
+    pub metadata: ChapterMetadata,
+    /// Files that aren't pages and were skipped.
+    pub warnings: Vec<String>,
+}
+
+impl ChapterSource {
+    /// Read a directory of images, or a CBZ or ZIP archive.
+    pub async fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        if tokio::fs::metadata(path).await?.is_dir() {
+            Self::from_dir(path).await
+        } else {
+            Self::from_archive(path).await
+        }
+    }
+    /// Read the images of a directory, its sub-directories are skipped.
+    pub async fn from_dir<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let dir = dir.as_ref();
+        let mut source = Self::default();
+        let mut pages: Vec<(String, PageSource)> = Vec::new();
+        let mut read_dir = tokio::fs::read_dir(dir).await?;
+        while let Some(entry) = read_dir.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if entry.file_type().await?.is_dir() {
+                source
+                    .warnings
+                    .push(format!("skipped {name}: sub-directories aren't read"));
+                continue;
+            }
+            if name.eq_ignore_ascii_case(COMIC_INFO_FILE_NAME) {
+                let xml = tokio::fs::read_to_string(entry.path()).await?;
+                source.metadata = ChapterMetadata::from_comic_info(&xml);
+                continue;
+            }
+            let mut header: Vec<u8> = Vec::with_capacity(16);
+            tokio::fs::File::open(entry.path())
+                .await?
+                .take(16)
+                .read_to_end(&mut header)
+                .await?;
+            match ImageFormat::from_magic_bytes(&header) {
+                Some(_) => pages.push((name, PageSource::File(entry.path()))),
+                None => source.warnings.push(not_an_image(&name)),
+            }
+        }
+        source.finish(pages, dir);
+        Ok(source)
+    }
+    /// Read the images of a CBZ or ZIP archive.
+    pub async fn from_archive<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let bytes = tokio::fs::read(&path).await?;
+        match tokio::task::spawn_blocking(move || Self::read_archive(Cursor::new(bytes), &path))
+            .await
+        {
+            Ok(d) => d,
+            Err(e) => Err(Error::UnexpectedError(anyhow::Error::new(e))),
+        }
+    }
+    /// Read the images of an archive, `path` is only used to infer the metadata.
+    pub fn read_archive<R, P>(reader: R, path: P) -> Result<Self>
+    where
+        R: Read + Seek,
+        P: AsRef<Path>,
+    {
+        let to_error = |e: zip::result::ZipError| Error::UnexpectedError(anyhow::Error::new(e));
+        let mut zip = zip::ZipArchive::new(reader).map_err(to_error)?;
+        let mut source = Self::default();
+        let mut pages: Vec<(String, PageSource)> = Vec::new();
+        for index in 0..zip.len() {
+            let mut file = zip.by_index(index).map_err(to_error)?;
+            if file.is_dir() {
+                continue;
+            }
+            let name = file.name().to_string();
+            let file_name = name.rsplit('/').next().unwrap_or(&name).to_string();
+            let mut bytes: Vec<u8> = Vec::with_capacity(file.size() as usize);
+            file.read_to_end(&mut bytes)?;
+            if file_name.eq_ignore_ascii_case(COMIC_INFO_FILE_NAME) {
+                source.metadata =
+                    ChapterMetadata::from_comic_info(&String::from_utf8_lossy(&bytes));
+            } else if ImageFormat::from_magic_bytes(&bytes).is_some() {
+                pages.push((name, PageSource::bytes(file_name, bytes)));
+            } else {
+                source.warnings.push(not_an_image(&name));
+            }
+        }
+        source.finish(pages, path.as_ref());
+        Ok(source)
+    }
+    fn finish(&mut self, mut pages: Vec<(String, PageSource)>, path: &Path) {
+        pages.sort_by(|a, b| natural_cmp(&a.0, &b.0));
+        self.pages = pages.into_iter().map(|(_, page)| page).collect();
+        self.warnings.sort_by(|a, b| natural_cmp(a, b));
+        if let Some(name) = path.file_name() {
+            self.metadata = std::mem::take(&mut self.metadata)
+                .or(ChapterMetadata::from_file_name(&name.to_string_lossy()));
+        }
+    }
+}
+
+fn not_an_image(name: &str) -> String {
+    format!("skipped {name}: not a JPEG, PNG, GIF or WebP image")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::export::cbz::{write_cbz, ComicInfo};
+
+    use super::*;
+
+    #[test]
+    fn pages_are_sorted_naturally() {
+        let mut names = vec!["p10.png", "p2.png", "P1.png", "p02b.png", "cover.jpg"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            names,
+            vec!["cover.jpg", "P1.png", "p2.png", "p02b.png", "p10.png"]
+        );
+    }
+
+    #[test]
+    fn metadata_from_file_names() {
+        let metadata =
+            |volume: Option<&str>, chapter: Option<&str>, title: Option<&str>| ChapterMetadata {
+                volume: volume.map(String::from),
+                chapter: chapter.map(String::from),
+                title: title.map(String::from),
+                language: None,
+            };
+        assert_eq!(
+            ChapterMetadata::from_file_name("Vol. 2 Ch. 10.5 - The Return.cbz"),
+            metadata(Some("2"), Some("10.5"), Some("The Return"))
+        );
+        assert_eq!(
+            ChapterMetadata::from_file_name("[Group] Title v01 c003"),
+            metadata(Some("1"), Some("3"), None)
+        );
+        assert_eq!(
+            ChapterMetadata::from_file_name("Chapter_012"),
+            metadata(None, Some("12"), None)
+        );
+        assert_eq!(
+            ChapterMetadata::from_file_name("Oshi no Ko 100"),
+            metadata(None, Some("100"), None)
+        );
+        assert_eq!(
+            ChapterMetadata::from_file_name("scans"),
+            metadata(None, None, None)
+        );
+    }
+
+    #[test]
+    fn archives_keep_the_images_and_read_comic_info() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!("mangadex-upload-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir)?;
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        for name in ["p10.png", "p2.png", "notes.txt"] {
+            let content = if name.ends_with(".png") {
+                png.clone()
+            } else {
+                b"notes".to_vec()
+            };
+            std::fs::write(dir.join(name), content)?;
+        }
+        let comic_info = ComicInfo {
+            number: Some("7".to_string()),
+            title: Some("Fish & Chips".to_string()),
+            language_iso: Some("fr".to_string()),
+            ..Default::default()
+        };
+        let files = ["p10.png", "p2.png", "notes.txt"].map(|name| dir.join(name));
+        let archive = write_cbz(Cursor::new(Vec::new()), &comic_info, &files)?.into_inner();
+        std::fs::remove_dir_all(&dir)?;
+
+        let source = ChapterSource::read_archive(Cursor::new(archive), "Vol. 3 Ch. 1.cbz")?;
+        let names: Vec<String> = source.pages.iter().map(PageSource::file_name).collect();
+        // `write_cbz` renames the pages in order
+        assert_eq!(names, vec!["001.png", "002.png"]);
+        assert_eq!(source.warnings, vec![not_an_image("003.txt")]);
+        assert_eq!(
+            source.metadata,
+            ChapterMetadata {
+                volume: Some("3".to_string()),
+                chapter: Some("7".to_string()),
+                title: Some("Fish & Chips".to_string()),
+                language: Some(Language::French),
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn comic_info_values_are_read_as_xml() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<ComicInfo xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+  <Series>Title &amp; Subtitle</Series>
+  <Title><![CDATA[Fish & <Chips>]]></Title>
+  <Number> 12.5 </Number>
+  <Volume/>
+  <LanguageISO>pt-BR</LanguageISO>
+  <Pages><Page Image="0"><Title>Nested</Title></Page></Pages>
+</ComicInfo>"#;
+        assert_eq!(
+            ChapterMetadata::from_comic_info(xml),
+            ChapterMetadata {
+                volume: None,
+                chapter: Some("12.5".to_string()),
+                title: Some("Fish & <Chips>".to_string()),
+                language: Some(Language::PortugueseBrazilian),
+            }
+        );
+        assert_eq!(
+            ChapterMetadata::from_comic_info("<ComicInfo><Number>3</Number><Title>"),
+            ChapterMetadata {
+                chapter: Some("3".to_string()),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn directories_keep_the_images_and_read_comic_info() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir()
+            .join(format!("mangadex-upload-{}", uuid::Uuid::new_v4()))
+            .join("Vol. 2 Ch. 5 - Homecoming");
+        std::fs::create_dir_all(dir.join("extras"))?;
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        std::fs::write(dir.join("p10.png"), &png)?;
+        std::fs::write(dir.join("p2.png"), &png)?;
+        std::fs::write(dir.join("notes.txt"), b"notes")?;
+        std::fs::write(
+            dir.join(COMIC_INFO_FILE_NAME),
+            "<ComicInfo><Number>6</Number><LanguageISO>fr</LanguageISO></ComicInfo>",
+        )?;
+
+        let source = ChapterSource::open(&dir).await;
+        std::fs::remove_dir_all(dir.parent().unwrap())?;
+        let source = source?;
+
+        assert_eq!(
+            source.pages,
+            vec![
+                PageSource::File(dir.join("p2.png")),
+                PageSource::File(dir.join("p10.png"))
+            ]
+        );
+        assert_eq!(
+            source.warnings,
+            vec![
+                "skipped extras: sub-directories aren't read".to_string(),
+                not_an_image("notes.txt")
+            ]
+        );
+        assert_eq!(
+            source.metadata,
+            ChapterMetadata {
+                volume: Some("2".to_string()),
+                chapter: Some("6".to_string()),
+                title: Some("Homecoming".to_string()),
+                language: Some(Language::French),
+            }
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,326 @@
+//! Check images against the MangaDex upload limits before sending them.
+
+use std::fmt;
+
+use crate::image::{ImageFormat, ImageInfo};
+
+const MIB: u64 = 1024 * 1024;
+
+/// Limits enforced by MangaDex on uploaded images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UploadLimits {
+    /// Maximum size of a single file, in bytes.
+    ///
+    /// Defaults to 20 MiB.
+    pub max_file_size: u64,
+    /// Maximum size of all the files of an upload session, in bytes.
+    ///
+    /// Defaults to 150 MiB.
+    pub max_session_size: u64,
+    /// Maximum width and height of an image, in pixels.
+    ///
+    /// Defaults to `10000`.
+    pub max_dimension: u32,
+    /// Maximum number of files in an upload session.
+    ///
+    /// Defaults to `500`.
+    pub max_files: usize,
+}
+
+impl Default for UploadLimits {
+    fn default() -> Self {
+        Self {
+            max_file_size: 20 * MIB,
+            max_session_size: 150 * MIB,
+            max_dimension: 10_000,
+            max_files: 500,
+        }
+    }
+}
+
+impl UploadLimits {
+    /// Limits left for new files, once `files` files of `size` bytes are in the upload session.
+    pub fn remaining(&self, files: usize, size: u64) -> Self {
+        Self {
+            max_session_size: self.max_session_size.saturating_sub(size),
+            max_files: self.max_files.saturating_sub(files),
+            ..*self
+        }
+    }
+}
+
+/// Why an image would be rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageProblem {
+    /// Not a JPEG, PNG, GIF or WebP image.
+    UnsupportedFormat,
+    /// The header is truncated or malformed.
+    UnreadableDimensions,
+    FileTooLarge {
+        size: u64,
+        max: u64,
+    },
+    DimensionsTooLarge {
+        width: u32,
+        height: u32,
+        max: u32,
+    },
+}
+
+impl fmt::Display for ImageProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedFormat => write!(f, "not a JPEG, PNG, GIF or WebP image"),
+            Self::UnreadableDimensions => write!(f, "the image dimensions can't be read"),
+            Self::FileTooLarge { size, max } => {
+                write!(f, "the file is {size} bytes, over the {max} bytes limit")
+            }
+            Self::DimensionsTooLarge { width, height, max } => {
+                write!(
+                    f,
+                    "the image is {width}x{height}, over the {max} pixels limit"
+                )
+            }
+        }
+    }
+}
+
+/// Validation of a single image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageReport {
+    /// Position of the image in the validated images.
+    pub index: usize,
+    pub name: String,
+    /// Size in bytes.
+    pub size: u64,
+    /// `None` if the format isn't supported or the header can't be read.
+    pub info: Option<ImageInfo>,
+    pub problems: Vec<ImageProblem>,
+}
+
+impl ImageReport {
+    pub fn new<N: Into<String>>(
+        index: usize,
+        name: N,
+        bytes: &[u8],
+        limits: &UploadLimits,
+    ) -> Self {
+        let size = bytes.len() as u64;
+        let info = ImageInfo::from_bytes(bytes);
+        let mut problems = Vec::new();
+        match &info {
+            Some(info)
+                if info.width > limits.max_dimension || info.height > limits.max_dimension =>
+            {
+                problems.push(ImageProblem::DimensionsTooLarge {
+                    width: info.width,
+                    height: info.height,
+                    max: limits.max_dimension,
+                })
+            }
+            Some(_) => {}
+            None if ImageFormat::from_magic_bytes(bytes).is_some() => {
+                problems.push(ImageProblem::UnreadableDimensions)
+            }
+            None => problems.push(ImageProblem::UnsupportedFormat),
+        }
+        if size > limits.max_file_size {
+            problems.push(ImageProblem::FileTooLarge {
+                size,
+                max: limits.max_file_size,
+            });
+        }
+        Self {
+            index,
+            name: name.into(),
+            size,
+            info,
+            problems,
+        }
+    }
+    pub fn is_valid(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Why the images can't be uploaded together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionProblem {
+    TooManyFiles { count: usize, max: usize },
+    SessionTooLarge { size: u64, max: u64 },
+}
+
+impl fmt::Display for SessionProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooManyFiles { count, max } => {
+                write!(f, "{count} files, over the {max} files limit")
+            }
+            Self::SessionTooLarge { size, max } => {
+                write!(f, "{size} bytes in total, over the {max} bytes limit")
+            }
+        }
+    }
+}
+
+/// Validation of the images of an upload, see [`ValidationReport::is_valid`].
+///
+/// It can be returned as an error, its message lists every problem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationReport {
+    pub images: Vec<ImageReport>,
+    /// Size of all the images, in bytes.
+    pub total_size: u64,
+    pub problems: Vec<SessionProblem>,
+}
+
+impl ValidationReport {
+    /// Validate named images.
+    pub fn new<'a, I, N>(images: I, limits: &UploadLimits) -> Self
+    where
+        I: IntoIterator<Item = (N, &'a [u8])>,
+        N: Into<String>,
+    {
+        Self::from_images(
+            images
+                .into_iter()
+                .enumerate()
+                .map(|(index, (name, bytes))| ImageReport::new(index, name, bytes, limits))
+                .collect(),
+            limits,
+        )
+    }
+    /// Check the images validated one by one against the session limits.
+    pub fn from_images(images: Vec<ImageReport>, limits: &UploadLimits) -> Self {
+        let total_size = images.iter().map(|image| image.size).sum();
+        let mut problems = Vec::new();
+        if images.len() > limits.max_files {
+            problems.push(SessionProblem::TooManyFiles {
+                count: images.len(),
+                max: limits.max_files,
+            });
+        }
+        if total_size > limits.max_session_size {
+            problems.push(SessionProblem::SessionTooLarge {
+                size: total_size,
+                max: limits.max_session_size,
+            });
+        }
+        Self {
+            images,
+            total_size,
+            problems,
+        }
+    }
+    pub fn is_valid(&self) -> bool {
+        self.problems.is_empty() && self.images.iter().all(ImageReport::is_valid)
+    }
+    /// The images that would be rejected.
+    pub fn rejected(&self) -> impl Iterator<Item = &ImageReport> {
+        self.images.iter().filter(|image| !image.is_valid())
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_valid() {
+            return write!(f, "{} valid images", self.images.len());
+        }
+        write!(f, "invalid images")?;
+        for problem in &self.problems {
+            write!(f, "; {problem}")?;
+        }
+        for image in self.rejected() {
+            for problem in &image.problems {
+                write!(f, "; {} (#{}): {problem}", image.name, image.index + 1)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationReport {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR".to_vec();
+        png.extend_from_slice(&width.to_be_bytes());
+        png.extend_from_slice(&height.to_be_bytes());
+        png
+    }
+
+    #[test]
+    fn every_problem_is_reported() {
+        let limits = UploadLimits {
+            max_file_size: 30,
+            max_session_size: 60,
+            max_files: 3,
+            ..Default::default()
+        };
+        let valid = png(800, 1200);
+        let tall = png(800, 12_000);
+        let large = [png(800, 1200), vec![0; 10]].concat();
+        let text = b"not an image".to_vec();
+        let truncated = b"GIF89a".to_vec();
+        let report = ValidationReport::new(
+            [
+                ("01.png", valid.as_slice()),
+                ("02.png", &tall),
+                ("03.png", &large),
+                ("04.txt", &text),
+                ("05.gif", &truncated),
+            ],
+            &limits,
+        );
+
+        assert!(!report.is_valid());
+        assert_eq!(
+            report.images[0].info.map(|info| info.format),
+            Some(ImageFormat::Png)
+        );
+        assert!(report.images[0].is_valid());
+        assert_eq!(
+            report.images[1].problems,
+            vec![ImageProblem::DimensionsTooLarge {
+                width: 800,
+                height: 12_000,
+                max: 10_000
+            }]
+        );
+        assert_eq!(
+            report.images[2].problems,
+            vec![ImageProblem::FileTooLarge { size: 34, max: 30 }]
+        );
+        assert_eq!(
+            report.images[3].problems,
+            vec![ImageProblem::UnsupportedFormat]
+        );
+        assert_eq!(
+            report.images[4].problems,
+            vec![ImageProblem::UnreadableDimensions]
+        );
+        assert_eq!(report.rejected().count(), 4);
+        assert_eq!(report.total_size, 24 + 24 + 34 + 12 + 6);
+        assert_eq!(
+            report.problems,
+            vec![
+                SessionProblem::TooManyFiles { count: 5, max: 3 },
+                SessionProblem::SessionTooLarge { size: 100, max: 60 }
+            ]
+        );
+        assert!(report
+            .to_string()
+            .contains("04.txt (#4): not a JPEG, PNG, GIF or WebP image"));
+    }
+
+    #[test]
+    fn remaining_limits_account_for_uploaded_files() {
+        let limits = UploadLimits::default().remaining(498, 140 * MIB);
+        assert_eq!(limits.max_files, 2);
+        assert_eq!(limits.max_session_size, 10 * MIB);
+        assert_eq!(limits.max_file_size, 20 * MIB);
+    }
+}
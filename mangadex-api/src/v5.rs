@@ -22,7 +22,7 @@ mod infrastructure;
 #[cfg(not(feature = "deserializable-endpoint"))]
 mod legacy;
 #[cfg(not(feature = "deserializable-endpoint"))]
-mod manga;
+pub(crate) mod manga;
 #[cfg(not(feature = "deserializable-endpoint"))]
 mod rating;
 #[cfg(not(feature = "deserializable-endpoint"))]
@@ -125,6 +125,10 @@ use crate::HttpClientRef;
 
 #[cfg(feature = "utils")]
 use crate::utils::download::DownloadBuilder;
+#[cfg(feature = "utils")]
+use crate::utils::export::ExportBuilder;
+#[cfg(feature = "utils")]
+use crate::utils::resolver::RelationshipResolver;
 
 /// API client to make requests to the MangaDex v5 API.
 #[derive(Clone, Debug)]
@@ -378,6 +382,18 @@ impl MangaDexClient {
     pub fn download(&self) -> DownloadBuilder {
         DownloadBuilder::new(self.http_client.clone())
     }
+
+    /// Export chapters, volumes or manga to formats used by offline readers.
+    #[cfg(feature = "utils")]
+    pub fn export(&self) -> ExportBuilder {
+        ExportBuilder::new(self.http_client.clone())
+    }
+
+    /// Get a resolver to fill unexpanded relationships with batched list requests.
+    #[cfg(feature = "utils")]
+    pub fn resolver(&self) -> RelationshipResolver {
+        RelationshipResolver::new(self.http_client.clone())
+    }
 }
 
 /// Create a new reference counted `HttpClient`.
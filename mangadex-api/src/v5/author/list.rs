@@ -153,8 +153,8 @@ mod tests {
         assert_eq!(author.attributes.name, author_name);
         assert_eq!(author.attributes.image_url, Some("".to_string()));
         assert_eq!(
-            author.attributes.biography.get(&Language::English),
-            Some(&author_biography)
+            author.attributes.biography.text(Language::English),
+            Some(author_biography.as_str())
         );
         assert_eq!(author.attributes.version, 1);
         assert_eq!(
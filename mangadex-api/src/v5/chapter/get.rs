@@ -135,7 +135,10 @@ mod tests {
         assert_eq!(res.data.attributes.volume, Some("1".to_string()));
         assert_eq!(res.data.attributes.chapter, Some("1.5".to_string()));
         assert_eq!(res.data.attributes.pages, 4);
-        assert_eq!(res.data.attributes.translated_language, Language::English);
+        assert_eq!(
+            res.data.attributes.translated_language.language(),
+            Language::English
+        );
         assert_eq!(res.data.attributes.version, 1);
         assert_eq!(
             res.data.attributes.created_at.to_string(),
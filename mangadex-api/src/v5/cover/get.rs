@@ -130,7 +130,14 @@ mod tests {
         assert_eq!(res.data.attributes.volume, Some("1".to_string()));
         assert_eq!(res.data.attributes.file_name, "1.jpg".to_string());
         assert_eq!(res.data.attributes.description, description);
-        assert_eq!(res.data.attributes.locale, Some(Language::English));
+        assert_eq!(
+            res.data
+                .attributes
+                .locale
+                .as_ref()
+                .map(|locale| locale.language()),
+            Some(Language::English)
+        );
         assert_eq!(res.data.attributes.version, 1);
         assert_eq!(
             res.data.attributes.created_at.to_string(),
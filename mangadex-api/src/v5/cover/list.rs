@@ -149,7 +149,14 @@ mod tests {
         assert_eq!(cover.attributes.volume, Some("1".to_string()));
         assert_eq!(cover.attributes.file_name, "1.jpg".to_string());
         assert_eq!(cover.attributes.description, description);
-        assert_eq!(cover.attributes.locale, Some(Language::English));
+        assert_eq!(
+            cover
+                .attributes
+                .locale
+                .as_ref()
+                .map(|locale| locale.language()),
+            Some(Language::English)
+        );
         assert_eq!(cover.attributes.version, 1);
         assert_eq!(
             cover.attributes.created_at.to_string(),
@@ -46,6 +46,9 @@ use reqwest::multipart::{Form, Part};
 use serde::Serialize;
 use uuid::Uuid;
 
+use crate::image::ImageFormat;
+#[cfg(feature = "utils")]
+use crate::utils::upload::{UploadLimits, ValidationReport};
 use crate::HttpClientRef;
 use mangadex_api_types::Language;
 
@@ -107,7 +110,19 @@ impl Endpoint for UploadCover {
     }
 
     fn multipart(&self) -> Option<Form> {
-        let part = Part::bytes(self.file.clone());
+        let format = ImageFormat::from_magic_bytes(&self.file);
+        let part = || {
+            Part::bytes(self.file.clone()).file_name(match format {
+                Some(format) => format!("cover.{}", format.extension()),
+                None => "cover".to_string(),
+            })
+        };
+        let part = match format {
+            Some(format) => part()
+                .mime_str(format.mime_type())
+                .unwrap_or_else(|_| part()),
+            None => part(),
+        };
         let mut form = Form::new().part("file", part);
 
         if let Some(volume) = &self.volume {
@@ -124,6 +139,11 @@ impl Endpoint for UploadCover {
 }
 
 impl UploadCover {
+    /// Check the cover against `limits` without sending it.
+    #[cfg(feature = "utils")]
+    pub fn validate(&self, limits: &UploadLimits) -> ValidationReport {
+        ValidationReport::new([("cover", self.file.as_ref())], limits)
+    }
     pub async fn send(&self) -> CoverResponse {
         #[cfg(not(feature = "multi-thread"))]
         {
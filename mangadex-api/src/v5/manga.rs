@@ -14,7 +14,7 @@ mod delete;
 #[cfg(not(feature = "deserializable-endpoint"))]
 mod delete_relation;
 #[cfg(not(feature = "deserializable-endpoint"))]
-mod feed;
+pub(crate) mod feed;
 #[cfg(not(feature = "deserializable-endpoint"))]
 mod follow;
 #[cfg(not(feature = "deserializable-endpoint"))]
@@ -190,14 +190,17 @@ mod tests {
         let manga = &res.data[0];
         assert_eq!(manga.id, manga_id);
         assert_eq!(
-            manga.attributes.title.get(&Language::English).unwrap(),
+            manga.attributes.title.text(Language::English).unwrap(),
             &manga_title
         );
         assert!(manga.attributes.alt_titles.is_empty());
         assert!(manga.attributes.description.is_empty());
         assert!(!manga.attributes.is_locked);
         assert_eq!(manga.attributes.links, None);
-        assert_eq!(manga.attributes.original_language, Language::Japanese);
+        assert_eq!(
+            manga.attributes.original_language.language(),
+            Language::Japanese
+        );
         assert_eq!(manga.attributes.last_volume, None);
         assert_eq!(manga.attributes.last_chapter, None);
         assert_eq!(
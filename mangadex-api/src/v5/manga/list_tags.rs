@@ -125,8 +125,8 @@ mod tests {
             Uuid::parse_str("0234a31e-a729-4e28-9d6a-3f87c4966b9e")?
         );
         assert_eq!(
-            oneshot.attributes.name.get(&Language::English),
-            Some(&"Oneshot".to_string())
+            oneshot.attributes.name.text(Language::English),
+            Some("Oneshot")
         );
         assert_eq!(oneshot.attributes.group, TagGroup::Format);
 
@@ -182,14 +182,17 @@ mod tests {
         assert_eq!(res.response, ResponseType::Entity);
         assert_eq!(res.data.id, manga_id);
         assert_eq!(
-            res.data.attributes.title.get(&Language::English).unwrap(),
+            res.data.attributes.title.text(Language::English).unwrap(),
             &manga_title
         );
         assert!(res.data.attributes.alt_titles.is_empty());
         assert!(res.data.attributes.description.is_empty());
         assert!(!res.data.attributes.is_locked);
         assert_eq!(res.data.attributes.links, None);
-        assert_eq!(res.data.attributes.original_language, Language::Japanese);
+        assert_eq!(
+            res.data.attributes.original_language.language(),
+            Language::Japanese
+        );
         assert_eq!(res.data.attributes.last_volume, None);
         assert_eq!(res.data.attributes.last_chapter, None);
         assert_eq!(
@@ -206,8 +209,8 @@ mod tests {
             res.data.attributes.tags[0]
                 .attributes
                 .name
-                .get(&Language::English),
-            Some(&"Action".to_string())
+                .text(Language::English),
+            Some("Action")
         );
         assert_eq!(
             res.data.attributes.created_at.to_string(),
@@ -123,7 +123,7 @@ mod tests {
         let reason = &res.data[0];
         assert_eq!(reason.id, report_id);
         assert_eq!(
-            reason.attributes.reason.get(&Language::English).unwrap(),
+            reason.attributes.reason.text(Language::English).unwrap(),
             &"Troll entry".to_string()
         );
         assert!(!reason.attributes.details_required);
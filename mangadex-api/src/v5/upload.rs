@@ -47,6 +47,9 @@ use crate::v5::upload::start_session::StartUploadSessionBuilder;
 use crate::v5::upload::upload_images::UploadImagesBuilder;
 use crate::HttpClientRef;
 
+#[cfg(feature = "utils")]
+use crate::utils::upload::{ChapterEditBuilder, ChapterUploadBuilder};
+
 /// Upload endpoint handler builder.
 #[derive(Debug)]
 pub struct UploadBuilder {
@@ -123,4 +126,16 @@ impl UploadBuilder {
     pub fn delete_images(&self) -> DeleteImagesBuilder {
         DeleteImagesBuilder::default().http_client(self.http_client.clone())
     }
+
+    /// Upload a whole chapter, see [`ChapterUpload`](crate::utils::upload::ChapterUpload).
+    #[cfg(feature = "utils")]
+    pub fn chapter(&self) -> ChapterUploadBuilder {
+        ChapterUploadBuilder::default().http_client(self.http_client.clone())
+    }
+
+    /// Edit the pages of a chapter, see [`ChapterEdit`](crate::utils::upload::ChapterEdit).
+    #[cfg(feature = "utils")]
+    pub fn edit_chapter(&self) -> ChapterEditBuilder {
+        ChapterEditBuilder::default().http_client(self.http_client.clone())
+    }
 }
@@ -49,6 +49,11 @@ use reqwest::multipart::{Form, Part};
 use serde::Serialize;
 use uuid::Uuid;
 
+use crate::image::ImageFormat;
+#[cfg(feature = "utils")]
+use crate::utils::upload::progress::{UploadProgressHandler, UploadTarget};
+#[cfg(feature = "utils")]
+use crate::utils::upload::{UploadLimits, ValidationReport};
 use crate::HttpClientRef;
 
 /// Upload images to the upload session.
@@ -77,6 +82,17 @@ pub struct UploadImages {
     /// Image bytes.
     #[builder(setter(each = "add_file"))]
     pub files: Vec<Cow<'static, [u8]>>,
+    /// File names of the images, in the same order as [`files`](Self::files).
+    ///
+    /// Images without a name are named after their position and format.
+    #[serde(default)]
+    #[builder(setter(each = "add_file_name"), default)]
+    pub file_names: Vec<String>,
+    /// Receives the bytes sent for each file.
+    #[cfg(feature = "utils")]
+    #[serde(skip)]
+    #[builder(default)]
+    pub progress: Option<UploadProgressHandler>,
 }
 
 // TODO: Come up with a way to generalize multipart form data for the `Endpoint` trait.
@@ -100,8 +116,20 @@ impl Endpoint for UploadImages {
     fn multipart(&self) -> Option<Form> {
         let mut form = Form::new();
 
-        for file in &self.files {
-            let part = Part::bytes(file.clone());
+        for (i, file) in self.files.iter().enumerate() {
+            let format = ImageFormat::from_magic_bytes(file);
+            let file_name = match (self.file_names.get(i), format) {
+                (Some(name), _) => name.clone(),
+                (None, Some(format)) => format!("{}.{}", i + 1, format.extension()),
+                (None, None) => (i + 1).to_string(),
+            };
+            let part = || self.part(i, file, &file_name).file_name(file_name.clone());
+            let part = match format {
+                Some(format) => part()
+                    .mime_str(format.mime_type())
+                    .unwrap_or_else(|_| part()),
+                None => part(),
+            };
             form = form.part("file", part);
         }
 
@@ -110,6 +138,44 @@ impl Endpoint for UploadImages {
 }
 
 impl UploadImages {
+    #[cfg(not(feature = "utils"))]
+    fn part(&self, _index: usize, file: &[u8], _file_name: &str) -> Part {
+        Part::bytes(file.to_vec())
+    }
+    /// Stream the file if the progress is reported.
+    #[cfg(feature = "utils")]
+    fn part(&self, index: usize, file: &[u8], file_name: &str) -> Part {
+        let progress = match &self.progress {
+            Some(progress) => progress,
+            None => return Part::bytes(file.to_vec()),
+        };
+        let file = bytes::Bytes::copy_from_slice(file);
+        let length = file.len() as u64;
+        let target = UploadTarget {
+            session_id: self.session_id,
+            index,
+            file_name: file_name.to_string(),
+        };
+        Part::stream_with_length(
+            reqwest::Body::wrap_stream(progress.stream(target, file)),
+            length,
+        )
+    }
+    /// Check the images against `limits` without sending them.
+    #[cfg(feature = "utils")]
+    pub fn validate(&self, limits: &UploadLimits) -> ValidationReport {
+        ValidationReport::new(
+            self.files.iter().enumerate().map(|(i, file)| {
+                let name = self
+                    .file_names
+                    .get(i)
+                    .cloned()
+                    .unwrap_or_else(|| (i + 1).to_string());
+                (name, file.as_ref())
+            }),
+            limits,
+        )
+    }
     pub async fn send(&self) -> UploadSessionFileResponse {
         #[cfg(not(feature = "multi-thread"))]
         let res = self.http_client.try_borrow()?.send_request(self).await?;
@@ -189,4 +255,60 @@ mod tests {
 
         Ok(())
     }
+
+    #[cfg(feature = "utils")]
+    #[tokio::test]
+    async fn upload_images_reports_the_bytes_sent() -> anyhow::Result<()> {
+        use crate::utils::upload::progress::{UploadEventKind, UploadProgressHandler};
+
+        let mock_server = MockServer::start().await;
+        let http_client: HttpClient = HttpClient::builder()
+            .base_url(Url::parse(&mock_server.uri())?)
+            .auth_tokens(AuthTokens {
+                session: "sessiontoken".to_string(),
+                refresh: "refreshtoken".to_string(),
+            })
+            .build()?;
+        let mangadex_client = MangaDexClient::new_with_http_client(http_client);
+
+        let session_id = Uuid::new_v4();
+        let response_body = json!({
+            "result": "ok",
+            "errors": [],
+            "data": [],
+        });
+
+        Mock::given(method("POST"))
+            .and(path_regex("/upload/[0-9a-fA-F-]+"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(response_body))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let (progress, mut events) = UploadProgressHandler::channel();
+        mangadex_client
+            .upload()
+            .upload_images()
+            .session_id(session_id)
+            .add_file(vec![0_u8; 3].into())
+            .add_file(vec![0_u8; 5].into())
+            .add_file_name("p01.jpg".to_string())
+            .progress(progress)
+            .build()?
+            .send()
+            .await?;
+
+        let mut sent = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let UploadEventKind::Bytes { sent: bytes, size } = event.kind {
+                sent.push((event.target.index, event.target.file_name, bytes, size));
+            }
+        }
+        assert_eq!(
+            sent,
+            vec![(0, "p01.jpg".to_string(), 3, 3), (1, "2".to_string(), 5, 5)]
+        );
+
+        Ok(())
+    }
 }